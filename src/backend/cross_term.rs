@@ -0,0 +1,411 @@
+//! A terminal backend on crossterm, with 24-bit colour, arrow and function keys, the mouse
+//! and resizing.
+
+use crate::{AppAction, GameState};
+use backend::events::EventQueue;
+use backend::key_map::Key;
+use backend::render::{self, Canvas, Paint, TextStyle};
+use backend::terminal::save_termios;
+use backend::{Error, GameBackend};
+use console::ConsoleAction;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::style::{self, Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{ExecutableCommand, QueueableCommand};
+use editor::{EditAction, Editor};
+use export::Colour;
+use grid::Grid;
+use library::{BrowseAction, Browser};
+use std::cell::RefCell;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::time::Duration;
+
+/// How long to wait for input before drawing the next frame.
+const POLL_TIME: Duration = Duration::from_millis(20);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ScreenCell {
+    ch: char,
+    paint: Option<Paint>,
+    style: TextStyle,
+}
+
+const BLANK: ScreenCell = ScreenCell {
+    ch: ' ',
+    paint: None,
+    style: TextStyle::Plain,
+};
+
+/// What the terminal shows, and what it will show after the next `flush`.
+struct Screen {
+    cols: usize,
+    rows: usize,
+    cells: Vec<ScreenCell>,
+    /// What's on the terminal now, or `None` if it has to be drawn again from scratch.
+    shown: Option<Vec<ScreenCell>>,
+}
+
+impl Screen {
+    fn new(cols: usize, rows: usize) -> Screen {
+        Screen {
+            cols,
+            rows,
+            cells: vec![BLANK; cols * rows],
+            shown: None,
+        }
+    }
+
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut ScreenCell> {
+        if x < self.cols && y < self.rows {
+            self.cells.get_mut(y * self.cols + x)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct CrossTerm {
+    out: RefCell<BufWriter<Stdout>>,
+    screen: RefCell<Screen>,
+    /// The actions from this frame's input that haven't been taken yet.
+    events: RefCell<EventQueue>,
+    viewport: RefCell<(usize, usize)>,
+    /// The size of the grid last drawn, so that clicks outside it can be ignored.
+    grid_size: RefCell<(usize, usize)>,
+    alive: Colour,
+    dead: Colour,
+}
+
+fn io_error(msg: &str) -> impl Fn(io::Error) -> Error + '_ {
+    move |err| Error::caused_by(msg.to_owned(), Box::new(err))
+}
+
+/// The key from a key event, with Ctrl and a letter made into a control character.
+fn key_of(event: &KeyEvent) -> Option<Key> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+    let key = match event.code {
+        KeyCode::Char(c) if event.modifiers.contains(KeyModifiers::CONTROL) => {
+            if !c.is_ascii_alphabetic() {
+                return None;
+            }
+            Key::Char((c.to_ascii_uppercase() as u8 & 0x1f) as char)
+        }
+        KeyCode::Char(c) => Key::Char(c),
+        KeyCode::Enter => Key::Char('\r'),
+        KeyCode::Esc => Key::Char('\x1b'),
+        KeyCode::Backspace => Key::Char('\x7f'),
+        KeyCode::Tab => Key::Char('\t'),
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::F(n) => Key::Function(n),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// The grid cell at a `column` and `row` of the screen, if it's on the grid, when the top
+/// `grid_rows` rows show a `width` by `height` grid from `viewport`.
+fn cell_at(
+    (column, row): (usize, usize),
+    grid_rows: usize,
+    (vx, vy): (usize, usize),
+    (width, height): (usize, usize),
+) -> Option<(usize, usize)> {
+    if row >= grid_rows {
+        return None;
+    }
+    let (x, y) = (vx + column, vy + row);
+    if x < width && y < height {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+impl CrossTerm {
+    /// Takes over the terminal until the `CrossTerm` is dropped, drawing live and dead
+    /// cells in the given colours.
+    pub fn new(alive: Colour, dead: Colour) -> Result<CrossTerm, Error> {
+        save_termios();
+        let (cols, rows) = terminal::size().map_err(io_error("Couldn't get terminal size"))?;
+        terminal::enable_raw_mode().map_err(io_error("Couldn't set up terminal"))?;
+        let mut out = BufWriter::new(io::stdout());
+        out.execute(EnterAlternateScreen)
+            .and_then(|out| out.execute(Hide))
+            .and_then(|out| out.execute(EnableMouseCapture))
+            .map_err(io_error("Couldn't set up terminal"))?;
+        Ok(CrossTerm {
+            out: RefCell::new(out),
+            screen: RefCell::new(Screen::new(cols as usize, rows as usize)),
+            events: RefCell::new(EventQueue::new()),
+            viewport: RefCell::new((0, 0)),
+            grid_size: RefCell::new((0, 0)),
+            alive,
+            dead,
+        })
+    }
+
+    fn colour(&self, paint: Paint) -> style::Color {
+        let Colour(r, g, b) = match paint {
+            Paint::Dead => self.dead,
+            Paint::Alive => self.alive,
+            Paint::Selected => Colour(40, 60, 200),
+            Paint::SelectedAlive => Colour(200, 0, 200),
+            Paint::Stamp => Colour(220, 200, 0),
+            Paint::Cursor => Colour(255, 255, 255),
+            Paint::CursorAlive => Colour(0, 200, 0),
+        };
+        style::Color::Rgb { r, g, b }
+    }
+
+    /// Reads the input that's waiting and queues its actions for `game_state`, dropping
+    /// actions left over from the last frame. Resizing is dealt with straight away.
+    fn read_events(&self, game_state: GameState) -> Result<(), io::Error> {
+        let mut events = self.events.borrow_mut();
+        events.clear();
+        let mut timeout = POLL_TIME;
+        while event::poll(timeout)? {
+            match event::read()? {
+                Event::Resize(cols, rows) => {
+                    *self.screen.borrow_mut() = Screen::new(cols as usize, rows as usize);
+                }
+                Event::Key(key) => {
+                    if let Some(k) = key_of(&key) {
+                        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+                        events.push_key(game_state, k, shift);
+                    }
+                }
+                Event::Mouse(mouse) if game_state == GameState::Editing => {
+                    for action in self.mouse_edit_actions(&mouse) {
+                        events.push_edit(action);
+                    }
+                }
+                _ => {}
+            }
+            timeout = Duration::from_millis(0);
+        }
+        Ok(())
+    }
+
+    /// The grid cell under a mouse event, if it's on the grid.
+    fn cell_under(&self, event: &MouseEvent) -> Option<(usize, usize)> {
+        cell_at(
+            (event.column as usize, event.row as usize),
+            render::grid_rows(self),
+            self.viewport(),
+            *self.grid_size.borrow(),
+        )
+    }
+
+    /// Clicking toggles a cell, or places the pending stamp, and dragging draws live cells
+    /// with the left button or dead ones with the right.
+    fn mouse_edit_actions(&self, event: &MouseEvent) -> Vec<EditAction> {
+        let (x, y) = match self.cell_under(event) {
+            Some(cell) => cell,
+            None => return Vec::new(),
+        };
+        let action = match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => EditAction::ToggleCell,
+            MouseEventKind::Drag(MouseButton::Left) => EditAction::AddCellAt { x, y },
+            MouseEventKind::Down(MouseButton::Right) | MouseEventKind::Drag(MouseButton::Right) => {
+                EditAction::KillCellAt { x, y }
+            }
+            _ => return Vec::new(),
+        };
+        vec![EditAction::MoveCursorTo { x, y }, action]
+    }
+
+    /// Writes the cells that have changed since the last frame to the terminal.
+    fn flush(&self) -> Result<(), io::Error> {
+        let mut screen = self.screen.borrow_mut();
+        let mut out = self.out.borrow_mut();
+        let shown = match screen.shown.take() {
+            Some(shown) => shown,
+            None => {
+                out.queue(ResetColor)?.queue(Clear(ClearType::All))?;
+                vec![BLANK; screen.cells.len()]
+            }
+        };
+        let cols = screen.cols.max(1);
+        for (i, (cell, old)) in screen.cells.iter().zip(&shown).enumerate() {
+            if cell == old {
+                continue;
+            }
+            out.queue(MoveTo((i % cols) as u16, (i / cols) as u16))?
+                .queue(SetAttribute(Attribute::Reset))?;
+            match cell.paint {
+                Some(paint) => out.queue(SetBackgroundColor(self.colour(paint)))?,
+                None => out.queue(ResetColor)?,
+            };
+            match cell.style {
+                TextStyle::Plain => &mut *out,
+                TextStyle::Bold => out.queue(SetAttribute(Attribute::Bold))?,
+                TextStyle::Reverse => out.queue(SetAttribute(Attribute::Reverse))?,
+            }
+            .queue(Print(cell.ch))?;
+        }
+        out.flush()?;
+        screen.shown = Some(screen.cells.clone());
+        Ok(())
+    }
+}
+
+impl Drop for CrossTerm {
+    fn drop(&mut self) {
+        let out = self.out.get_mut();
+        let _ = out
+            .execute(DisableMouseCapture)
+            .and_then(|out| out.execute(ResetColor))
+            .and_then(|out| out.execute(Show))
+            .and_then(|out| out.execute(LeaveAlternateScreen));
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Canvas for CrossTerm {
+    fn clear(&self) {
+        for cell in self.screen.borrow_mut().cells.iter_mut() {
+            *cell = BLANK;
+        }
+    }
+
+    fn paint(&self, x: usize, y: usize, paint: Paint) {
+        if let Some(cell) = self.screen.borrow_mut().get_mut(x, y) {
+            cell.paint = Some(paint);
+        }
+    }
+
+    fn print(&self, x: usize, y: usize, max_width: usize, text: &str, style: TextStyle) {
+        let mut screen = self.screen.borrow_mut();
+        for (i, c) in text.chars().take(max_width).enumerate() {
+            if let Some(cell) = screen.get_mut(x + i, y) {
+                cell.ch = c;
+                cell.style = style;
+            }
+        }
+    }
+}
+
+impl GameBackend for CrossTerm {
+    fn num_cols(&self) -> usize {
+        self.screen.borrow().cols
+    }
+
+    fn num_rows(&self) -> usize {
+        self.screen.borrow().rows
+    }
+
+    fn viewport(&self) -> (usize, usize) {
+        *self.viewport.borrow()
+    }
+
+    fn set_viewport(&self, x: usize, y: usize) {
+        *self.viewport.borrow_mut() = (x, y);
+    }
+
+    /// Reads this frame's input, so it must be called before `edit_actions`.
+    fn app_actions(&self, game_state: GameState) -> Box<dyn Iterator<Item = AppAction>> {
+        // a failed read is treated as no input, like the rustty backend does
+        let _ = self.read_events(game_state);
+        self.events.borrow_mut().take_app_actions()
+    }
+
+    fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>> {
+        self.events.borrow_mut().take_edit_actions()
+    }
+
+    /// Reads this frame's input, as `app_actions` isn't called while browsing.
+    fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>> {
+        let _ = self.read_events(GameState::Browsing);
+        self.events.borrow_mut().take_browse_actions()
+    }
+
+    /// Reads this frame's input, as `app_actions` isn't called at the console.
+    fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+        let _ = self.read_events(GameState::Console);
+        self.events.borrow_mut().take_console_actions()
+    }
+
+    fn draw_game(&self, grid: &Grid<u8>, status: &str) -> Result<(), Error> {
+        *self.grid_size.borrow_mut() = (grid.width(), grid.height());
+        render::render_game(self, grid);
+        render::render_status(self, status);
+        self.flush().map_err(io_error("Error drawing game"))
+    }
+
+    fn draw_editor(&self, editor: &Editor, grid: &Grid<u8>, status: &str) -> Result<(), Error> {
+        *self.grid_size.borrow_mut() = (grid.width(), grid.height());
+        render::render_editor(self, editor, grid);
+        render::render_status(self, status);
+        self.flush().map_err(io_error("Error drawing editor"))
+    }
+
+    fn draw_browser(&self, browser: &Browser) -> Result<(), Error> {
+        render::render_browser(self, browser);
+        self.flush().map_err(io_error("Error drawing browser"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Option<Key> {
+        key_of(&KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn keys_are_the_characters_the_terminal_would_send() {
+        let none = KeyModifiers::NONE;
+        assert_eq!(key(KeyCode::Char('a'), none), Some(Key::Char('a')));
+        assert_eq!(
+            key(KeyCode::Char('A'), KeyModifiers::SHIFT),
+            Some(Key::Char('A'))
+        );
+        assert_eq!(key(KeyCode::Enter, none), Some(Key::Char('\r')));
+        assert_eq!(key(KeyCode::Esc, none), Some(Key::Char('\x1b')));
+        assert_eq!(key(KeyCode::Backspace, none), Some(Key::Char('\x7f')));
+        assert_eq!(key(KeyCode::Tab, none), Some(Key::Char('\t')));
+        assert_eq!(key(KeyCode::Left, none), Some(Key::Left));
+        assert_eq!(key(KeyCode::F(5), none), Some(Key::Function(5)));
+        assert_eq!(key(KeyCode::Home, none), None);
+    }
+
+    #[test]
+    fn control_and_a_letter_is_a_control_character() {
+        let control = KeyModifiers::CONTROL;
+        assert_eq!(key(KeyCode::Char('z'), control), Some(Key::Char('\x1a')));
+        assert_eq!(key(KeyCode::Char('Z'), control), Some(Key::Char('\x1a')));
+        assert_eq!(key(KeyCode::Char('1'), control), None);
+    }
+
+    #[test]
+    fn releasing_a_key_does_nothing() {
+        let release = KeyEvent::new_with_kind(
+            KeyCode::Char('a'),
+            KeyModifiers::NONE,
+            KeyEventKind::Release,
+        );
+        assert_eq!(key_of(&release), None);
+    }
+
+    #[test]
+    fn the_cell_under_the_mouse_is_offset_by_the_viewport() {
+        // the bottom row of a 10 row screen is the status line
+        let under = |column, row| cell_at((column, row), 9, (3, 2), (8, 20));
+        assert_eq!(under(0, 0), Some((3, 2)));
+        assert_eq!(under(4, 8), Some((7, 10)));
+        // beyond the right of the grid, and on the status line
+        assert_eq!(under(5, 0), None);
+        assert_eq!(under(0, 9), None);
+        assert_eq!(cell_at((0, 5), 9, (0, 16), (8, 20)), None);
+    }
+}
@@ -0,0 +1,180 @@
+//! Input sorted into the actions it stands for as soon as it's read, so that each kind of
+//! action can be taken without looking through the rest.
+
+use crate::{AppAction, GameState};
+use backend::key_map::*;
+use console::ConsoleAction;
+use editor::EditAction;
+use library::BrowseAction;
+use std::mem;
+
+/// The actions from one frame's input that haven't been taken yet.
+#[derive(Debug, Default)]
+pub struct EventQueue {
+    app: Vec<AppAction>,
+    edit: Vec<EditAction>,
+    browse: Vec<BrowseAction>,
+    console: Vec<ConsoleAction>,
+}
+
+impl EventQueue {
+    pub fn new() -> EventQueue {
+        EventQueue::default()
+    }
+
+    /// Forgets actions from the last frame that weren't taken, such as editing keys
+    /// pressed just before leaving Edit mode.
+    pub fn clear(&mut self) {
+        self.app.clear();
+        self.edit.clear();
+        self.browse.clear();
+        self.console.clear();
+    }
+
+    /// Adds the action for a key press in the given state, if it has one. Every key is
+    /// part of the search while browsing or of the line at the console, and keys for the
+    /// whole game come before editing keys.
+    pub fn push_key(&mut self, game_state: GameState, key: Key, shift: bool) {
+        if game_state == GameState::Browsing {
+            self.browse.extend(map_key_press_to_browse_action(key));
+        } else if game_state == GameState::Console {
+            self.console.extend(map_key_press_to_console_action(key));
+        } else if let Some(action) = map_key_press_to_global_action(game_state, key) {
+            self.app.push(action);
+        } else if game_state == GameState::Editing {
+            self.edit.extend(map_key_press_to_edit_action(key, shift));
+        }
+    }
+
+    pub fn push_app(&mut self, action: AppAction) {
+        self.app.push(action);
+    }
+
+    pub fn push_edit(&mut self, action: EditAction) {
+        self.edit.push(action);
+    }
+
+    pub fn push_browse(&mut self, action: BrowseAction) {
+        self.browse.push(action);
+    }
+
+    pub fn push_console(&mut self, action: ConsoleAction) {
+        self.console.push(action);
+    }
+
+    pub fn take_app_actions(&mut self) -> Box<dyn Iterator<Item = AppAction>> {
+        Box::new(mem::take(&mut self.app).into_iter())
+    }
+
+    pub fn take_edit_actions(&mut self) -> Box<dyn Iterator<Item = EditAction>> {
+        Box::new(mem::take(&mut self.edit).into_iter())
+    }
+
+    pub fn take_browse_actions(&mut self) -> Box<dyn Iterator<Item = BrowseAction>> {
+        Box::new(mem::take(&mut self.browse).into_iter())
+    }
+
+    pub fn take_console_actions(&mut self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+        Box::new(mem::take(&mut self.console).into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A queue with the keys pressed in `state`, one after another.
+    fn pressed(state: GameState, keys: &str) -> EventQueue {
+        let mut events = EventQueue::new();
+        for c in keys.chars() {
+            events.push_key(state, Key::Char(c), false);
+        }
+        events
+    }
+
+    #[test]
+    fn keys_are_sorted_by_the_state_they_are_pressed_in() {
+        let mut editing = pressed(GameState::Editing, "q l");
+        assert_eq!(
+            editing.take_app_actions().collect::<Vec<_>>(),
+            [AppAction::Quit]
+        );
+        assert_eq!(
+            editing.take_edit_actions().collect::<Vec<_>>(),
+            [
+                EditAction::ToggleCell,
+                EditAction::MoveCursorBy { x: 1, y: 0 }
+            ]
+        );
+
+        // editing keys do nothing while the game runs
+        let mut running = pressed(GameState::Running, "q l");
+        assert_eq!(
+            running.take_app_actions().collect::<Vec<_>>(),
+            [AppAction::Quit]
+        );
+        assert_eq!(running.take_edit_actions().count(), 0);
+
+        // and every key is typed while browsing or at the console
+        let mut browsing = pressed(GameState::Browsing, "q\r");
+        assert_eq!(browsing.take_app_actions().count(), 0);
+        assert_eq!(
+            browsing.take_browse_actions().collect::<Vec<_>>(),
+            [BrowseAction::Type('q'), BrowseAction::Choose]
+        );
+        let mut console = pressed(GameState::Console, "q\r");
+        assert_eq!(console.take_app_actions().count(), 0);
+        assert_eq!(
+            console.take_console_actions().collect::<Vec<_>>(),
+            [ConsoleAction::Type('q'), ConsoleAction::Run]
+        );
+    }
+
+    #[test]
+    fn shift_moves_the_cursor_eight_cells() {
+        let mut events = EventQueue::new();
+        events.push_key(GameState::Editing, Key::Left, true);
+        assert_eq!(
+            events.take_edit_actions().collect::<Vec<_>>(),
+            [EditAction::MoveCursorBy { x: -8, y: 0 }]
+        );
+    }
+
+    #[test]
+    fn taking_one_kind_of_action_leaves_the_others() {
+        let mut events = EventQueue::new();
+        events.push_app(AppAction::Census);
+        events.push_edit(EditAction::Copy);
+        events.push_browse(BrowseAction::Next);
+        events.push_console(ConsoleAction::Complete);
+        assert_eq!(
+            events.take_edit_actions().collect::<Vec<_>>(),
+            [EditAction::Copy]
+        );
+        assert_eq!(events.take_edit_actions().count(), 0);
+        assert_eq!(
+            events.take_app_actions().collect::<Vec<_>>(),
+            [AppAction::Census]
+        );
+        assert_eq!(
+            events.take_browse_actions().collect::<Vec<_>>(),
+            [BrowseAction::Next]
+        );
+        assert_eq!(
+            events.take_console_actions().collect::<Vec<_>>(),
+            [ConsoleAction::Complete]
+        );
+    }
+
+    #[test]
+    fn clearing_forgets_every_kind_of_action() {
+        let mut events = pressed(GameState::Editing, "q ");
+        events.push_browse(BrowseAction::Next);
+        events.push_console(ConsoleAction::Complete);
+        events.clear();
+        assert_eq!(events.take_app_actions().count(), 0);
+        assert_eq!(events.take_edit_actions().count(), 0);
+        assert_eq!(events.take_browse_actions().count(), 0);
+        assert_eq!(events.take_console_actions().count(), 0);
+    }
+}
@@ -1,5 +1,23 @@
 use crate::{AppAction, GameState};
-use editor::EditAction;
+use console::ConsoleAction;
+use editor::{EditAction, Transform};
+use library::BrowseAction;
+use shapes::Shape;
+
+/// A key press from a backend that reports more than characters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Key {
+    /// A character, with Ctrl giving control characters as for `map_key_to_global_action`.
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Function(u8),
+}
+
+/// How far Shift and an arrow key move the cursor.
+const FAST_CURSOR_STEP: isize = 8;
 
 pub fn map_key_to_global_action(game_state: GameState, key: char) -> Option<AppAction> {
     match key {
@@ -7,6 +25,16 @@ pub fn map_key_to_global_action(game_state: GameState, key: char) -> Option<AppA
         '\r' if game_state == GameState::Editing => Some(AppAction::EditDone),
         '\r' => Some(AppAction::TogglePause),
         'e' if game_state != GameState::Editing => Some(AppAction::EditMode),
+        'b' if game_state == GameState::Editing => Some(AppAction::Browse),
+        's' => Some(AppAction::SaveSession),
+        'o' => Some(AppAction::LoadSession),
+        '+' | '=' => Some(AppAction::Faster),
+        '-' => Some(AppAction::Slower),
+        'n' => Some(AppAction::Census),
+        't' => Some(AppAction::ExportStats),
+        'G' => Some(AppAction::ToggleRecording),
+        'P' => Some(AppAction::Snapshot),
+        ':' => Some(AppAction::Console),
         _ => None,
     }
 }
@@ -19,9 +47,103 @@ pub fn map_key_to_edit_action(key: char) -> Option<EditAction> {
         'j' => Some(EditAction::MoveCursorBy { x: -1, y: 0 }),
         'l' => Some(EditAction::MoveCursorBy { x: 1, y: 0 }),
         ' ' => Some(EditAction::ToggleCell),
-        n if n.is_digit(10) => Some(EditAction::AddPreset {
-            index: n.to_string().parse().unwrap(),
+        'r' => Some(EditAction::TransformStamp(Transform::RotateClockwise)),
+        'R' => Some(EditAction::TransformStamp(Transform::RotateAnticlockwise)),
+        'm' => Some(EditAction::TransformStamp(Transform::FlipHorizontal)),
+        'M' => Some(EditAction::TransformStamp(Transform::FlipVertical)),
+        '\x1b' => Some(EditAction::CancelStamp),
+        'v' => Some(EditAction::ToggleSelection),
+        'y' => Some(EditAction::Copy),
+        'x' => Some(EditAction::Cut),
+        'p' => Some(EditAction::Paste),
+        'f' => Some(EditAction::FillSelection),
+        'd' => Some(EditAction::ClearSelection),
+        'D' => Some(EditAction::ClearOutsideSelection),
+        'g' => Some(EditAction::MoveSelection),
+        'w' => Some(EditAction::ExportClipboard("clipboard.rle".into())),
+        'L' => Some(EditAction::Draw(Shape::Line)),
+        'B' => Some(EditAction::Draw(Shape::Rectangle)),
+        'F' => Some(EditAction::Draw(Shape::FilledRectangle)),
+        'O' => Some(EditAction::Draw(Shape::Ellipse)),
+        'E' => Some(EditAction::Draw(Shape::FilledEllipse)),
+        'u' => Some(EditAction::FloodFill),
+        'S' => Some(EditAction::RandomSoup {
+            seed: None,
+            density: None,
+            symmetry: None,
         }),
+        'Y' => Some(EditAction::CycleSoupSymmetry),
+        '[' => Some(EditAction::ChangeSoupDensity(-0.05)),
+        ']' => Some(EditAction::ChangeSoupDensity(0.05)),
+        'z' => Some(EditAction::Undo),
+        'Z' => Some(EditAction::Redo),
+        _ => None,
+    }
+}
+
+pub fn map_key_to_browse_action(key: char) -> Option<BrowseAction> {
+    match key {
+        '\r' => Some(BrowseAction::Choose),
+        '\x1b' => Some(BrowseAction::Cancel),
+        '\x7f' | '\x08' => Some(BrowseAction::Backspace),
+        '\x0e' | '\t' => Some(BrowseAction::Next),
+        '\x10' => Some(BrowseAction::Previous),
+        c if !c.is_control() => Some(BrowseAction::Type(c)),
+        _ => None,
+    }
+}
+
+pub fn map_key_to_console_action(key: char) -> Option<ConsoleAction> {
+    match key {
+        '\r' => Some(ConsoleAction::Run),
+        '\x1b' => Some(ConsoleAction::Cancel),
+        '\x7f' | '\x08' => Some(ConsoleAction::Backspace),
+        '\t' => Some(ConsoleAction::Complete),
+        '\x10' => Some(ConsoleAction::Previous),
+        '\x0e' => Some(ConsoleAction::Next),
+        c if !c.is_control() => Some(ConsoleAction::Type(c)),
+        _ => None,
+    }
+}
+
+pub fn map_key_press_to_global_action(game_state: GameState, key: Key) -> Option<AppAction> {
+    match key {
+        Key::Char(c) => map_key_to_global_action(game_state, c),
+        Key::Function(2) => Some(AppAction::SaveSession),
+        Key::Function(3) => Some(AppAction::LoadSession),
+        // the same as Enter: pause and unpause, or leave Edit mode
+        Key::Function(5) => map_key_to_global_action(game_state, '\r'),
+        Key::Function(10) => Some(AppAction::Quit),
+        _ => None,
+    }
+}
+
+pub fn map_key_press_to_edit_action(key: Key, shift: bool) -> Option<EditAction> {
+    let step = if shift { FAST_CURSOR_STEP } else { 1 };
+    match key {
+        Key::Char(c) => map_key_to_edit_action(c),
+        Key::Up => Some(EditAction::MoveCursorBy { x: 0, y: -step }),
+        Key::Down => Some(EditAction::MoveCursorBy { x: 0, y: step }),
+        Key::Left => Some(EditAction::MoveCursorBy { x: -step, y: 0 }),
+        Key::Right => Some(EditAction::MoveCursorBy { x: step, y: 0 }),
+        Key::Function(_) => None,
+    }
+}
+
+pub fn map_key_press_to_browse_action(key: Key) -> Option<BrowseAction> {
+    match key {
+        Key::Char(c) => map_key_to_browse_action(c),
+        Key::Up => Some(BrowseAction::Previous),
+        Key::Down => Some(BrowseAction::Next),
+        _ => None,
+    }
+}
+
+pub fn map_key_press_to_console_action(key: Key) -> Option<ConsoleAction> {
+    match key {
+        Key::Char(c) => map_key_to_console_action(c),
+        Key::Up => Some(ConsoleAction::Previous),
+        Key::Down => Some(ConsoleAction::Next),
         _ => None,
     }
 }
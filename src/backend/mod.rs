@@ -1,11 +1,22 @@
 use crate::{AppAction, GameState};
+use backend::replay::External;
+use console::ConsoleAction;
 use editor::{EditAction, Editor};
 use grid::Grid;
+use library::{BrowseAction, Browser};
+use soup::Soup;
 use std::error;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
 
+pub(crate) mod cross_term;
+pub(crate) mod events;
 pub(crate) mod key_map;
+pub(crate) mod names;
+pub(crate) mod render;
+pub(crate) mod replay;
 pub(crate) mod terminal;
+pub(crate) mod web;
 
 #[derive(Debug)]
 pub struct Error {
@@ -26,12 +37,42 @@ impl Error {
     }
 }
 
-impl error::Error for Error {}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.cause.as_deref()
+    }
+}
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "Error: {}", self.msg)?;
-        Ok(())
+        match &self.cause {
+            Some(cause) => write!(f, "{}: {}", self.msg, cause),
+            None => write!(f, "{}", self.msg),
+        }
+    }
+}
+
+/// The backends that can be chosen at startup.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+    /// The original backend, on rustty, with eight colours.
+    Rustty,
+    /// 24-bit colour, arrow and function keys, the mouse and resizing.
+    Crossterm,
+    /// A page for web browsers, served over HTTP and WebSocket.
+    Web,
+}
+
+impl FromStr for Kind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Kind, Error> {
+        match s {
+            "rustty" => Ok(Kind::Rustty),
+            "crossterm" => Ok(Kind::Crossterm),
+            "web" => Ok(Kind::Web),
+            _ => Err(Error::new(format!("Unknown backend: {}", s))),
+        }
     }
 }
 
@@ -40,11 +81,43 @@ pub trait GameBackend {
 
     fn num_rows(&self) -> usize;
 
+    /// The grid cell shown in the top left corner of the screen.
+    fn viewport(&self) -> (usize, usize);
+
+    fn set_viewport(&self, x: usize, y: usize);
+
     fn app_actions(&self, game_state: GameState) -> Box<dyn Iterator<Item = AppAction>>;
 
     fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>>;
 
-    fn draw_game(&self, grid: &Grid<u8>) -> Result<(), Error>;
+    fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>>;
+
+    fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>>;
+
+    fn draw_game(&self, grid: &Grid<u8>, status: &str) -> Result<(), Error>;
 
-    fn draw_editor(&self, editor: &Editor, grid: &Grid<u8>) -> Result<(), Error>;
+    fn draw_editor(&self, editor: &Editor, grid: &Grid<u8>, status: &str) -> Result<(), Error>;
+
+    fn draw_browser(&self, browser: &Browser) -> Result<(), Error>;
+
+    /// A seed for a random soup, which a recording writes down to play back.
+    fn seed(&self) -> u64 {
+        Soup::random_seed()
+    }
+
+    /// Notes something done to the game other than with the backend's input, for a
+    /// recording.
+    fn record(&self, _external: &External) {}
+
+    /// What's to be done to the game this frame other than with the backend's input, when
+    /// it's played back.
+    fn replayed(&self) -> Vec<External> {
+        Vec::new()
+    }
+
+    /// Whether the board is changed to fit the screen when it's resized. A replay changes it
+    /// as it was changed when recording.
+    fn follows_screen(&self) -> bool {
+        true
+    }
 }
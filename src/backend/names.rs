@@ -0,0 +1,203 @@
+//! Actions by the names of their variants, for input that arrives as text and for
+//! recordings.
+
+use crate::{AppAction, GameState};
+use console::ConsoleAction;
+use editor::{EditAction, Transform};
+use library::BrowseAction;
+use shapes::Shape;
+
+static APP_ACTIONS: [(&str, AppAction); 14] = [
+    ("Quit", AppAction::Quit),
+    ("TogglePause", AppAction::TogglePause),
+    ("EditMode", AppAction::EditMode),
+    ("EditDone", AppAction::EditDone),
+    ("Browse", AppAction::Browse),
+    ("SaveSession", AppAction::SaveSession),
+    ("LoadSession", AppAction::LoadSession),
+    ("Faster", AppAction::Faster),
+    ("Slower", AppAction::Slower),
+    ("Census", AppAction::Census),
+    ("ExportStats", AppAction::ExportStats),
+    ("ToggleRecording", AppAction::ToggleRecording),
+    ("Snapshot", AppAction::Snapshot),
+    ("Console", AppAction::Console),
+];
+
+/// The edit actions that don't take anything.
+static PLAIN_EDIT_ACTIONS: [(&str, EditAction); 16] = [
+    ("ToggleCell", EditAction::ToggleCell),
+    ("Clear", EditAction::Clear),
+    ("CommitStamp", EditAction::CommitStamp),
+    ("CancelStamp", EditAction::CancelStamp),
+    ("ToggleSelection", EditAction::ToggleSelection),
+    ("Copy", EditAction::Copy),
+    ("Cut", EditAction::Cut),
+    ("Paste", EditAction::Paste),
+    ("FillSelection", EditAction::FillSelection),
+    ("ClearSelection", EditAction::ClearSelection),
+    ("ClearOutsideSelection", EditAction::ClearOutsideSelection),
+    ("MoveSelection", EditAction::MoveSelection),
+    ("FloodFill", EditAction::FloodFill),
+    ("CycleSoupSymmetry", EditAction::CycleSoupSymmetry),
+    ("Undo", EditAction::Undo),
+    ("Redo", EditAction::Redo),
+];
+
+static TRANSFORMS: [(&str, Transform); 4] = [
+    ("RotateClockwise", Transform::RotateClockwise),
+    ("RotateAnticlockwise", Transform::RotateAnticlockwise),
+    ("FlipHorizontal", Transform::FlipHorizontal),
+    ("FlipVertical", Transform::FlipVertical),
+];
+
+static SHAPES: [(&str, Shape); 5] = [
+    ("Line", Shape::Line),
+    ("Rectangle", Shape::Rectangle),
+    ("FilledRectangle", Shape::FilledRectangle),
+    ("Ellipse", Shape::Ellipse),
+    ("FilledEllipse", Shape::FilledEllipse),
+];
+
+/// The browse actions other than typing.
+static BROWSE_ACTIONS: [(&str, BrowseAction); 5] = [
+    ("Backspace", BrowseAction::Backspace),
+    ("Next", BrowseAction::Next),
+    ("Previous", BrowseAction::Previous),
+    ("Choose", BrowseAction::Choose),
+    ("Cancel", BrowseAction::Cancel),
+];
+
+/// The console actions other than typing.
+static CONSOLE_ACTIONS: [(&str, ConsoleAction); 6] = [
+    ("Backspace", ConsoleAction::Backspace),
+    ("Complete", ConsoleAction::Complete),
+    ("Previous", ConsoleAction::Previous),
+    ("Next", ConsoleAction::Next),
+    ("Run", ConsoleAction::Run),
+    ("Cancel", ConsoleAction::Cancel),
+];
+
+fn named<T: Clone>(names: &[(&str, T)], name: &str) -> Option<T> {
+    names
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .map(|(_, value)| value.clone())
+}
+
+/// The name of a value in a table that has them all.
+fn name_in<T: PartialEq>(names: &'static [(&'static str, T)], value: &T) -> &'static str {
+    names
+        .iter()
+        .find(|(_, v)| v == value)
+        .map(|&(name, _)| name)
+        .expect("every variant is named")
+}
+
+pub fn app_action_named(name: &str) -> Option<AppAction> {
+    named(&APP_ACTIONS, name)
+}
+
+pub fn app_action_name(action: AppAction) -> &'static str {
+    name_in(&APP_ACTIONS, &action)
+}
+
+/// The edit action with this name, given the cell or distance it takes if it takes one.
+pub fn edit_action_named(name: &str, at: Option<(isize, isize)>) -> Option<EditAction> {
+    let (x, y) = match at {
+        Some(at) => at,
+        None => return named(&PLAIN_EDIT_ACTIONS, name),
+    };
+    if name == "MoveCursorBy" {
+        return Some(EditAction::MoveCursorBy { x, y });
+    }
+    if x < 0 || y < 0 {
+        return None;
+    }
+    let (x, y) = (x as usize, y as usize);
+    let action = match name {
+        "MoveCursorTo" => EditAction::MoveCursorTo { x, y },
+        "ToggleCellAt" => EditAction::ToggleCellAt { x, y },
+        "KillCellAt" => EditAction::KillCellAt { x, y },
+        "AddCellAt" => EditAction::AddCellAt { x, y },
+        "FloodFillAt" => EditAction::FloodFillAt { x, y },
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// The name of an edit action's variant, e.g. `MoveCursorBy` for
+/// `MoveCursorBy { x: 1, y: 0 }`.
+pub fn edit_action_name(action: &EditAction) -> &'static str {
+    match action {
+        EditAction::MoveCursorBy { .. } => "MoveCursorBy",
+        EditAction::MoveCursorTo { .. } => "MoveCursorTo",
+        EditAction::ToggleCellAt { .. } => "ToggleCellAt",
+        EditAction::KillCellAt { .. } => "KillCellAt",
+        EditAction::AddCellAt { .. } => "AddCellAt",
+        EditAction::FloodFillAt { .. } => "FloodFillAt",
+        EditAction::AddPattern(_) => "AddPattern",
+        EditAction::TransformStamp(_) => "TransformStamp",
+        EditAction::ExportClipboard(_) => "ExportClipboard",
+        EditAction::Draw(_) => "Draw",
+        EditAction::DrawBetween { .. } => "DrawBetween",
+        EditAction::RandomSoup { .. } => "RandomSoup",
+        EditAction::ChangeSoupDensity(_) => "ChangeSoupDensity",
+        action => name_in(&PLAIN_EDIT_ACTIONS, action),
+    }
+}
+
+pub fn transform_named(name: &str) -> Option<Transform> {
+    named(&TRANSFORMS, name)
+}
+
+pub fn transform_name(transform: Transform) -> &'static str {
+    name_in(&TRANSFORMS, &transform)
+}
+
+pub fn shape_named(name: &str) -> Option<Shape> {
+    named(&SHAPES, name)
+}
+
+pub fn shape_name(shape: Shape) -> &'static str {
+    name_in(&SHAPES, &shape)
+}
+
+/// The browse action with this name, given the character it types if it's `Type`.
+pub fn browse_action_named(name: &str, c: Option<char>) -> Option<BrowseAction> {
+    match name {
+        "Type" => Some(BrowseAction::Type(c?)),
+        name => named(&BROWSE_ACTIONS, name),
+    }
+}
+
+pub fn browse_action_name(action: &BrowseAction) -> &'static str {
+    match action {
+        BrowseAction::Type(_) => "Type",
+        action => name_in(&BROWSE_ACTIONS, action),
+    }
+}
+
+/// The console action with this name, given the character it types if it's `Type`.
+pub fn console_action_named(name: &str, c: Option<char>) -> Option<ConsoleAction> {
+    match name {
+        "Type" => Some(ConsoleAction::Type(c?)),
+        name => named(&CONSOLE_ACTIONS, name),
+    }
+}
+
+pub fn console_action_name(action: &ConsoleAction) -> &'static str {
+    match action {
+        ConsoleAction::Type(_) => "Type",
+        action => name_in(&CONSOLE_ACTIONS, action),
+    }
+}
+
+/// Whether an action sent by name makes sense in this state, as its key would.
+pub fn applies_in(game_state: GameState, action: AppAction) -> bool {
+    match action {
+        AppAction::EditMode => game_state != GameState::Editing,
+        AppAction::EditDone | AppAction::Browse => game_state == GameState::Editing,
+        _ => true,
+    }
+}
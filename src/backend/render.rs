@@ -0,0 +1,337 @@
+//! Drawing the game, editor and pattern library, for any backend that can colour in
+//! character cells and print text.
+
+use backend::GameBackend;
+use editor::Editor;
+use grid::Grid;
+use library::Browser;
+use pattern::Pattern;
+
+/// What a cell on the screen shows, which each backend gives its own colour.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Paint {
+    Dead,
+    Alive,
+    /// A dead cell inside the selection.
+    Selected,
+    SelectedAlive,
+    /// Where the pending stamp will go.
+    Stamp,
+    /// The cursor over a dead cell.
+    Cursor,
+    CursorAlive,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextStyle {
+    Plain,
+    Bold,
+    Reverse,
+}
+
+/// A screen of character cells, with the bottom row kept for the status line.
+pub trait Canvas: GameBackend {
+    /// Blanks the whole screen.
+    fn clear(&self);
+
+    /// Colours in the cell at a screen position. Positions off the screen are ignored.
+    fn paint(&self, x: usize, y: usize, paint: Paint);
+
+    /// Prints text from a screen position, cut off after `max_width` characters.
+    fn print(&self, x: usize, y: usize, max_width: usize, text: &str, style: TextStyle);
+}
+
+/// The number of rows available for the grid, above the status line.
+pub fn grid_rows<C: Canvas + ?Sized>(canvas: &C) -> usize {
+    canvas.num_rows().saturating_sub(1)
+}
+
+/// Where a grid cell is on the screen, if it's in view.
+pub fn screen_position<C: Canvas + ?Sized>(
+    canvas: &C,
+    x: usize,
+    y: usize,
+) -> Option<(usize, usize)> {
+    let (vx, vy) = canvas.viewport();
+    if x < vx || y < vy || x - vx >= canvas.num_cols() || y - vy >= grid_rows(canvas) {
+        None
+    } else {
+        Some((x - vx, y - vy))
+    }
+}
+
+/// Moves the viewport as little as possible to bring a cell into view.
+pub fn scroll_to<C: Canvas + ?Sized>(canvas: &C, x: usize, y: usize) {
+    let (cols, rows) = (canvas.num_cols().max(1), grid_rows(canvas).max(1));
+    let (mut vx, mut vy) = canvas.viewport();
+    vx = vx.min(x).max((x + 1).saturating_sub(cols));
+    vy = vy.min(y).max((y + 1).saturating_sub(rows));
+    canvas.set_viewport(vx, vy);
+}
+
+/// Colours in a grid cell, if it's in view.
+fn paint_cell<C: Canvas + ?Sized>(canvas: &C, x: usize, y: usize, paint: Paint) {
+    if let Some((x, y)) = screen_position(canvas, x, y) {
+        canvas.paint(x, y, paint);
+    }
+}
+
+pub fn render_game<C: Canvas + ?Sized>(canvas: &C, grid: &Grid<u8>) {
+    // grids larger than the screen are clipped to the viewport
+    let (vx, vy) = canvas.viewport();
+    let width = grid.width().saturating_sub(vx).min(canvas.num_cols());
+    let height = grid.height().saturating_sub(vy).min(grid_rows(canvas));
+    canvas.clear();
+    for x in 0..width {
+        for y in 0..height {
+            let paint = if grid.get(vx + x, vy + y) == 1 {
+                Paint::Alive
+            } else {
+                Paint::Dead
+            };
+            canvas.paint(x, y, paint);
+        }
+    }
+}
+
+pub fn render_editor<C: Canvas + ?Sized>(canvas: &C, editor: &Editor, grid: &Grid<u8>) {
+    let (x, y) = editor.get_cursor();
+    scroll_to(canvas, x, y);
+    render_game(canvas, grid);
+    if let Some(selection) = editor.selection() {
+        for j in selection.y..selection.y + selection.height {
+            for i in selection.x..selection.x + selection.width {
+                let paint = if grid.get(i, j) == 1 {
+                    Paint::SelectedAlive
+                } else {
+                    Paint::Selected
+                };
+                paint_cell(canvas, i, j, paint);
+            }
+        }
+    }
+    if let Some(stamp) = editor.stamp() {
+        // a ghost of the pending stamp, wrapped the same way as when it's placed
+        let (w, h) = (grid.width(), grid.height());
+        for &(i, j) in &stamp.pattern().cells {
+            paint_cell(canvas, (x + i) % w, (y + j) % h, Paint::Stamp);
+        }
+    }
+    let paint = if grid.get(x, y) == 1 {
+        Paint::CursorAlive
+    } else {
+        Paint::Cursor
+    };
+    paint_cell(canvas, x, y, paint);
+}
+
+/// Draws the status line over the bottom row of the screen.
+pub fn render_status<C: Canvas + ?Sized>(canvas: &C, status: &str) {
+    let (cols, rows) = (canvas.num_cols(), canvas.num_rows());
+    let line = format!("{:width$}", status, width = cols);
+    canvas.print(0, rows.saturating_sub(1), cols, &line, TextStyle::Reverse);
+}
+
+/// Draws `pattern` scaled down to fit in the given box, if necessary.
+fn render_thumbnail<C: Canvas + ?Sized>(
+    canvas: &C,
+    pattern: &Pattern,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let scale = pattern
+        .width
+        .div_ceil(width)
+        .max(pattern.height.div_ceil(height))
+        .max(1);
+    for &(i, j) in &pattern.cells {
+        canvas.paint(x + i / scale, y + j / scale, Paint::Alive);
+    }
+}
+
+pub fn render_browser<C: Canvas + ?Sized>(canvas: &C, browser: &Browser) {
+    canvas.clear();
+    let (cols, rows) = (canvas.num_cols(), canvas.num_rows());
+    let mut title = format!("Patterns ({})", browser.library().patterns().len());
+    if browser.library().skipped() > 0 {
+        title += &format!(", {} files skipped", browser.library().skipped());
+    }
+    title += " - type to search, ^N/^P to move, Enter to stamp, Esc to cancel";
+    canvas.print(0, 0, cols, &title, TextStyle::Bold);
+    canvas.print(
+        0,
+        1,
+        cols,
+        &format!("> {}_", browser.query()),
+        TextStyle::Plain,
+    );
+
+    let list_width = (cols / 2).min(40);
+    let list_rows = rows.saturating_sub(3);
+    let matches = browser.matches();
+    let selected = browser.selected_index();
+    let first = (selected + 1).saturating_sub(list_rows);
+    for (i, pattern) in matches.iter().enumerate().skip(first).take(list_rows) {
+        let style = if i == selected {
+            TextStyle::Reverse
+        } else {
+            TextStyle::Plain
+        };
+        let line = format!("{:width$}", pattern.name, width = list_width);
+        canvas.print(0, 3 + i - first, list_width, &line, style);
+    }
+
+    if let Some(pattern) = browser.selected() {
+        let x = list_width + 2;
+        let mut info = format!("{} ({}x{})", pattern.name, pattern.width, pattern.height);
+        if let Some(rule) = &pattern.rule {
+            info += &format!(" {}", rule);
+        }
+        canvas.print(x, 2, cols.saturating_sub(x), &info, TextStyle::Bold);
+        let comments = pattern.comments.len().min(3);
+        for (i, comment) in pattern.comments.iter().take(comments).enumerate() {
+            canvas.print(x, 3 + i, cols.saturating_sub(x), comment, TextStyle::Plain);
+        }
+        let top = 4 + comments;
+        render_thumbnail(
+            canvas,
+            pattern,
+            x,
+            top,
+            cols.saturating_sub(x + 1),
+            rows.saturating_sub(top + 1),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AppAction, GameState};
+    use backend::Error;
+    use console::ConsoleAction;
+    use editor::EditAction;
+    use library::BrowseAction;
+    use std::cell::RefCell;
+    use std::iter;
+
+    /// A screen that remembers what was painted and printed on each row.
+    struct Screen {
+        cols: usize,
+        rows: usize,
+        viewport: RefCell<(usize, usize)>,
+        painted: RefCell<Vec<(usize, usize)>>,
+        printed: RefCell<Vec<usize>>,
+    }
+
+    impl Screen {
+        fn new(cols: usize, rows: usize) -> Screen {
+            Screen {
+                cols,
+                rows,
+                viewport: RefCell::new((0, 0)),
+                painted: RefCell::new(Vec::new()),
+                printed: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn lowest_painted_row(&self) -> Option<usize> {
+            self.painted.borrow().iter().map(|&(_, y)| y).max()
+        }
+    }
+
+    impl Canvas for Screen {
+        fn clear(&self) {
+            self.painted.borrow_mut().clear();
+        }
+
+        fn paint(&self, x: usize, y: usize, _: Paint) {
+            self.painted.borrow_mut().push((x, y));
+        }
+
+        fn print(&self, _: usize, y: usize, _: usize, _: &str, _: TextStyle) {
+            self.printed.borrow_mut().push(y);
+        }
+    }
+
+    impl GameBackend for Screen {
+        fn num_cols(&self) -> usize {
+            self.cols
+        }
+
+        fn num_rows(&self) -> usize {
+            self.rows
+        }
+
+        fn viewport(&self) -> (usize, usize) {
+            *self.viewport.borrow()
+        }
+
+        fn set_viewport(&self, x: usize, y: usize) {
+            *self.viewport.borrow_mut() = (x, y);
+        }
+
+        fn app_actions(&self, _: GameState) -> Box<dyn Iterator<Item = AppAction>> {
+            Box::new(iter::empty())
+        }
+
+        fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>> {
+            Box::new(iter::empty())
+        }
+
+        fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>> {
+            Box::new(iter::empty())
+        }
+
+        fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+            Box::new(iter::empty())
+        }
+
+        fn draw_game(&self, _: &Grid<u8>, _: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn draw_editor(&self, _: &Editor, _: &Grid<u8>, _: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn draw_browser(&self, _: &Browser) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_board_as_tall_as_the_screen_stops_above_the_status_line() {
+        let screen = Screen::new(10, 8);
+        render_game(&screen, &Grid::new(10, 8));
+        render_status(&screen, "status");
+        assert_eq!(screen.lowest_painted_row(), Some(6));
+        assert_eq!(*screen.printed.borrow(), [7]);
+    }
+
+    #[test]
+    fn the_editor_scrolls_the_last_row_into_view_above_the_status_line() {
+        let screen = Screen::new(10, 8);
+        let grid = Grid::new(10, 8);
+        let mut editor = Editor::new();
+        editor.set_cursor(0, 7);
+        render_editor(&screen, &editor, &grid);
+        assert_eq!(screen.viewport(), (0, 1));
+        assert_eq!(screen.lowest_painted_row(), Some(6));
+    }
+
+    #[test]
+    fn a_screen_without_rows_draws_nothing_but_the_status_line() {
+        let screen = Screen::new(10, 0);
+        assert_eq!(grid_rows(&screen), 0);
+        render_game(&screen, &Grid::new(10, 8));
+        render_status(&screen, "status");
+        assert_eq!(screen.lowest_painted_row(), None);
+        assert_eq!(*screen.printed.borrow(), [0]);
+    }
+}
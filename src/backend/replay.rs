@@ -0,0 +1,926 @@
+//! Recordings of everything done in a game, which can be played back to do it all again,
+//! for bug reports, demos and checking that a change hasn't broken anything.
+//!
+//! A recording is the session the game started from, then a line `#actions`, then one JSON
+//! object per action with the frame whose input it was, the milliseconds since the first
+//! frame and the generation on the board, e.g.
+//! `{"app":"EditMode","frame":12,"generation":0,"time":840}`. Besides the backend's input,
+//! the seed of every random soup, the requests of other programs and the board following
+//! the screen's size are written down, so that playing back does exactly the same.
+
+use crate::{AppAction, GameState};
+use backend::events::EventQueue;
+use backend::names::*;
+use backend::{Error as BackendError, GameBackend};
+use console::ConsoleAction;
+use editor::{EditAction, Editor};
+use game::Gol;
+use grid::Grid;
+use library::{BrowseAction, Browser};
+use pattern::Pattern;
+use serde_json::{json, Map, Value};
+use session::{self, Session};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+static HEADER: &str = "#GOL recording";
+
+/// The line between the session and the actions.
+static ACTIONS: &str = "#actions";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(String),
+    Session(session::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Parse(msg) => write!(f, "Invalid recording: {}", msg),
+            Error::Session(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Error {
+        Error::Io(other)
+    }
+}
+
+impl From<session::Error> for Error {
+    fn from(other: session::Error) -> Error {
+        Error::Session(other)
+    }
+}
+
+/// Something done to the game other than with a backend's input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum External {
+    /// A request from another program, by its method and params.
+    Request(String, Map<String, Value>),
+    /// The board following the screen to a new size.
+    Resize { width: usize, height: usize },
+}
+
+/// An action as a backend gave it, or something else that happened to the game.
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    App(AppAction),
+    Edit(EditAction),
+    Browse(BrowseAction),
+    Console(ConsoleAction),
+    External(External),
+    /// A seed chosen for a random soup.
+    Seed(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    /// Which frame's input it was, counting from the first.
+    frame: u64,
+    /// Milliseconds from the first frame.
+    time: u64,
+    /// The generation on the board when its frame's input was read.
+    generation: u64,
+    action: Action,
+}
+
+impl Entry {
+    fn to_json(&self) -> Value {
+        let mut fields = match &self.action {
+            Action::App(action) => named("app", app_action_name(*action)),
+            Action::Edit(action) => edit_fields(action),
+            Action::Browse(action) => {
+                let mut fields = named("browse", browse_action_name(action));
+                if let BrowseAction::Type(c) = action {
+                    fields.insert("char".into(), json!(c.to_string()));
+                }
+                fields
+            }
+            Action::Console(action) => {
+                let mut fields = named("console", console_action_name(action));
+                if let ConsoleAction::Type(c) = action {
+                    fields.insert("char".into(), json!(c.to_string()));
+                }
+                fields
+            }
+            Action::External(External::Request(method, params)) => {
+                let mut fields = named("request", method);
+                fields.insert("params".into(), Value::Object(params.clone()));
+                fields
+            }
+            Action::External(External::Resize { width, height }) => {
+                let mut fields = Map::new();
+                fields.insert("resize".into(), json!([width, height]));
+                fields
+            }
+            Action::Seed(seed) => {
+                let mut fields = Map::new();
+                fields.insert("seed".into(), json!(seed));
+                fields
+            }
+        };
+        fields.insert("frame".into(), json!(self.frame));
+        fields.insert("time".into(), json!(self.time));
+        fields.insert("generation".into(), json!(self.generation));
+        Value::Object(fields)
+    }
+
+    fn from_json(fields: &Value) -> Option<Entry> {
+        let text = |key| fields.get(key).and_then(Value::as_str);
+        let c = text("char").and_then(|c| c.chars().next());
+        let action = if let Some(name) = text("app") {
+            Action::App(app_action_named(name)?)
+        } else if text("edit").is_some() {
+            Action::Edit(edit_action(fields)?)
+        } else if let Some(name) = text("browse") {
+            Action::Browse(browse_action_named(name, c)?)
+        } else if let Some(name) = text("console") {
+            Action::Console(console_action_named(name, c)?)
+        } else if let Some(method) = text("request") {
+            let params = fields.get("params")?.as_object()?.clone();
+            Action::External(External::Request(method.to_owned(), params))
+        } else if let Some(size) = fields.get("resize") {
+            match size.as_array()?.as_slice() {
+                [width, height] => Action::External(External::Resize {
+                    width: width.as_u64()? as usize,
+                    height: height.as_u64()? as usize,
+                }),
+                _ => return None,
+            }
+        } else {
+            Action::Seed(fields.get("seed")?.as_u64()?)
+        };
+        Some(Entry {
+            frame: fields.get("frame")?.as_u64()?,
+            time: fields.get("time")?.as_u64()?,
+            generation: fields.get("generation")?.as_u64()?,
+            action,
+        })
+    }
+}
+
+fn named(kind: &str, name: &str) -> Map<String, Value> {
+    let mut fields = Map::new();
+    fields.insert(kind.into(), json!(name));
+    fields
+}
+
+fn edit_fields(action: &EditAction) -> Map<String, Value> {
+    let mut fields = named("edit", edit_action_name(action));
+    let mut add = |key: &str, value| {
+        fields.insert(key.into(), value);
+    };
+    match action {
+        EditAction::MoveCursorBy { x, y } => {
+            add("x", json!(x));
+            add("y", json!(y));
+        }
+        EditAction::MoveCursorTo { x, y }
+        | EditAction::ToggleCellAt { x, y }
+        | EditAction::KillCellAt { x, y }
+        | EditAction::AddCellAt { x, y }
+        | EditAction::FloodFillAt { x, y } => {
+            add("x", json!(x));
+            add("y", json!(y));
+        }
+        EditAction::AddPattern(pattern) => add("rle", json!(pattern.to_rle())),
+        EditAction::TransformStamp(transform) => {
+            add("transform", json!(transform_name(*transform)));
+        }
+        EditAction::ExportClipboard(path) => add("path", json!(path.to_string_lossy())),
+        EditAction::Draw(shape) => add("shape", json!(shape_name(*shape))),
+        EditAction::DrawBetween { shape, from, to } => {
+            add("shape", json!(shape_name(*shape)));
+            add("from", json!([from.0, from.1]));
+            add("to", json!([to.0, to.1]));
+        }
+        EditAction::RandomSoup {
+            seed,
+            density,
+            symmetry,
+        } => {
+            add("seed", json!(seed));
+            add("density", json!(density));
+            add(
+                "symmetry",
+                json!(symmetry.map(|symmetry| symmetry.to_string())),
+            );
+        }
+        EditAction::ChangeSoupDensity(by) => add("by", json!(by)),
+        _ => {}
+    }
+    fields
+}
+
+fn edit_action(fields: &Value) -> Option<EditAction> {
+    let text = |key| fields.get(key).and_then(Value::as_str);
+    let cell = |key| match fields.get(key)?.as_array()?.as_slice() {
+        [x, y] => Some((x.as_u64()? as usize, y.as_u64()? as usize)),
+        _ => None,
+    };
+    let action = match text("edit")? {
+        "AddPattern" => EditAction::AddPattern(Box::new(Pattern::from_rle(text("rle")?).ok()?)),
+        "TransformStamp" => EditAction::TransformStamp(transform_named(text("transform")?)?),
+        "ExportClipboard" => EditAction::ExportClipboard(text("path")?.into()),
+        "Draw" => EditAction::Draw(shape_named(text("shape")?)?),
+        "DrawBetween" => EditAction::DrawBetween {
+            shape: shape_named(text("shape")?)?,
+            from: cell("from")?,
+            to: cell("to")?,
+        },
+        "RandomSoup" => EditAction::RandomSoup {
+            seed: fields.get("seed").and_then(Value::as_u64),
+            density: fields.get("density").and_then(Value::as_f64),
+            symmetry: match text("symmetry") {
+                Some(symmetry) => Some(symmetry.parse().ok()?),
+                None => None,
+            },
+        },
+        "ChangeSoupDensity" => EditAction::ChangeSoupDensity(fields.get("by")?.as_f64()?),
+        name => {
+            let number = |key| fields.get(key).and_then(Value::as_i64);
+            let at = match (number("x"), number("y")) {
+                (Some(x), Some(y)) => Some((x as isize, y as isize)),
+                _ => None,
+            };
+            edit_action_named(name, at)?
+        }
+    };
+    Some(action)
+}
+
+/// A recording read back from its file.
+pub struct Recording {
+    /// How the game was when the recording started.
+    pub session: Session,
+    entries: VecDeque<Entry>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Recording, Error> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(HEADER) {
+            return Err(Error::Parse("not a recording".to_owned()));
+        }
+        let session: Vec<&str> = lines
+            .by_ref()
+            .take_while(|line| line.trim() != ACTIONS)
+            .collect();
+        let session = session.join("\n").parse()?;
+        let mut entries = VecDeque::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let entry = serde_json::from_str(line)
+                .ok()
+                .as_ref()
+                .and_then(Entry::from_json)
+                .ok_or_else(|| Error::Parse(format!("bad action {:?}", line)))?;
+            entries.push_back(entry);
+        }
+        Ok(Recording { session, entries })
+    }
+}
+
+/// Milliseconds since the first frame, which starts now if this is it.
+fn elapsed(start: &Cell<Option<Instant>>) -> u64 {
+    let start = start.get().unwrap_or_else(Instant::now);
+    start.elapsed().as_millis() as u64
+}
+
+/// A backend that writes down everything done with another backend, as it's done.
+pub struct Record {
+    inner: Box<dyn GameBackend>,
+    out: RefCell<BufWriter<File>>,
+    game: Arc<Mutex<Gol>>,
+    /// When the first frame's input was read.
+    start: Cell<Option<Instant>>,
+    frame: Cell<u64>,
+    /// The generation when this frame's input was read.
+    generation: Cell<u64>,
+}
+
+impl Record {
+    /// Starts a recording in `path` of a game that starts from `session`.
+    pub fn new(
+        path: &Path,
+        session: &Session,
+        inner: Box<dyn GameBackend>,
+        game: Arc<Mutex<Gol>>,
+    ) -> Result<Record, Error> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", HEADER)?;
+        write!(out, "{}", session)?;
+        writeln!(out, "{}", ACTIONS)?;
+        out.flush()?;
+        Ok(Record {
+            inner,
+            out: RefCell::new(out),
+            game,
+            start: Cell::new(None),
+            frame: Cell::new(0),
+            generation: Cell::new(0),
+        })
+    }
+
+    /// Counts a frame, as each frame's input is read by one call for the state it's in.
+    /// Everything in the frame is written down with the generation its input was read on,
+    /// as the game may be locked by the time it's written.
+    fn next_frame(&self) {
+        match self.start.get() {
+            Some(_) => self.frame.set(self.frame.get() + 1),
+            None => self.start.set(Some(Instant::now())),
+        }
+        self.generation.set(self.game.lock().unwrap().generation());
+    }
+
+    fn write<I: IntoIterator<Item = Action>>(&self, actions: I) {
+        let mut actions = actions.into_iter().peekable();
+        if actions.peek().is_none() {
+            return;
+        }
+        let time = elapsed(&self.start);
+        let mut out = self.out.borrow_mut();
+        for action in actions {
+            let entry = Entry {
+                frame: self.frame.get(),
+                time,
+                generation: self.generation.get(),
+                action,
+            };
+            // a recording that can't be written is given up on, rather than the game
+            let _ = writeln!(out, "{}", entry.to_json());
+        }
+        // flushed straight away, so that the recording of a crash is complete
+        let _ = out.flush();
+    }
+}
+
+impl GameBackend for Record {
+    fn num_cols(&self) -> usize {
+        self.inner.num_cols()
+    }
+
+    fn num_rows(&self) -> usize {
+        self.inner.num_rows()
+    }
+
+    fn viewport(&self) -> (usize, usize) {
+        self.inner.viewport()
+    }
+
+    fn set_viewport(&self, x: usize, y: usize) {
+        self.inner.set_viewport(x, y)
+    }
+
+    fn app_actions(&self, game_state: GameState) -> Box<dyn Iterator<Item = AppAction>> {
+        let actions: Vec<_> = self.inner.app_actions(game_state).collect();
+        self.next_frame();
+        self.write(actions.iter().cloned().map(Action::App));
+        Box::new(actions.into_iter())
+    }
+
+    /// Records the seed of each random soup, so that the same soup is made again.
+    fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>> {
+        let actions: Vec<_> = self
+            .inner
+            .edit_actions()
+            .map(|action| action.with_seed(|| self.inner.seed()))
+            .collect();
+        self.write(actions.iter().cloned().map(Action::Edit));
+        Box::new(actions.into_iter())
+    }
+
+    fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>> {
+        let actions: Vec<_> = self.inner.browse_actions().collect();
+        self.next_frame();
+        self.write(actions.iter().cloned().map(Action::Browse));
+        Box::new(actions.into_iter())
+    }
+
+    fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+        let actions: Vec<_> = self.inner.console_actions().collect();
+        self.next_frame();
+        self.write(actions.iter().cloned().map(Action::Console));
+        Box::new(actions.into_iter())
+    }
+
+    fn draw_game(&self, grid: &Grid<u8>, status: &str) -> Result<(), BackendError> {
+        self.inner.draw_game(grid, status)
+    }
+
+    fn draw_editor(
+        &self,
+        editor: &Editor,
+        grid: &Grid<u8>,
+        status: &str,
+    ) -> Result<(), BackendError> {
+        self.inner.draw_editor(editor, grid, status)
+    }
+
+    fn draw_browser(&self, browser: &Browser) -> Result<(), BackendError> {
+        self.inner.draw_browser(browser)
+    }
+
+    fn seed(&self) -> u64 {
+        let seed = self.inner.seed();
+        self.write(Some(Action::Seed(seed)));
+        seed
+    }
+
+    fn record(&self, external: &External) {
+        self.write(Some(Action::External(external.clone())));
+        self.inner.record(external);
+    }
+
+    /// Records what's played back too, so that a replay can be recorded again.
+    fn replayed(&self) -> Vec<External> {
+        let replayed = self.inner.replayed();
+        self.write(replayed.iter().cloned().map(Action::External));
+        replayed
+    }
+
+    fn follows_screen(&self) -> bool {
+        self.inner.follows_screen()
+    }
+}
+
+/// A backend that plays back a recording, drawing with another backend. Until the
+/// recording runs out, the other backend's input is ignored, apart from quitting.
+///
+/// Each frame's actions are taken when they're due, on the generation they were taken
+/// on: the runner is held back from going past it, and if it hasn't got there the
+/// game is caught up.
+///
+/// Nothing is left out, so files are written and scripts run as they were when recording.
+pub struct Replay {
+    inner: Box<dyn GameBackend>,
+    entries: RefCell<VecDeque<Entry>>,
+    /// The actions from this frame of the recording that haven't been taken yet.
+    events: RefCell<EventQueue>,
+    /// What else happened in this frame of the recording.
+    external: RefCell<Vec<External>>,
+    /// The seeds of random soups, which are used in the order they were chosen.
+    seeds: RefCell<VecDeque<u64>>,
+    start: Cell<Option<Instant>>,
+    /// The generation the runner mustn't go past.
+    limit: Arc<AtomicU64>,
+    /// Runs generations until the game reaches the one given.
+    catch_up: Box<dyn Fn(u64)>,
+}
+
+impl Replay {
+    pub fn new<F>(
+        recording: Recording,
+        inner: Box<dyn GameBackend>,
+        limit: Arc<AtomicU64>,
+        catch_up: F,
+    ) -> Replay
+    where
+        F: Fn(u64) + 'static,
+    {
+        let replay = Replay {
+            inner,
+            entries: RefCell::new(recording.entries),
+            events: RefCell::new(EventQueue::new()),
+            external: RefCell::new(Vec::new()),
+            seeds: RefCell::new(VecDeque::new()),
+            start: Cell::new(None),
+            limit,
+            catch_up: Box::new(catch_up),
+        };
+        replay.hold();
+        replay
+    }
+
+    fn finished(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    /// Holds the runner at the generation of the next action.
+    fn hold(&self) {
+        let next = self
+            .entries
+            .borrow()
+            .front()
+            .map_or(u64::MAX, |entry| entry.generation);
+        self.limit.store(next, Ordering::Relaxed);
+    }
+
+    /// Reads the next frame of the recording, if it's due.
+    fn read(&self) {
+        if self.start.get().is_none() {
+            self.start.set(Some(Instant::now()));
+        }
+        let mut events = self.events.borrow_mut();
+        events.clear();
+        let (frame, generation) = match self.entries.borrow().front() {
+            Some(entry) if entry.time <= elapsed(&self.start) => (entry.frame, entry.generation),
+            _ => return,
+        };
+        (self.catch_up)(generation);
+        let mut entries = self.entries.borrow_mut();
+        while entries.front().is_some_and(|entry| entry.frame == frame) {
+            match entries.pop_front().unwrap().action {
+                Action::App(action) => events.push_app(action),
+                Action::Edit(action) => events.push_edit(action),
+                Action::Browse(action) => events.push_browse(action),
+                Action::Console(action) => events.push_console(action),
+                Action::External(external) => self.external.borrow_mut().push(external),
+                Action::Seed(seed) => self.seeds.borrow_mut().push_back(seed),
+            }
+        }
+        drop(entries);
+        self.hold();
+    }
+}
+
+impl GameBackend for Replay {
+    fn num_cols(&self) -> usize {
+        self.inner.num_cols()
+    }
+
+    fn num_rows(&self) -> usize {
+        self.inner.num_rows()
+    }
+
+    fn viewport(&self) -> (usize, usize) {
+        self.inner.viewport()
+    }
+
+    fn set_viewport(&self, x: usize, y: usize) {
+        self.inner.set_viewport(x, y)
+    }
+
+    fn app_actions(&self, game_state: GameState) -> Box<dyn Iterator<Item = AppAction>> {
+        if self.finished() {
+            return self.inner.app_actions(game_state);
+        }
+        let quit: Vec<_> = self
+            .inner
+            .app_actions(game_state)
+            .filter(|&action| action == AppAction::Quit)
+            .collect();
+        self.read();
+        Box::new(self.events.borrow_mut().take_app_actions().chain(quit))
+    }
+
+    fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>> {
+        let actions = self.inner.edit_actions();
+        let replayed = self.events.borrow_mut().take_edit_actions();
+        // the last frame of the recording may have had edits
+        if self.finished() {
+            Box::new(replayed.chain(actions))
+        } else {
+            replayed
+        }
+    }
+
+    fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>> {
+        if self.finished() {
+            return self.inner.browse_actions();
+        }
+        self.inner.browse_actions().for_each(drop);
+        self.read();
+        self.events.borrow_mut().take_browse_actions()
+    }
+
+    fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+        if self.finished() {
+            return self.inner.console_actions();
+        }
+        self.inner.console_actions().for_each(drop);
+        self.read();
+        self.events.borrow_mut().take_console_actions()
+    }
+
+    fn draw_game(&self, grid: &Grid<u8>, status: &str) -> Result<(), BackendError> {
+        self.inner.draw_game(grid, status)
+    }
+
+    fn draw_editor(
+        &self,
+        editor: &Editor,
+        grid: &Grid<u8>,
+        status: &str,
+    ) -> Result<(), BackendError> {
+        self.inner.draw_editor(editor, grid, status)
+    }
+
+    fn draw_browser(&self, browser: &Browser) -> Result<(), BackendError> {
+        self.inner.draw_browser(browser)
+    }
+
+    /// The seed that was chosen when recording, or a new one once they run out.
+    fn seed(&self) -> u64 {
+        let seed = self.seeds.borrow_mut().pop_front();
+        seed.unwrap_or_else(|| self.inner.seed())
+    }
+
+    fn replayed(&self) -> Vec<External> {
+        let mut replayed = self.inner.replayed();
+        replayed.append(&mut self.external.borrow_mut());
+        replayed
+    }
+
+    /// The board follows the screen once the recording runs out.
+    fn follows_screen(&self) -> bool {
+        self.finished() && self.inner.follows_screen()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor::Transform;
+    use setup::{game_from_session, session_of};
+    use shapes::Shape;
+    use soup::Symmetry;
+    use std::env;
+    use std::iter;
+    use topology::Topology;
+
+    /// A backend that gives a list of edits a frame, and the same seed for every soup.
+    struct Keys {
+        frames: RefCell<VecDeque<Vec<EditAction>>>,
+        seed: u64,
+    }
+
+    impl Keys {
+        fn new(frames: Vec<Vec<EditAction>>, seed: u64) -> Keys {
+            Keys {
+                frames: RefCell::new(frames.into()),
+                seed,
+            }
+        }
+    }
+
+    impl GameBackend for Keys {
+        fn num_cols(&self) -> usize {
+            80
+        }
+
+        fn num_rows(&self) -> usize {
+            24
+        }
+
+        fn viewport(&self) -> (usize, usize) {
+            (0, 0)
+        }
+
+        fn set_viewport(&self, _: usize, _: usize) {}
+
+        fn app_actions(&self, _: GameState) -> Box<dyn Iterator<Item = AppAction>> {
+            Box::new(iter::empty())
+        }
+
+        fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>> {
+            let frame = self.frames.borrow_mut().pop_front();
+            Box::new(frame.unwrap_or_default().into_iter())
+        }
+
+        fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>> {
+            Box::new(iter::empty())
+        }
+
+        fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+            Box::new(iter::empty())
+        }
+
+        fn draw_game(&self, _: &Grid<u8>, _: &str) -> Result<(), BackendError> {
+            Ok(())
+        }
+
+        fn draw_editor(&self, _: &Editor, _: &Grid<u8>, _: &str) -> Result<(), BackendError> {
+            Ok(())
+        }
+
+        fn draw_browser(&self, _: &Browser) -> Result<(), BackendError> {
+            Ok(())
+        }
+
+        fn seed(&self) -> u64 {
+            self.seed
+        }
+    }
+
+    /// One frame of editing, as the game does it: the input, then the edits, then a
+    /// generation if the runner isn't held.
+    fn frame(ui: &dyn GameBackend, game: &Mutex<Gol>, editor: &mut Editor, limit: &AtomicU64) {
+        ui.app_actions(GameState::Editing).for_each(drop);
+        let mut game = game.lock().unwrap();
+        for action in ui.edit_actions() {
+            editor.apply_action(action.with_seed(|| ui.seed()), game.grid_mut());
+        }
+        if game.generation() < limit.load(Ordering::Relaxed) {
+            game.next_turn();
+        }
+    }
+
+    fn entry(action: Action) -> Entry {
+        Entry {
+            frame: 12,
+            time: 840,
+            generation: 3,
+            action,
+        }
+    }
+
+    fn read_back(entry: &Entry) -> Option<Entry> {
+        let line = entry.to_json().to_string();
+        Entry::from_json(&serde_json::from_str(&line).unwrap())
+    }
+
+    #[test]
+    fn every_action_is_read_back_as_it_was_written() {
+        let glider = Pattern::from_rle("x = 3, y = 3\nbob$2bo$3o!").unwrap();
+        let mut actions: Vec<Action> = [
+            AppAction::Quit,
+            AppAction::TogglePause,
+            AppAction::EditMode,
+            AppAction::EditDone,
+            AppAction::Browse,
+            AppAction::SaveSession,
+            AppAction::LoadSession,
+            AppAction::Faster,
+            AppAction::Slower,
+            AppAction::Census,
+            AppAction::ExportStats,
+            AppAction::ToggleRecording,
+            AppAction::Snapshot,
+            AppAction::Console,
+        ]
+        .iter()
+        .map(|&action| Action::App(action))
+        .collect();
+        let edits = vec![
+            EditAction::MoveCursorBy { x: -1, y: 2 },
+            EditAction::MoveCursorTo { x: 3, y: 4 },
+            EditAction::ToggleCell,
+            EditAction::ToggleCellAt { x: 0, y: 1 },
+            EditAction::KillCellAt { x: 1, y: 0 },
+            EditAction::AddCellAt { x: 5, y: 5 },
+            EditAction::Clear,
+            EditAction::AddPattern(Box::new(glider)),
+            EditAction::TransformStamp(Transform::RotateClockwise),
+            EditAction::TransformStamp(Transform::RotateAnticlockwise),
+            EditAction::TransformStamp(Transform::FlipHorizontal),
+            EditAction::TransformStamp(Transform::FlipVertical),
+            EditAction::CommitStamp,
+            EditAction::CancelStamp,
+            EditAction::ToggleSelection,
+            EditAction::Copy,
+            EditAction::Cut,
+            EditAction::Paste,
+            EditAction::FillSelection,
+            EditAction::ClearSelection,
+            EditAction::ClearOutsideSelection,
+            EditAction::MoveSelection,
+            EditAction::ExportClipboard("clip.rle".into()),
+            EditAction::Draw(Shape::Line),
+            EditAction::Draw(Shape::Rectangle),
+            EditAction::Draw(Shape::FilledRectangle),
+            EditAction::Draw(Shape::Ellipse),
+            EditAction::DrawBetween {
+                shape: Shape::FilledEllipse,
+                from: (1, 2),
+                to: (7, 9),
+            },
+            EditAction::FloodFill,
+            EditAction::FloodFillAt { x: 2, y: 3 },
+            EditAction::RandomSoup {
+                seed: Some(42),
+                density: Some(0.25),
+                symmetry: Some(Symmetry::D4),
+            },
+            EditAction::RandomSoup {
+                seed: Some(7),
+                density: None,
+                symmetry: None,
+            },
+            EditAction::CycleSoupSymmetry,
+            EditAction::ChangeSoupDensity(-0.05),
+            EditAction::Undo,
+            EditAction::Redo,
+        ];
+        actions.extend(edits.into_iter().map(Action::Edit));
+        actions.extend(
+            [
+                BrowseAction::Type('g'),
+                BrowseAction::Backspace,
+                BrowseAction::Next,
+                BrowseAction::Previous,
+                BrowseAction::Choose,
+                BrowseAction::Cancel,
+            ]
+            .iter()
+            .cloned()
+            .map(Action::Browse),
+        );
+        actions.extend(
+            [
+                ConsoleAction::Type(' '),
+                ConsoleAction::Backspace,
+                ConsoleAction::Complete,
+                ConsoleAction::Previous,
+                ConsoleAction::Next,
+                ConsoleAction::Run,
+                ConsoleAction::Cancel,
+            ]
+            .iter()
+            .cloned()
+            .map(Action::Console),
+        );
+        let mut params = Map::new();
+        params.insert("cells".into(), json!([[1, 2], [3, 4]]));
+        params.insert("alive".into(), json!(false));
+        actions.push(Action::External(External::Request(
+            "set_cells".into(),
+            params,
+        )));
+        actions.push(Action::External(External::Resize {
+            width: 80,
+            height: 23,
+        }));
+        actions.push(Action::Seed(u64::MAX));
+        for action in actions {
+            let entry = entry(action);
+            assert_eq!(read_back(&entry), Some(entry));
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_not_read() {
+        let line = r#"{"app":"Dance","frame":0,"generation":0,"time":0}"#;
+        assert_eq!(Entry::from_json(&serde_json::from_str(line).unwrap()), None);
+        let line = r#"{"edit":"MoveCursorTo","x":-1,"y":0,"frame":0,"generation":0,"time":0}"#;
+        assert_eq!(Entry::from_json(&serde_json::from_str(line).unwrap()), None);
+    }
+
+    #[test]
+    fn a_replay_ends_up_where_the_recording_did() {
+        let path = env::temp_dir().join(format!("gol-replay-{}.rec", std::process::id()));
+        let soup = EditAction::RandomSoup {
+            seed: None,
+            density: Some(0.4),
+            symmetry: None,
+        };
+        let frames = vec![
+            vec![soup],
+            vec![],
+            vec![EditAction::ToggleCellAt { x: 1, y: 1 }],
+            vec![],
+            vec![],
+            vec![EditAction::AddCellAt { x: 6, y: 6 }, EditAction::Undo],
+        ];
+        let mut editor = Editor::new();
+        let game = Arc::new(Mutex::new(Gol::new(12, 12, Topology::Torus)));
+        let session = session_of(&game.lock().unwrap(), &editor, (0, 0), 0);
+        let keys = Box::new(Keys::new(frames, 1234));
+        let record = Record::new(&path, &session, keys, Arc::clone(&game)).unwrap();
+        let running = AtomicU64::new(u64::MAX);
+        for _ in 0..6 {
+            frame(&record, &game, &mut editor, &running);
+        }
+        drop(record);
+        let recorded = game.lock().unwrap();
+
+        let recording = Recording::load(&path).unwrap();
+        let mut editor = Editor::new();
+        let game = Arc::new(Mutex::new(game_from_session(&recording.session)));
+        let limit = Arc::new(AtomicU64::new(u64::MAX));
+        let caught_up = Arc::clone(&game);
+        let catch_up = move |generation| {
+            let mut game = caught_up.lock().unwrap();
+            while game.generation() < generation {
+                game.next_turn();
+            }
+        };
+        // a different seed, so that the soup can only be the same by playing back its seed
+        let keys = Box::new(Keys::new(Vec::new(), 99));
+        let replay = Replay::new(recording, keys, Arc::clone(&limit), catch_up);
+        while !replay.finished() {
+            frame(&replay, &game, &mut editor, &limit);
+        }
+        let replayed = game.lock().unwrap();
+        assert_eq!(replayed.generation(), 6);
+        assert_eq!(replayed.generation(), recorded.generation());
+        assert_eq!(replayed.grid(), recorded.grid());
+        assert!(replayed.population() > 0);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,73 +1,200 @@
-use backend::key_map::*;
-use backend::{Error, GameBackend};
 use crate::{AppAction, GameState};
+use backend::events::EventQueue;
+use backend::key_map::Key;
+use backend::render::{self, Canvas, Paint, TextStyle};
+use backend::{Error, GameBackend};
+use console::ConsoleAction;
 use editor::{EditAction, Editor};
 use grid::Grid;
+use libc;
+use library::{BrowseAction, Browser};
 use rustty::*;
 use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
 use std::time::Duration;
 
+/// The terminal settings from before the game started.
+static ORIGINAL_TERMIOS: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+/// Stops reporting the mouse, shows the cursor, resets colours, clears the screen and
+/// leaves the alternate screen.
+static RESTORE_SEQUENCE: &str =
+    "\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?25h\x1b[0m\x1b[H\x1b[2J\x1b[?1049l";
+
+fn open_tty() -> Option<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()
+}
+
+/// Puts the terminal back the way it was before the first backend was made, then prints
+/// `message`. This is for panics, which can happen on threads that can't drop the backend.
+/// Standard error is captured while the game is running, so the message goes straight to
+/// the terminal.
+pub fn restore_after_panic(message: &str) {
+    if let Some(mut tty) = open_tty() {
+        let original = ORIGINAL_TERMIOS
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(termios) = original.as_ref() {
+            unsafe {
+                libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, termios);
+            }
+        }
+        let _ = writeln!(tty, "{}{}", RESTORE_SEQUENCE, message);
+    }
+}
+
+/// Remembers the terminal settings, for `restore_after_panic`.
+pub(crate) fn save_termios() {
+    let mut original = ORIGINAL_TERMIOS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    if original.is_some() {
+        return;
+    }
+    if let Some(tty) = open_tty() {
+        let mut termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut termios) } == 0 {
+            *original = Some(termios);
+        }
+    }
+}
+
+/// How long to wait for a key press before drawing the next frame.
+const POLL_TIME: Duration = Duration::from_millis(20);
+
+/// How long to wait for the rest of an escape sequence, before taking Esc as a key.
+const ESCAPE_TIME: Duration = Duration::from_millis(10);
+
 pub struct Term {
     term: RefCell<Terminal>,
-    key_presses: RefCell<Vec<char>>,
+    events: RefCell<EventQueue>,
+    viewport: RefCell<(usize, usize)>,
 }
 
 impl Term {
-    pub fn new() -> Term {
-        Term {
-            term: RefCell::new(Terminal::new().unwrap()),
-            key_presses: RefCell::new(Vec::new()),
+    /// Takes over the terminal until the `Term` is dropped.
+    pub fn new() -> Result<Term, Error> {
+        save_termios();
+        let term = Terminal::new().map_err(|err| {
+            Error::caused_by("Couldn't set up terminal".to_owned(), Box::new(err))
+        })?;
+        Ok(Term {
+            term: RefCell::new(term),
+            events: RefCell::new(EventQueue::new()),
+            viewport: RefCell::new((0, 0)),
+        })
+    }
+
+    /// Waits a frame for a key press, and queues its action for `game_state`. Actions
+    /// left over from the last frame are dropped.
+    fn read_key(&self, game_state: GameState) {
+        let mut events = self.events.borrow_mut();
+        events.clear();
+        let mut term = self.term.borrow_mut();
+        // a failed read is treated as no input
+        let key = match term.get_event(POLL_TIME) {
+            Ok(Some(Event::Key('\x1b'))) => parse_escape(&read_escape(&mut term)),
+            Ok(Some(Event::Key(c))) => Some((Key::Char(c), false)),
+            _ => None,
+        };
+        if let Some((key, shift)) = key {
+            events.push_key(game_state, key, shift);
+        }
+    }
+}
+
+/// The rest of an escape sequence after Esc, which rustty gives as separate characters,
+/// or nothing if Esc was pressed on its own.
+fn read_escape(term: &mut Terminal) -> String {
+    let mut sequence = String::new();
+    while let Ok(Some(Event::Key(c))) = term.get_event(ESCAPE_TIME) {
+        sequence.push(c);
+        // sequences start with `[` or `O` and end with a letter or `~`
+        let ended = match sequence.len() {
+            1 => c != '[' && c != 'O',
+            _ => c.is_ascii_alphabetic() || c == '~',
+        };
+        if ended {
+            break;
         }
     }
+    sequence
+}
 
-    fn render_game(&self, grid: &Grid<u8>) {
-        let width = grid.width();
-        let height = grid.height();
-        for x in 0..width {
-            for y in 0..height {
-                let col = if grid.get(x, y) == 1 {
-                    Color::Red
-                } else {
-                    Color::Black
-                };
-                self.term.borrow_mut()[(x, y)].set_bg(col);
-            }
+/// The key an escape sequence stands for, and whether Shift was held, given what came
+/// after Esc. Sequences for keys the game doesn't use are dropped, rather than being
+/// taken as the letters they're made of.
+fn parse_escape(sequence: &str) -> Option<(Key, bool)> {
+    let mut chars = sequence.chars();
+    let introducer = match chars.next() {
+        Some(c) => c,
+        None => return Some((Key::Char('\x1b'), false)),
+    };
+    let last = chars.next_back()?;
+    let params: Vec<u8> = match chars.as_str() {
+        "" => Vec::new(),
+        params => params
+            .split(';')
+            .map(|p| p.parse().ok())
+            .collect::<Option<_>>()?,
+    };
+    // the second parameter is 1 plus the modifiers, of which Shift is the first bit
+    let shift = params.get(1).is_some_and(|&m| m > 0 && (m - 1) & 1 == 1);
+    let key = match (introducer, last, params.first()) {
+        ('[', 'A', _) | ('O', 'A', _) => Key::Up,
+        ('[', 'B', _) | ('O', 'B', _) => Key::Down,
+        ('[', 'C', _) | ('O', 'C', _) => Key::Right,
+        ('[', 'D', _) | ('O', 'D', _) => Key::Left,
+        ('[', c @ 'P'..='S', _) | ('O', c @ 'P'..='S', _) => Key::Function(c as u8 - b'P' + 1),
+        ('[', '~', Some(&n)) => match n {
+            11..=15 => Key::Function(n - 10),
+            17..=21 => Key::Function(n - 11),
+            23 | 24 => Key::Function(n - 12),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((key, shift))
+}
+
+impl Canvas for Term {
+    fn clear(&self) {
+        CellAccessor::clear(&mut *self.term.borrow_mut(), Cell::default());
+    }
+
+    fn paint(&self, x: usize, y: usize, paint: Paint) {
+        let col = match paint {
+            Paint::Dead => Color::Black,
+            Paint::Alive => Color::Red,
+            Paint::Selected => Color::Blue,
+            Paint::SelectedAlive => Color::Magenta,
+            Paint::Stamp => Color::Yellow,
+            Paint::Cursor => Color::White,
+            Paint::CursorAlive => Color::Green,
+        };
+        if let Some(cell) = self.term.borrow_mut().get_mut(x, y) {
+            cell.set_bg(col);
         }
     }
 
-    fn render_editor(&self, editor: &Editor, grid: &Grid<u8>) {
-        self.render_game(grid);
-        let (x, y) = editor.get_cursor();
-        let col = if grid.get(x, y) == 1 {
-            Color::Green
-        } else {
-            Color::White
+    fn print(&self, x: usize, y: usize, max_width: usize, text: &str, style: TextStyle) {
+        let attrs = match style {
+            TextStyle::Plain => Attr::Default,
+            TextStyle::Bold => Attr::Bold,
+            TextStyle::Reverse => Attr::Reverse,
         };
-        self.term.borrow_mut()[(x, y)].set_bg(col);
-    }
-
-    fn filter_remove_actions<F, T>(&self, filter: F) -> Box<dyn Iterator<Item = T>>
-    where
-        F: Fn(char) -> Option<T> + 'static,
-        T: Copy + 'static,
-    {
-        let mut presses = self.key_presses.borrow_mut();
-        let presses: Vec<_> = presses.drain_filter(|key| filter(*key).is_some()).collect();
-
-        if presses.is_empty() {
-            let press = self
-                .term
-                .borrow_mut()
-                .get_event(Duration::from_millis(20))
-                .ok();
-            Box::new(
-                press
-                    .into_iter()
-                    .flatten()
-                    .flat_map(move |Event::Key(k)| filter(k)),
-            )
-        } else {
-            Box::new(presses.into_iter().flat_map(filter))
+        let mut term = self.term.borrow_mut();
+        for (i, c) in text.chars().take(max_width).enumerate() {
+            if let Some(cell) = term.get_mut(x + i, y) {
+                cell.set_ch(c).set_attrs(attrs);
+            }
         }
     }
 }
@@ -81,27 +208,110 @@ impl GameBackend for Term {
         self.term.borrow().rows()
     }
 
+    fn viewport(&self) -> (usize, usize) {
+        *self.viewport.borrow()
+    }
+
+    fn set_viewport(&self, x: usize, y: usize) {
+        *self.viewport.borrow_mut() = (x, y);
+    }
+
+    /// Reads this frame's input, so it must be called before `edit_actions`.
     fn app_actions(&self, game_state: GameState) -> Box<dyn Iterator<Item = AppAction>> {
-        self.filter_remove_actions(move |key| map_key_to_global_action(game_state, key))
+        self.read_key(game_state);
+        self.events.borrow_mut().take_app_actions()
     }
 
     fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>> {
-        self.filter_remove_actions(map_key_to_edit_action)
+        self.events.borrow_mut().take_edit_actions()
+    }
+
+    /// Reads this frame's input, as `app_actions` isn't called while browsing.
+    fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>> {
+        self.read_key(GameState::Browsing);
+        self.events.borrow_mut().take_browse_actions()
     }
 
-    fn draw_game(&self, grid: &Grid<u8>) -> Result<(), Error> {
-        self.render_game(grid);
+    /// Reads this frame's input, as `app_actions` isn't called at the console.
+    fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+        self.read_key(GameState::Console);
+        self.events.borrow_mut().take_console_actions()
+    }
+
+    fn draw_game(&self, grid: &Grid<u8>, status: &str) -> Result<(), Error> {
+        render::render_game(self, grid);
+        render::render_status(self, status);
         self.term
             .borrow_mut()
             .swap_buffers()
             .map_err(|err| Error::caused_by("Error drawing game".to_owned(), Box::new(err)))
     }
 
-    fn draw_editor(&self, editor: &Editor, grid: &Grid<u8>) -> Result<(), Error> {
-        self.render_editor(editor, grid);
+    fn draw_editor(&self, editor: &Editor, grid: &Grid<u8>, status: &str) -> Result<(), Error> {
+        render::render_editor(self, editor, grid);
+        render::render_status(self, status);
         self.term
             .borrow_mut()
             .swap_buffers()
             .map_err(|err| Error::caused_by("Error drawing editor".to_owned(), Box::new(err)))
     }
+
+    fn draw_browser(&self, browser: &Browser) -> Result<(), Error> {
+        render::render_browser(self, browser);
+        self.term
+            .borrow_mut()
+            .swap_buffers()
+            .map_err(|err| Error::caused_by("Error drawing browser".to_owned(), Box::new(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrow_keys_are_read_from_their_escape_sequences() {
+        assert_eq!(parse_escape("[A"), Some((Key::Up, false)));
+        assert_eq!(parse_escape("[B"), Some((Key::Down, false)));
+        assert_eq!(parse_escape("[C"), Some((Key::Right, false)));
+        assert_eq!(parse_escape("[D"), Some((Key::Left, false)));
+        assert_eq!(parse_escape("OA"), Some((Key::Up, false)));
+        assert_eq!(parse_escape("[1;2D"), Some((Key::Left, true)));
+        assert_eq!(parse_escape("[1;5D"), Some((Key::Left, false)));
+    }
+
+    #[test]
+    fn arrow_keys_in_edit_mode_only_move_the_cursor() {
+        // rather than drawing a rectangle for the `B` of Down, or cancelling the stamp and
+        // thinning soups for its Esc and `[`
+        for sequence in &["[A", "[B", "[C", "[D", "[1;2B"] {
+            let (key, shift) = parse_escape(sequence).unwrap();
+            let mut events = EventQueue::new();
+            events.push_key(GameState::Editing, key, shift);
+            let edits: Vec<_> = events.take_edit_actions().collect();
+            assert!(
+                matches!(edits.as_slice(), [EditAction::MoveCursorBy { .. }]),
+                "{:?}",
+                edits
+            );
+            assert_eq!(events.take_app_actions().count(), 0);
+        }
+    }
+
+    #[test]
+    fn function_keys_are_read_from_their_escape_sequences() {
+        assert_eq!(parse_escape("OP"), Some((Key::Function(1), false)));
+        assert_eq!(parse_escape("OS"), Some((Key::Function(4), false)));
+        assert_eq!(parse_escape("[15~"), Some((Key::Function(5), false)));
+        assert_eq!(parse_escape("[21~"), Some((Key::Function(10), false)));
+        assert_eq!(parse_escape("[24~"), Some((Key::Function(12), false)));
+    }
+
+    #[test]
+    fn esc_on_its_own_is_a_key_and_other_sequences_are_dropped() {
+        assert_eq!(parse_escape(""), Some((Key::Char('\x1b'), false)));
+        assert_eq!(parse_escape("[H"), None);
+        assert_eq!(parse_escape("[3~"), None);
+        assert_eq!(parse_escape("x"), None);
+    }
 }
@@ -0,0 +1,677 @@
+//! A backend that serves the game to web browsers, so that several people can watch and
+//! edit the same board. It only listens on this machine unless `--listen` gives an address
+//! other machines can reach. Browsers load a page over HTTP, then get the
+//! board over a WebSocket as a full picture followed by the cells that change, and send
+//! back key presses, clicks and actions.
+
+use crate::{AppAction, GameState};
+use backend::events::EventQueue;
+use backend::key_map::*;
+use backend::names::*;
+use backend::{Error, GameBackend};
+use console::ConsoleAction;
+use editor::{EditAction, Editor};
+use grid::Grid;
+use library::{BrowseAction, Browser};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{self, Message, WebSocket};
+
+/// The page that draws the board and sends input back.
+static PAGE: &str = include_str!("web.html");
+
+/// How long to wait for input before drawing the next frame.
+const POLL_TIME: Duration = Duration::from_millis(20);
+
+/// How long a browser has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest request that's read, which is plenty for a page or a WebSocket handshake.
+const MAX_REQUEST: usize = 8192;
+
+/// The size of a new board. Browsers show all of it, so it doesn't depend on any screen,
+/// and there's one row more than the board for the status line, as in the terminal.
+const COLS: usize = 80;
+const ROWS: usize = 41;
+
+/// The number of pattern names shown in the browser at a time.
+const BROWSER_ROWS: usize = 30;
+
+/// Something a browser sent.
+#[derive(Debug, PartialEq)]
+enum Input {
+    /// A key press, and whether Shift was held.
+    Key(Key, bool),
+    App(AppAction),
+    Edit(EditAction),
+}
+
+/// A connected browser, and the messages waiting to be sent to it.
+struct Client {
+    outgoing: Sender<String>,
+    /// Whether it still needs the whole board rather than what's changed.
+    fresh: bool,
+}
+
+pub struct Web {
+    clients: Arc<Mutex<Vec<Client>>>,
+    incoming: Receiver<Input>,
+    /// The actions from this frame's input that haven't been taken yet.
+    events: RefCell<EventQueue>,
+    /// The board as browsers last saw it.
+    shown: RefCell<Option<Grid<u8>>>,
+    /// The last view sent, so that it's only sent again when it changes.
+    view: RefCell<String>,
+    viewport: RefCell<(usize, usize)>,
+}
+
+impl Web {
+    /// Listens for browsers on `addr` until the `Web` is dropped.
+    pub fn new(addr: SocketAddr) -> Result<Web, Error> {
+        let listener = TcpListener::bind(addr).map_err(|err| {
+            Error::caused_by(format!("Couldn't listen on {}", addr), Box::new(err))
+        })?;
+        // the port is the one chosen for it, if it was 0
+        let addr = listener.local_addr().unwrap_or(addr);
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let (inputs, incoming) = channel();
+        let accepting = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let clients = Arc::clone(&accepting);
+                let inputs = inputs.clone();
+                thread::spawn(move || serve(stream, addr, &clients, inputs));
+            }
+        });
+        Ok(Web {
+            clients,
+            incoming,
+            events: RefCell::new(EventQueue::new()),
+            shown: RefCell::new(None),
+            view: RefCell::new(String::new()),
+            viewport: RefCell::new((0, 0)),
+        })
+    }
+
+    /// Receives the input that's waiting and queues its actions for `game_state`, dropping
+    /// actions left over from the last frame. Actions sent by name that don't make sense in
+    /// this state are ignored.
+    fn read_inputs(&self, game_state: GameState) {
+        let mut events = self.events.borrow_mut();
+        events.clear();
+        let first = match self.incoming.recv_timeout(POLL_TIME) {
+            Ok(input) => input,
+            Err(_) => return,
+        };
+        for input in Some(first).into_iter().chain(self.incoming.try_iter()) {
+            match input {
+                Input::Key(key, shift) => events.push_key(game_state, key, shift),
+                Input::App(action) => {
+                    let typing = matches!(game_state, GameState::Browsing | GameState::Console);
+                    if !typing && applies_in(game_state, action) {
+                        events.push_app(action);
+                    }
+                }
+                Input::Edit(action) => {
+                    if game_state == GameState::Editing {
+                        events.push_edit(action);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends each browser what's changed on the board, or all of it if it's new or the
+    /// board has changed size, and the view if it's changed.
+    fn publish(&self, grid: Option<&Grid<u8>>, view: String) {
+        let mut shown = self.shown.borrow_mut();
+        let (full, diff) = match (grid, shown.as_ref()) {
+            (Some(grid), Some(old))
+                if (old.width(), old.height()) == (grid.width(), grid.height()) =>
+            {
+                (None, diff_message(old, grid))
+            }
+            (Some(grid), _) => (Some(full_message(grid)), None),
+            (None, _) => (None, None),
+        };
+        let view_changed = *self.view.borrow() != view;
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain_mut(|client| {
+            let mut messages = Vec::new();
+            if full.is_some() || client.fresh {
+                if let Some(grid) = grid.or(shown.as_ref()) {
+                    messages.push(full.clone().unwrap_or_else(|| full_message(grid)));
+                }
+            } else if let Some(diff) = &diff {
+                messages.push(diff.clone());
+            }
+            if view_changed || client.fresh {
+                messages.push(view.clone());
+            }
+            client.fresh = false;
+            // a browser that's gone has dropped its end of the channel
+            messages
+                .into_iter()
+                .all(|message| client.outgoing.send(message).is_ok())
+        });
+        if let Some(grid) = grid {
+            *shown = Some(grid.clone());
+        }
+        *self.view.borrow_mut() = view;
+    }
+}
+
+impl Drop for Web {
+    /// Disconnects the browsers.
+    fn drop(&mut self) {
+        self.clients.lock().unwrap().clear();
+    }
+}
+
+/// Reads a request, then either serves the page or, if the browser asked for a WebSocket,
+/// plays the game with it.
+fn serve(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    clients: &Mutex<Vec<Client>>,
+    inputs: Sender<Input>,
+) {
+    let request = match peek_request(&stream) {
+        Ok(Some(request)) => request,
+        _ => return,
+    };
+    if request.to_ascii_lowercase().contains("upgrade: websocket") {
+        if let Ok(socket) = tungstenite::accept_hdr(stream, SameOrigin(addr)) {
+            let (outgoing, messages) = channel();
+            clients.lock().unwrap().push(Client {
+                outgoing,
+                fresh: true,
+            });
+            play(socket, messages, inputs);
+        }
+        return;
+    }
+    // the request has only been peeked at, so take it off the stream before answering
+    let mut discard = vec![0; request.len()];
+    if stream.read_exact(&mut discard).is_err() {
+        return;
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let response = if path == "/" || path == "/index.html" {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            PAGE.len(),
+            PAGE
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+    };
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Refuses WebSockets that aren't from the game's own page at the address listened on.
+struct SameOrigin(SocketAddr);
+
+impl Callback for SameOrigin {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let header = |name| request.headers().get(name).and_then(|v| v.to_str().ok());
+        if is_same_origin(self.0, header("host"), header("origin")) {
+            Ok(response)
+        } else {
+            let mut refusal = ErrorResponse::new(Some("Not from this game's page".to_owned()));
+            *refusal.status_mut() = StatusCode::FORBIDDEN;
+            Err(refusal)
+        }
+    }
+}
+
+/// Whether a WebSocket request comes from the game's own page, rather than another page
+/// open in the browser, which could otherwise type commands that write files. The host
+/// must be the address listened on, so that a name pointed at it can't pass for it, and
+/// the origin the page at that host.
+fn is_same_origin(addr: SocketAddr, host: Option<&str>, origin: Option<&str>) -> bool {
+    let (host, origin) = match (host, origin) {
+        (Some(host), Some(origin)) => (host, origin),
+        _ => return false,
+    };
+    let is_host = match host.parse::<SocketAddr>() {
+        Ok(host) => {
+            host.port() == addr.port() && (host.ip() == addr.ip() || addr.ip().is_unspecified())
+        }
+        Err(_) => addr.ip().is_loopback() && host == format!("localhost:{}", addr.port()),
+    };
+    is_host && origin == format!("http://{}", host)
+}
+
+/// The request line and headers, without taking them off the stream, or `None` if the
+/// browser hung up first.
+fn peek_request(stream: &TcpStream) -> Result<Option<String>, io::Error> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut buffer = [0; MAX_REQUEST];
+    loop {
+        let read = stream.peek(&mut buffer)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let text = String::from_utf8_lossy(&buffer[..read]);
+        if let Some(end) = text.find("\r\n\r\n") {
+            return Ok(Some(text[..end + 4].to_owned()));
+        }
+        if read == MAX_REQUEST {
+            return Err(io::Error::new(ErrorKind::InvalidData, "request too long"));
+        }
+        // the rest of the request is on its way
+        thread::sleep(POLL_TIME);
+    }
+}
+
+/// Passes on what a browser sends and sends it the messages for it, until either end
+/// hangs up.
+fn play(mut socket: WebSocket<TcpStream>, messages: Receiver<String>, inputs: Sender<Input>) {
+    if socket.get_ref().set_read_timeout(Some(POLL_TIME)).is_err() {
+        return;
+    }
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                if let Some(input) = parse_input(&text) {
+                    if inputs.send(input).is_err() {
+                        break;
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(ref err))
+                if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => {}
+            Err(_) => return,
+        }
+        loop {
+            match messages.try_recv() {
+                Ok(message) => {
+                    if socket.write(Message::Text(message)).is_err() {
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                    return;
+                }
+            }
+        }
+        if socket.flush().is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads a command from a browser:
+///
+/// * `key MODIFIERS NAME`: a key press, with the key named as browsers name them, and
+///   modifiers `s` for Shift and `c` for Ctrl, or `-` for neither.
+/// * `app ACTION`: one of the `AppAction`s.
+/// * `edit ACTION [X Y]`: one of the `EditAction`s without a pattern or shape, with the
+///   cell or offset for those that need one, which must fit in 32 bits.
+fn parse_input(text: &str) -> Option<Input> {
+    let mut words = text.splitn(3, ' ');
+    match words.next()? {
+        "key" => {
+            let modifiers = words.next()?;
+            let key = key_named(words.next()?, modifiers.contains('c'))?;
+            Some(Input::Key(key, modifiers.contains('s')))
+        }
+        "app" => app_action_named(words.next()?).map(Input::App),
+        "edit" => {
+            let name = words.next()?;
+            let mut numbers = words.next().unwrap_or("").split_whitespace();
+            let mut number = || numbers.next().and_then(|n| n.parse::<i32>().ok());
+            let at = match (number(), number()) {
+                (Some(x), Some(y)) => Some((x as isize, y as isize)),
+                (None, None) => None,
+                _ => return None,
+            };
+            edit_action_named(name, at).map(Input::Edit)
+        }
+        _ => None,
+    }
+}
+
+/// The key with the name a browser gives it, with Ctrl and a letter made into a control
+/// character.
+fn key_named(name: &str, ctrl: bool) -> Option<Key> {
+    let key = match name {
+        "Enter" => Key::Char('\r'),
+        "Escape" => Key::Char('\x1b'),
+        "Backspace" => Key::Char('\x7f'),
+        "Tab" => Key::Char('\t'),
+        "ArrowUp" => Key::Up,
+        "ArrowDown" => Key::Down,
+        "ArrowLeft" => Key::Left,
+        "ArrowRight" => Key::Right,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next()?, chars.next()) {
+                (c, None) if ctrl => {
+                    if !c.is_ascii_alphabetic() {
+                        return None;
+                    }
+                    Key::Char((c.to_ascii_uppercase() as u8 & 0x1f) as char)
+                }
+                (c, None) => Key::Char(c),
+                ('F', Some(_)) => Key::Function(name[1..].parse().ok()?),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+/// The indexes of the cells in `grid` that `keep` picks, row by row.
+fn cells_where<F>(grid: &Grid<u8>, keep: F) -> Vec<usize>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let width = grid.width();
+    (0..width * grid.height())
+        .filter(|i| keep(i % width, i / width))
+        .collect()
+}
+
+/// The whole board, as the indexes of its live cells.
+fn full_message(grid: &Grid<u8>) -> String {
+    let alive = cells_where(grid, |x, y| grid.get(x, y) == 1);
+    json!({
+        "type": "full",
+        "width": grid.width(),
+        "height": grid.height(),
+        "alive": alive,
+    })
+    .to_string()
+}
+
+/// The cells that have changed between two boards of the same size, if any have.
+fn diff_message(old: &Grid<u8>, new: &Grid<u8>) -> Option<String> {
+    let born = cells_where(new, |x, y| old.get(x, y) != 1 && new.get(x, y) == 1);
+    let died = cells_where(new, |x, y| old.get(x, y) == 1 && new.get(x, y) != 1);
+    if born.is_empty() && died.is_empty() {
+        return None;
+    }
+    Some(json!({"type": "diff", "born": born, "died": died}).to_string())
+}
+
+fn game_view(status: &str) -> String {
+    json!({"type": "view", "mode": "game", "status": status}).to_string()
+}
+
+fn editor_view(editor: &Editor, grid: &Grid<u8>, status: &str) -> String {
+    let (x, y) = editor.get_cursor();
+    let selection = match editor.selection() {
+        Some(s) => json!([s.x, s.y, s.width, s.height]),
+        None => Value::Null,
+    };
+    // the pending stamp, wrapped the same way as when it's placed
+    let (w, h) = (grid.width().max(1), grid.height().max(1));
+    let stamp: Vec<_> = match editor.stamp() {
+        Some(stamp) => stamp
+            .pattern()
+            .cells
+            .iter()
+            .map(|&(i, j)| [(x + i) % w, (y + j) % h])
+            .collect(),
+        None => Vec::new(),
+    };
+    json!({
+        "type": "view",
+        "mode": "editor",
+        "status": status,
+        "cursor": [x, y],
+        "selection": selection,
+        "stamp": stamp,
+    })
+    .to_string()
+}
+
+fn browser_view(browser: &Browser) -> String {
+    let mut title = format!("Patterns ({})", browser.library().patterns().len());
+    if browser.library().skipped() > 0 {
+        title += &format!(", {} files skipped", browser.library().skipped());
+    }
+    title += " - type to search, Tab/arrows to move, Enter to stamp, Esc to cancel";
+    let matches = browser.matches();
+    let selected = browser.selected_index();
+    let first = (selected + 1).saturating_sub(BROWSER_ROWS);
+    let names: Vec<_> = matches
+        .iter()
+        .skip(first)
+        .take(BROWSER_ROWS)
+        .map(|pattern| pattern.name.as_str())
+        .collect();
+    json!({
+        "type": "view",
+        "mode": "browser",
+        "title": title,
+        "query": browser.query(),
+        "matches": names,
+        "selected": selected - first,
+    })
+    .to_string()
+}
+
+impl GameBackend for Web {
+    fn num_cols(&self) -> usize {
+        COLS
+    }
+
+    fn num_rows(&self) -> usize {
+        ROWS
+    }
+
+    fn viewport(&self) -> (usize, usize) {
+        *self.viewport.borrow()
+    }
+
+    /// The viewport is remembered for sessions, but browsers always show the whole board.
+    fn set_viewport(&self, x: usize, y: usize) {
+        *self.viewport.borrow_mut() = (x, y);
+    }
+
+    /// Receives this frame's input, so it must be called before `edit_actions`.
+    fn app_actions(&self, game_state: GameState) -> Box<dyn Iterator<Item = AppAction>> {
+        self.read_inputs(game_state);
+        self.events.borrow_mut().take_app_actions()
+    }
+
+    fn edit_actions(&self) -> Box<dyn Iterator<Item = EditAction>> {
+        self.events.borrow_mut().take_edit_actions()
+    }
+
+    /// Receives this frame's input, as `app_actions` isn't called while browsing.
+    fn browse_actions(&self) -> Box<dyn Iterator<Item = BrowseAction>> {
+        self.read_inputs(GameState::Browsing);
+        self.events.borrow_mut().take_browse_actions()
+    }
+
+    /// Reads this frame's input, as `app_actions` isn't called at the console.
+    fn console_actions(&self) -> Box<dyn Iterator<Item = ConsoleAction>> {
+        self.read_inputs(GameState::Console);
+        self.events.borrow_mut().take_console_actions()
+    }
+
+    fn draw_game(&self, grid: &Grid<u8>, status: &str) -> Result<(), Error> {
+        self.publish(Some(grid), game_view(status));
+        Ok(())
+    }
+
+    fn draw_editor(&self, editor: &Editor, grid: &Grid<u8>, status: &str) -> Result<(), Error> {
+        self.publish(Some(grid), editor_view(editor, grid, status));
+        Ok(())
+    }
+
+    fn draw_browser(&self, browser: &Browser) -> Result<(), Error> {
+        self.publish(None, browser_view(browser));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> Value {
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn keys_are_read_with_their_modifiers() {
+        assert_eq!(
+            parse_input("key - p"),
+            Some(Input::Key(Key::Char('p'), false))
+        );
+        assert_eq!(
+            parse_input("key s P"),
+            Some(Input::Key(Key::Char('P'), true))
+        );
+        assert_eq!(
+            parse_input("key c z"),
+            Some(Input::Key(Key::Char('\x1a'), false))
+        );
+        assert_eq!(
+            parse_input("key - ArrowUp"),
+            Some(Input::Key(Key::Up, false))
+        );
+        assert_eq!(
+            parse_input("key - F2"),
+            Some(Input::Key(Key::Function(2), false))
+        );
+        assert_eq!(parse_input("key - Shift"), None);
+        assert_eq!(parse_input("key c 1"), None);
+    }
+
+    #[test]
+    fn actions_are_read_with_their_cells() {
+        assert_eq!(parse_input("app Quit"), Some(Input::App(AppAction::Quit)));
+        assert_eq!(
+            parse_input("edit ToggleCell"),
+            Some(Input::Edit(EditAction::ToggleCell))
+        );
+        assert_eq!(
+            parse_input("edit AddCellAt 3 4"),
+            Some(Input::Edit(EditAction::AddCellAt { x: 3, y: 4 }))
+        );
+        assert_eq!(
+            parse_input("edit MoveCursorBy -1 0"),
+            Some(Input::Edit(EditAction::MoveCursorBy { x: -1, y: 0 }))
+        );
+    }
+
+    #[test]
+    fn offsets_larger_than_the_board_wrap_round() {
+        let action = match parse_input("edit MoveCursorBy -1000 250") {
+            Some(Input::Edit(action)) => action,
+            input => panic!("{:?}", input),
+        };
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(100, 100));
+        editor.set_cursor(5, 5);
+        editor.apply_action(action, &mut grid);
+        assert_eq!(editor.get_cursor(), (5, 55));
+    }
+
+    #[test]
+    fn anything_else_is_ignored() {
+        for text in [
+            "",
+            "app",
+            "app Fly",
+            "edit AddCellAt 3",
+            "edit AddCellAt x y",
+            "jump",
+        ] {
+            assert_eq!(parse_input(text), None, "{:?}", text);
+        }
+        for text in [
+            "edit MoveCursorBy -9223372036854775808 0",
+            "edit AddCellAt 0 2147483648",
+        ] {
+            assert_eq!(parse_input(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn a_diff_lists_the_cells_born_and_died_by_index() {
+        let old = Grid::with_data(3, 2, vec![1, 0, 0, 0, 1, 0]);
+        let new = Grid::with_data(3, 2, vec![0, 0, 1, 0, 1, 1]);
+        assert_eq!(
+            message(&diff_message(&old, &new).unwrap()),
+            json!({"type": "diff", "born": [2, 5], "died": [0]})
+        );
+        assert_eq!(diff_message(&new, &new), None);
+    }
+
+    #[test]
+    fn text_in_messages_is_escaped() {
+        let status = "say \"hi\" \\ \u{7}";
+        assert_eq!(message(&game_view(status))["status"], status);
+    }
+
+    #[test]
+    fn only_the_games_own_page_is_the_same_origin() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let check = |host, origin| is_same_origin(addr, Some(host), Some(origin));
+        assert!(check("127.0.0.1:8080", "http://127.0.0.1:8080"));
+        assert!(check("localhost:8080", "http://localhost:8080"));
+        assert!(!check("127.0.0.1:8080", "http://evil.example"));
+        assert!(!check("127.0.0.1:8080", "null"));
+        // a name pointed at this machine by DNS rebinding
+        assert!(!check("evil.example:8080", "http://evil.example:8080"));
+        assert!(!check("127.0.0.1:9090", "http://127.0.0.1:9090"));
+        assert!(!is_same_origin(addr, Some("127.0.0.1:8080"), None));
+        assert!(!is_same_origin(addr, None, Some("http://127.0.0.1:8080")));
+        let anywhere: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+        let (host, origin) = (Some("192.168.1.5:8080"), Some("http://192.168.1.5:8080"));
+        assert!(is_same_origin(anywhere, host, origin));
+        assert!(!is_same_origin(
+            anywhere,
+            Some("localhost:8080"),
+            Some("http://localhost:8080")
+        ));
+    }
+
+    #[test]
+    fn websockets_from_other_pages_are_refused() {
+        use tungstenite::client::IntoClientRequest;
+        use tungstenite::HandshakeError;
+
+        // a free port, as the backend doesn't say which it was given
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let _web = Web::new(addr).unwrap();
+        // the status of a refusal, or `None` if the WebSocket was accepted
+        let refusal = |origin: &str| {
+            let mut request = format!("ws://{}/", addr).into_client_request().unwrap();
+            request
+                .headers_mut()
+                .insert("Origin", origin.parse().unwrap());
+            match tungstenite::client(request, TcpStream::connect(addr).unwrap()) {
+                Ok(_) => None,
+                Err(HandshakeError::Failure(tungstenite::Error::Http(response))) => {
+                    Some(response.status())
+                }
+                Err(err) => panic!("{}", err),
+            }
+        };
+        assert_eq!(refusal("http://evil.example"), Some(StatusCode::FORBIDDEN));
+        assert_eq!(refusal(&format!("http://{}", addr)), None);
+    }
+}
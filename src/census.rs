@@ -0,0 +1,479 @@
+//! Splitting the board into separate objects and naming them, as apgsearch does.
+//!
+//! Each object is run on its own to find its period, then given an apgcode: `xs` for a
+//! still life, `xp` for an oscillator or `xq` for a spaceship, followed by its population
+//! or period and the extended Wechsler format of its smallest phase and orientation.
+
+use grid::{Grid, NEIGHBOUR_POSITIONS};
+use rule::Rule;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use topology::{Topology, Twist};
+
+type Cell = (isize, isize);
+
+/// How long an object is run on its own to find its period.
+const MAX_PERIOD: u64 = 512;
+
+/// How long the pieces of a group are run apart, to check that they don't interact.
+const SEPARATION_GENERATIONS: u64 = 128;
+
+/// Objects that grow bigger than this are given up on.
+const MAX_POPULATION: usize = 1000;
+
+/// Only phases that fit in a square this size are canonised, as in apgsearch.
+const MAX_CANONICAL_SIZE: isize = 40;
+
+/// The code given to objects that don't repeat within `MAX_PERIOD` generations.
+pub static PATHOLOGICAL: &str = "PATHOLOGICAL";
+
+static WECHSLER_CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// The names of some common objects.
+static NAMES: &[(&str, &str)] = &[
+    ("xs4_33", "block"),
+    ("xs6_696", "beehive"),
+    ("xs7_2596", "loaf"),
+    ("xs5_253", "boat"),
+    ("xs6_356", "ship"),
+    ("xs4_252", "tub"),
+    ("xs8_6996", "pond"),
+    ("xs6_25a4", "barge"),
+    ("xs7_25ac", "long boat"),
+    ("xp2_7", "blinker"),
+    ("xp2_7e", "toad"),
+    ("xp2_318c", "beacon"),
+    ("xp3_co9nas0san9oczgoldlo0oldlogz1047210127401", "pulsar"),
+    ("xp15_4r4z4r4", "pentadecathlon"),
+    ("xq4_153", "glider"),
+    ("xq4_6frc", "lightweight spaceship"),
+    ("xq4_27dee6", "middleweight spaceship"),
+    ("xq4_27deee6", "heavyweight spaceship"),
+];
+
+/// The common name for an apgcode, if it has one.
+pub fn name(code: &str) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|&&(c, _)| c == code)
+        .map(|&(_, name)| name)
+}
+
+/// A linear map of steps across the board, `((a, b), (c, d))` taking a step `(x, y)` to
+/// `(a x + b y, c x + d y)`.
+type Turn = ((isize, isize), (isize, isize));
+
+const STRAIGHT: Turn = ((1, 0), (0, 1));
+const MIRROR_X: Turn = ((-1, 0), (0, 1));
+const MIRROR_Y: Turn = ((1, 0), (0, -1));
+
+fn turn(((a, b), (c, d)): Turn, (x, y): Cell) -> Cell {
+    (a * x + b * y, c * x + d * y)
+}
+
+/// The turn made by `second` and then `first`.
+fn after(((a, b), (c, d)): Turn, ((e, f), (g, h)): Turn) -> Turn {
+    (
+        (a * e + b * g, a * f + b * h),
+        (c * e + d * g, c * f + d * h),
+    )
+}
+
+/// How steps on the board from where the position `(x, y)` beyond an edge wraps to are
+/// turned to carry on in the same direction as the step that crossed the edge.
+fn across_edge(topology: Topology, (x, y): Cell, width: usize, height: usize) -> Turn {
+    let outside_x = x < 0 || x >= width as isize;
+    let outside_y = y < 0 || y >= height as isize;
+    match topology {
+        Topology::KleinBottle(Twist::TopBottom) | Topology::CrossSurface if outside_y => MIRROR_X,
+        Topology::KleinBottle(Twist::LeftRight) | Topology::CrossSurface if outside_x => MIRROR_Y,
+        Topology::Sphere if outside_y => ((0, 1), (-1, 0)),
+        Topology::Sphere if outside_x => ((0, -1), (1, 0)),
+        _ => STRAIGHT,
+    }
+}
+
+/// The groups of live cells that are close enough to affect each other. Cells are in the
+/// same group if they are within two cells of each other, following the edges of the
+/// grid where they are joined. Each group's cells are given relative to one of them, and
+/// aren't wrapped round the edges: cells across a twisted edge are turned back the way
+/// they face from the first cell.
+pub fn objects(grid: &Grid<u8>, topology: Topology) -> Vec<Vec<Cell>> {
+    let (width, height) = (grid.width(), grid.height());
+    let mut seen = Grid::new(width, height);
+    let mut objects = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if grid.get(x, y) == 0 || seen.get(x, y) {
+                continue;
+            }
+            seen.set(x, y, true);
+            let mut cells = Vec::new();
+            let mut stack = vec![((x, y), (0, 0), STRAIGHT)];
+            while let Some(((x, y), (i, j), facing)) = stack.pop() {
+                cells.push((i, j));
+                for dy in -2..=2 {
+                    for dx in -2..=2 {
+                        let beyond = (x as isize + dx, y as isize + dy);
+                        let wrapped = topology.wrap(beyond.0, beyond.1, width, height);
+                        if let Some((nx, ny)) = wrapped {
+                            if grid.get(nx, ny) != 0 && !seen.get(nx, ny) {
+                                seen.set(nx, ny, true);
+                                let (di, dj) = turn(facing, (dx, dy));
+                                let edge = across_edge(topology, beyond, width, height);
+                                stack.push(((nx, ny), (i + di, j + dj), after(facing, edge)));
+                            }
+                        }
+                    }
+                }
+            }
+            objects.push(cells);
+        }
+    }
+    objects
+}
+
+/// The next generation of a pattern on an unbounded plane.
+fn step(cells: &HashSet<Cell>, rule: &Rule) -> HashSet<Cell> {
+    let mut neighbourhoods: HashMap<Cell, u8> = cells.iter().map(|&cell| (cell, 0)).collect();
+    for &(x, y) in cells {
+        for (i, &(dx, dy)) in NEIGHBOUR_POSITIONS.iter().enumerate() {
+            *neighbourhoods.entry((x - dx, y - dy)).or_insert(0) |= 1 << i;
+        }
+    }
+    neighbourhoods
+        .into_iter()
+        .filter(|&(cell, neighbourhood)| rule.next_state(cells.contains(&cell), neighbourhood))
+        .map(|(cell, _)| cell)
+        .collect()
+}
+
+/// Splits a group of cells into the pieces that touch, if running the pieces apart gives
+/// the same result as running them together. Otherwise the group is one object.
+fn separate(cells: &[Cell], rule: &Rule) -> Vec<Vec<Cell>> {
+    let mut remaining: HashSet<Cell> = cells.iter().cloned().collect();
+    let mut pieces: Vec<HashSet<Cell>> = Vec::new();
+    while let Some(&first) = remaining.iter().next() {
+        remaining.remove(&first);
+        let mut piece = HashSet::new();
+        let mut stack = vec![first];
+        while let Some((x, y)) = stack.pop() {
+            piece.insert((x, y));
+            for &(dx, dy) in NEIGHBOUR_POSITIONS.iter() {
+                if remaining.remove(&(x + dx, y + dy)) {
+                    stack.push((x + dx, y + dy));
+                }
+            }
+        }
+        pieces.push(piece);
+    }
+    if pieces.len() > 1 {
+        let mut together: HashSet<Cell> = cells.iter().cloned().collect();
+        let mut apart = pieces.clone();
+        for _ in 0..SEPARATION_GENERATIONS {
+            together = step(&together, rule);
+            apart = apart.iter().map(|piece| step(piece, rule)).collect();
+            let union: HashSet<Cell> = apart.iter().flatten().cloned().collect();
+            if union != together || together.len() > MAX_POPULATION {
+                return vec![cells.to_vec()];
+            }
+        }
+    }
+    pieces
+        .into_iter()
+        .map(|piece| piece.into_iter().collect())
+        .collect()
+}
+
+/// The cells moved so that their bounding box is at the origin, in order, with where the
+/// box was.
+fn normalise(cells: &HashSet<Cell>) -> (Vec<Cell>, Cell) {
+    let x0 = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+    let y0 = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+    let mut normalised: Vec<Cell> = cells.iter().map(|&(x, y)| (x - x0, y - y0)).collect();
+    normalised.sort();
+    (normalised, (x0, y0))
+}
+
+/// One strip of five rows after another, each column a character with the top row as the
+/// lowest bit, and runs of empty columns shortened.
+fn wechsler(cells: &HashSet<Cell>, width: isize, height: isize) -> String {
+    let mut out = String::new();
+    for strip in 0..(height + 4) / 5 {
+        if strip > 0 {
+            out.push('z');
+        }
+        let mut zeroes = 0;
+        for x in 0..width {
+            let column = (0..5)
+                .filter(|&row| cells.contains(&(x, strip * 5 + row)))
+                .fold(0, |column, row| column | 1 << row);
+            if column == 0 {
+                zeroes += 1;
+                continue;
+            }
+            match zeroes {
+                0 => {}
+                1 => out.push('0'),
+                2 => out.push('w'),
+                3 => out.push('x'),
+                n => {
+                    out.push('y');
+                    out.push(WECHSLER_CHARS[n - 4] as char);
+                }
+            }
+            zeroes = 0;
+            out.push(WECHSLER_CHARS[column] as char);
+        }
+    }
+    out
+}
+
+/// The smallest description of a phase over its eight orientations, if it's small enough.
+fn canonise_phase(cells: &[Cell]) -> Option<String> {
+    let width = cells.iter().map(|&(x, _)| x + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+    if width > MAX_CANONICAL_SIZE || height > MAX_CANONICAL_SIZE {
+        return None;
+    }
+    let (w, h) = (width - 1, height - 1);
+    let orientations: [&dyn Fn(Cell) -> Cell; 8] = [
+        &|(x, y)| (x, y),
+        &|(x, y)| (w - x, y),
+        &|(x, y)| (x, h - y),
+        &|(x, y)| (w - x, h - y),
+        &|(x, y)| (y, x),
+        &|(x, y)| (y, w - x),
+        &|(x, y)| (h - y, x),
+        &|(x, y)| (h - y, w - x),
+    ];
+    orientations
+        .iter()
+        .enumerate()
+        .map(|(i, orientation)| {
+            let transformed: HashSet<Cell> = cells.iter().map(|&c| orientation(c)).collect();
+            if i < 4 {
+                wechsler(&transformed, width, height)
+            } else {
+                wechsler(&transformed, height, width)
+            }
+        })
+        .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+}
+
+/// The apgcode of an object, found by running it on its own under `rule`.
+pub fn apgcode(cells: &[Cell], rule: &Rule) -> String {
+    let start: HashSet<Cell> = cells.iter().cloned().collect();
+    let (shape, origin) = normalise(&start);
+    if shape.is_empty() {
+        return "0".to_owned();
+    }
+    let mut phases = vec![shape.clone()];
+    let mut current = start;
+    for period in 1..=MAX_PERIOD {
+        current = step(&current, rule);
+        if current.len() > MAX_POPULATION {
+            break;
+        }
+        let (phase, position) = normalise(&current);
+        if phase != shape {
+            phases.push(phase);
+            continue;
+        }
+        let prefix = if position != origin {
+            format!("xq{}", period)
+        } else if period == 1 {
+            format!("xs{}", shape.len())
+        } else {
+            format!("xp{}", period)
+        };
+        let description = phases
+            .iter()
+            .filter_map(|phase| canonise_phase(phase))
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+            .unwrap_or_else(|| "#".to_owned());
+        return format!("{}_{}", prefix, description);
+    }
+    PATHOLOGICAL.to_owned()
+}
+
+/// The number of each kind of object on the board.
+#[derive(Debug, Default)]
+pub struct Census {
+    /// Apgcodes with how many there are, most common first.
+    pub counts: Vec<(String, usize)>,
+}
+
+impl Census {
+    /// Counts the objects on the board, running each on its own under `rule` to name it.
+    pub fn new(grid: &Grid<u8>, topology: Topology, rule: &Rule) -> Census {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for group in objects(grid, topology) {
+            for object in separate(&group, rule) {
+                *counts.entry(apgcode(&object, rule)).or_insert(0) += 1;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Census { counts }
+    }
+
+    /// The number of objects.
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|&(_, count)| count).sum()
+    }
+
+    /// A one line summary, e.g. `3 block, 1 blinker, 1 xs6_356`.
+    pub fn summary(&self) -> String {
+        if self.counts.is_empty() {
+            return "no objects".to_owned();
+        }
+        let items: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(code, count)| format!("{} {}", count, name(code).unwrap_or(code)))
+            .collect();
+        items.join(", ")
+    }
+}
+
+impl Display for Census {
+    /// A table of counts, apgcodes and names.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{:>6}  {:<24}  name", "count", "apgcode")?;
+        for (code, count) in &self.counts {
+            writeln!(
+                f,
+                "{:>6}  {:<24}  {}",
+                count,
+                code,
+                name(code).unwrap_or("")
+            )?;
+        }
+        write!(f, "{:>6}  objects", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid with the cells marked `o` in the rows alive.
+    fn grid(rows: &[&str]) -> Grid<u8> {
+        let mut grid = Grid::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'o' {
+                    grid.set(x, y, 1);
+                }
+            }
+        }
+        grid
+    }
+
+    fn code(rows: &[&str]) -> String {
+        let objects = objects(&grid(rows), Topology::Plane);
+        assert_eq!(objects.len(), 1);
+        apgcode(&objects[0], &Rule::life())
+    }
+
+    #[test]
+    fn still_lifes_oscillators_and_spaceships_are_coded() {
+        assert_eq!(code(&["oo", "oo"]), "xs4_33");
+        assert_eq!(code(&[".oo.", "o..o", ".oo."]), "xs6_696");
+        assert_eq!(code(&["ooo"]), "xp2_7");
+        assert_eq!(code(&["o", "o", "o"]), "xp2_7");
+        assert_eq!(code(&[".o.", "..o", "ooo"]), "xq4_153");
+        assert_eq!(code(&["o..o.", "....o", "o...o", ".oooo"]), "xq4_6frc");
+        assert_eq!(name("xq4_6frc"), Some("lightweight spaceship"));
+    }
+
+    #[test]
+    fn every_orientation_and_phase_has_the_same_code() {
+        // the glider's other phases, flipped and rotated
+        assert_eq!(code(&["o.o", ".oo", ".o."]), "xq4_153");
+        assert_eq!(code(&["ooo", "o..", ".o."]), "xq4_153");
+        assert_eq!(code(&["o..", "o.o", "oo."]), "xq4_153");
+    }
+
+    #[test]
+    fn separate_objects_are_counted_apart() {
+        let grid = grid(&[
+            "oo.......",
+            "oo.......",
+            ".........",
+            ".........",
+            "......ooo",
+            ".........",
+            "...oo....",
+            "...oo....",
+        ]);
+        let census = Census::new(&grid, Topology::Plane, &Rule::life());
+        assert_eq!(
+            census.counts,
+            [("xs4_33".to_owned(), 2), ("xp2_7".to_owned(), 1)]
+        );
+        assert_eq!(census.total(), 3);
+        assert_eq!(census.summary(), "2 block, 1 blinker");
+    }
+
+    #[test]
+    fn objects_are_followed_across_joined_edges() {
+        let grid = grid(&["o....o", "o....o", "......", "......"]);
+        assert_eq!(objects(&grid, Topology::Plane).len(), 2);
+        let census = Census::new(&grid, Topology::Torus, &Rule::life());
+        assert_eq!(census.counts, [("xs4_33".to_owned(), 1)]);
+        let empty = Census::new(&Grid::new(4, 4), Topology::Torus, &Rule::life());
+        assert_eq!(empty.summary(), "no objects");
+    }
+
+    fn blocks(rows: &[&str], topology: Topology) -> Vec<(String, usize)> {
+        Census::new(&grid(rows), topology, &Rule::life()).counts
+    }
+
+    #[test]
+    fn offsets_carry_on_the_same_way_past_a_twisted_edge() {
+        // a hook whose top row is across the top edge of a K8*,6 board, mirrored
+        let hook = [
+            ".o......", "........", "........", "........", "........", "..ooooo.",
+        ];
+        let objects = objects(&grid(&hook), Topology::KleinBottle(Twist::TopBottom));
+        assert_eq!(objects.len(), 1);
+        let mut cells = objects[0].clone();
+        cells.sort();
+        assert_eq!(cells, [(0, -1), (0, 0), (1, -1), (2, -1), (3, -1), (4, -1)]);
+    }
+
+    #[test]
+    fn objects_are_turned_back_across_twisted_edges() {
+        let block = vec![("xs4_33".to_owned(), 1)];
+        // the top row is joined to the bottom row mirrored, on a K6*,4 board
+        let klein = [".oo...", "......", "......", "...oo."];
+        assert_eq!(
+            blocks(&klein, Topology::KleinBottle(Twist::TopBottom)),
+            block
+        );
+        let boat = ["o.o...", ".o....", "......", "....oo"];
+        assert_eq!(
+            blocks(&boat, Topology::KleinBottle(Twist::TopBottom)),
+            [("xs5_253".to_owned(), 1)]
+        );
+        // cells found across the edge lead on to the rest of a pond the right way round
+        let pond = [".o..o.", "..oo..", "......", "......", "..oo..", ".o..o."];
+        assert_eq!(
+            blocks(&pond, Topology::KleinBottle(Twist::TopBottom)),
+            [("xs8_6996".to_owned(), 1)]
+        );
+        // and the left column to the right column mirrored, on a K4,6* board
+        let klein = ["....", "o...", "o...", "...o", "...o", "...."];
+        assert_eq!(
+            blocks(&klein, Topology::KleinBottle(Twist::LeftRight)),
+            block
+        );
+        let cross = ["....", "o...", "o...", "...o", "...o", "...."];
+        assert_eq!(blocks(&cross, Topology::CrossSurface), block);
+        // the top row is joined to the left column, on an S5 board
+        let sphere = ["..oo.", ".....", "o....", "o....", "....."];
+        assert_eq!(blocks(&sphere, Topology::Sphere), block);
+    }
+}
@@ -0,0 +1,212 @@
+//! Carrying out commands from the console and requests from other programs, and the
+//! board the runner edits and steps.
+
+use crate::{AppAction, GameState};
+use backend::names::applies_in;
+use backend::GameBackend;
+use console::Command;
+use editor::{EditAction, Editor};
+use export::animation::Recorder;
+use game::Gol;
+use pattern::Pattern;
+use remote::{self, Call, Failure, Remote, Request};
+use runner::{Runner, Simulation};
+use script;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The game and the editor, as the runner works on them.
+pub struct Board<'a> {
+    pub game: &'a Mutex<Gol>,
+    pub editor: &'a Mutex<Editor>,
+    pub recording: &'a Mutex<Option<Recorder>>,
+    /// The generation to hold at while a replay waits for its next action.
+    pub limit: &'a AtomicU64,
+    /// Programs to tell about each generation.
+    pub remote: Option<&'a Remote>,
+}
+
+impl<'a> Simulation for Board<'a> {
+    fn edit(&mut self, action: EditAction) {
+        let mut game = self.game.lock().unwrap();
+        let mut editor = self.editor.lock().unwrap();
+        editor.set_topology(game.topology());
+        editor.apply_action(action, game.grid_mut());
+    }
+
+    fn step(&mut self) -> bool {
+        let mut game = self.game.lock().unwrap();
+        if game.generation() >= self.limit.load(Ordering::Relaxed) {
+            return false;
+        }
+        advance(&mut game, self.recording);
+        if let Some(remote) = self.remote {
+            remote.announce(&game);
+        }
+        true
+    }
+}
+
+/// Runs a generation, adding it to the recording if there is one.
+pub fn advance(game: &mut Gol, recording: &Mutex<Option<Recorder>>) {
+    game.next_turn();
+    if let Some(recorder) = recording.lock().unwrap().as_mut() {
+        recorder.record(game.grid());
+    }
+}
+
+/// Carries out a command from the console or another program, other than app actions, and
+/// returns what to show on the status line, or why it couldn't be done. Edits go through
+/// the editor, so they can be undone.
+pub fn run_command<Ui: GameBackend + ?Sized>(
+    command: Command,
+    game: &Mutex<Gol>,
+    editor: &Mutex<Editor>,
+    ui: &Ui,
+    runner: &mut Runner,
+) -> Result<Option<String>, String> {
+    // locked in the same order as the runner's edits are
+    let mut game = game.lock().unwrap();
+    let mut editor = editor.lock().unwrap();
+    editor.set_topology(game.topology());
+    let message = match command {
+        Command::App(_) => None,
+        Command::Edit(action) => {
+            editor.apply_action(action.with_seed(|| ui.seed()), game.grid_mut());
+            editor.message().map(str::to_owned)
+        }
+        Command::Rule(rule) => {
+            let message = format!("Rule: {}", rule);
+            game.set_rule(*rule);
+            Some(message)
+        }
+        Command::Load { path, at } => {
+            let pattern = Pattern::load(&path)
+                .map_err(|err| format!("Couldn't load {}: {}", path.display(), err))?;
+            let (width, height) = (game.grid().width(), game.grid().height());
+            let (x, y) = at.unwrap_or_else(|| editor.get_cursor());
+            if x >= width || y >= height {
+                return Err(format!("{}, {} is off the board", x, y));
+            }
+            let name = pattern.name.clone();
+            let grid = game.grid_mut();
+            editor.apply_action(EditAction::MoveCursorTo { x, y }, grid);
+            editor.apply_action(EditAction::AddPattern(Box::new(pattern)), grid);
+            editor.apply_action(EditAction::CommitStamp, grid);
+            Some(format!("Placed {} at {}, {}", name, x, y))
+        }
+        Command::Step(generations) => {
+            // run a frame at a time, so that they can be stopped
+            runner.step(generations);
+            None
+        }
+        Command::Resize { width, height } => {
+            game.resize(width, height);
+            editor.resize(width, height);
+            // the view may be off the edge of a smaller board
+            ui.set_viewport(0, 0);
+            None
+        }
+        Command::Save(path) => {
+            game.save(&path)
+                .map_err(|err| format!("Couldn't save the board: {}", err))?;
+            Some(format!("Saved the board to {}", path.display()))
+        }
+        Command::Script(path) => {
+            let size = (game.grid().width(), game.grid().height());
+            // the last line the script prints is shown on the status line
+            let printed = Rc::new(RefCell::new(None));
+            let last = Rc::clone(&printed);
+            let (limit, seed) = (Some(script::TIME_LIMIT), ui.seed());
+            let result = script::run(&path, &mut game, &mut editor, limit, seed, move |line| {
+                *last.borrow_mut() = Some(line.to_owned());
+            });
+            if (game.grid().width(), game.grid().height()) != size {
+                ui.set_viewport(0, 0);
+            }
+            result.map_err(|err| err.to_string())?;
+            let printed = printed.borrow_mut().take();
+            Some(printed.unwrap_or_else(|| format!("Ran {}", path.display())))
+        }
+    };
+    Ok(message)
+}
+
+/// Answers a request from another program. App actions are taken along with those from
+/// the keys, and only where their keys would work; everything else is done straight away.
+pub fn answer_request<Ui: GameBackend + ?Sized>(
+    request: Request,
+    state: GameState,
+    commanded: &mut Vec<AppAction>,
+    game: &Mutex<Gol>,
+    editor: &Mutex<Editor>,
+    ui: &Ui,
+    runner: &mut Runner,
+) {
+    let result = request.call().and_then(|call| match call {
+        Call::App(action) | Call::Command(Command::App(action)) => {
+            let typing = matches!(state, GameState::Browsing | GameState::Console);
+            if typing || !applies_in(state, action) {
+                return Err(Failure::Failed(format!(
+                    "Can't {:?} while {:?}",
+                    action, state
+                )));
+            }
+            commanded.push(action);
+            Ok(Value::Null)
+        }
+        Call::Pause | Call::Resume => {
+            let running = matches!(call, Call::Resume);
+            match state {
+                GameState::Running | GameState::Paused => {
+                    if (state == GameState::Running) != running {
+                        commanded.push(AppAction::TogglePause);
+                    }
+                    Ok(Value::Null)
+                }
+                _ => Err(Failure::Failed(format!(
+                    "Can't pause or resume while {:?}",
+                    state
+                ))),
+            }
+        }
+        Call::Command(command) => run_command(command, game, editor, ui, runner)
+            .map(|message| json!({ "message": message }))
+            .map_err(Failure::Failed),
+        Call::Region(area) => remote::region(&game.lock().unwrap(), area),
+        Call::SetCells { cells, alive } => {
+            // locked in the same order as the runner's edits are
+            let mut game = game.lock().unwrap();
+            remote::set_cells(&mut game, &mut editor.lock().unwrap(), &cells, alive)
+        }
+        Call::Stats => Ok(remote::stats(&game.lock().unwrap(), state)),
+    });
+    request.respond(result);
+}
+
+/// The size of a board that fills the screen. The bottom row of the terminal is the
+/// status line, and a board needs a cell.
+pub fn screen_board_size<Ui: GameBackend + ?Sized>(ui: &Ui) -> (usize, usize) {
+    (ui.num_cols().max(1), ui.num_rows().saturating_sub(1).max(1))
+}
+
+/// The size of the board once it follows the screen from one size to another, changed in
+/// whichever directions it was the size of the screen before.
+pub fn follow_screen(
+    from: (usize, usize),
+    to: (usize, usize),
+    board: (usize, usize),
+) -> (usize, usize) {
+    let width = if board.0 == from.0 { to.0 } else { board.0 };
+    let height = if board.1 == from.1 { to.1 } else { board.1 };
+    (width, height)
+}
+
+/// Changes the size of the board as it follows the screen, or did in a recording.
+pub fn resize_board(game: &mut Gol, editor: &Mutex<Editor>, width: usize, height: usize) {
+    game.resize(width, height);
+    editor.lock().unwrap().resize(width, height);
+}
@@ -0,0 +1,499 @@
+//! The `:` command line, for things too fiddly for a single key, e.g. `:rule B36/S23`.
+
+use crate::AppAction;
+use editor::EditAction;
+use pattern::MAX_SIZE;
+use rule::{self, Rule};
+use soup::{self, Symmetry};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleAction {
+    Type(char),
+    Backspace,
+    Complete,
+    Previous,
+    Next,
+    Run,
+    Cancel,
+}
+
+/// The commands, with the arguments they take.
+static COMMANDS: [(&str, &str); 18] = [
+    ("census", ""),
+    ("clear", ""),
+    ("edit", ""),
+    ("faster", ""),
+    ("load", "FILE [X Y]"),
+    ("pause", ""),
+    ("quit", ""),
+    ("random", "[DENSITY] [seed=N] [symmetry=S]"),
+    ("record", ""),
+    ("redo", ""),
+    ("resize", "WIDTH HEIGHT"),
+    ("rule", "RULE"),
+    ("save", "FILE"),
+    ("script", "FILE"),
+    ("slower", ""),
+    ("snapshot", ""),
+    ("step", "[GENERATIONS]"),
+    ("undo", ""),
+];
+
+/// The commands whose argument is a file name, for tab completion.
+static FILE_COMMANDS: [&str; 3] = ["load", "save", "script"];
+
+/// How many lines the history keeps.
+const HISTORY_LIMIT: usize = 100;
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownCommand(String),
+    /// The arguments didn't fit the command with this name.
+    Usage(&'static str),
+    Rule(rule::Error),
+    Soup(soup::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownCommand(name) => write!(f, "Unknown command: {}", name),
+            Error::Usage(name) => {
+                let args = COMMANDS
+                    .iter()
+                    .find(|(command, _)| command == name)
+                    .map_or("", |&(_, args)| args);
+                write!(f, "Usage: {}", format!(":{} {}", name, args).trim_end())
+            }
+            Error::Rule(err) => write!(f, "{}", err),
+            Error::Soup(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<rule::Error> for Error {
+    fn from(other: rule::Error) -> Error {
+        Error::Rule(other)
+    }
+}
+
+impl From<soup::Error> for Error {
+    fn from(other: soup::Error) -> Error {
+        Error::Soup(other)
+    }
+}
+
+/// A line typed at the console.
+#[derive(Debug)]
+pub enum Command {
+    /// Something a key does outside Edit mode.
+    App(AppAction),
+    /// Something a key does in Edit mode, which can be undone.
+    Edit(EditAction),
+    Rule(Box<Rule>),
+    /// Places a pattern file with its top left corner at a cell, or at the cursor.
+    Load {
+        path: PathBuf,
+        at: Option<(usize, usize)>,
+    },
+    /// Runs generations, whether or not the game is running.
+    Step(u64),
+    /// Changes the size of the board, keeping the cells in the top left corner that fit.
+    Resize {
+        width: usize,
+        height: usize,
+    },
+    /// Saves the whole board as a pattern file.
+    Save(PathBuf),
+    /// Runs a Rhai script on the board.
+    Script(PathBuf),
+}
+
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Command, Error> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let name = match COMMANDS.iter().find(|&&(command, _)| command == name) {
+            Some(&(command, _)) => command,
+            None => return Err(Error::UnknownCommand(name.to_owned())),
+        };
+        let args: Vec<&str> = words.collect();
+        let usage = || Error::Usage(name);
+        let number = |arg: &str| arg.parse::<usize>().map_err(|_| usage());
+        let command = match (name, args.as_slice()) {
+            ("census", []) => Command::App(AppAction::Census),
+            ("edit", []) => Command::App(AppAction::EditMode),
+            ("faster", []) => Command::App(AppAction::Faster),
+            ("pause", []) => Command::App(AppAction::TogglePause),
+            ("quit", []) => Command::App(AppAction::Quit),
+            ("record", []) => Command::App(AppAction::ToggleRecording),
+            ("slower", []) => Command::App(AppAction::Slower),
+            ("snapshot", []) => Command::App(AppAction::Snapshot),
+            ("clear", []) => Command::Edit(EditAction::Clear),
+            ("redo", []) => Command::Edit(EditAction::Redo),
+            ("undo", []) => Command::Edit(EditAction::Undo),
+            ("random", args) => random_soup(args).ok_or_else(usage)?,
+            ("rule", [rule]) => Command::Rule(Box::new(rule.parse()?)),
+            ("load", [path]) => Command::Load {
+                path: path.into(),
+                at: None,
+            },
+            ("load", [path, x, y]) => Command::Load {
+                path: path.into(),
+                at: Some((number(x)?, number(y)?)),
+            },
+            ("step", []) => Command::Step(1),
+            ("step", [generations]) => Command::Step(generations.parse().map_err(|_| usage())?),
+            ("resize", [width, height]) => {
+                // no bigger than a pattern file can be, so that a slip can't use up the memory
+                let side = |arg| {
+                    number(arg).and_then(|n| match n {
+                        1..=MAX_SIZE => Ok(n),
+                        _ => Err(usage()),
+                    })
+                };
+                Command::Resize {
+                    width: side(width)?,
+                    height: side(height)?,
+                }
+            }
+            ("save", [path]) => Command::Save(path.into()),
+            ("script", [path]) => Command::Script(path.into()),
+            _ => return Err(usage()),
+        };
+        Ok(command)
+    }
+}
+
+/// `random [DENSITY] [seed=N] [symmetry=S]`, or `None` if an argument doesn't fit. A bad
+/// symmetry is reported as bad usage, as the names are in the README.
+fn random_soup(args: &[&str]) -> Option<Command> {
+    let (mut seed, mut density, mut symmetry) = (None, None, None);
+    for arg in args {
+        if let Some(value) = arg.strip_prefix("seed=") {
+            seed = Some(value.parse().ok()?);
+        } else if let Some(value) = arg.strip_prefix("symmetry=") {
+            symmetry = Some(value.parse::<Symmetry>().ok()?);
+        } else {
+            let value = arg.strip_prefix("density=").unwrap_or(arg);
+            density = Some(
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|d| (0.0..=1.0).contains(d))?,
+            );
+        }
+    }
+    Some(Command::Edit(EditAction::RandomSoup {
+        seed,
+        density,
+        symmetry,
+    }))
+}
+
+/// The line being typed, and the lines typed before.
+pub struct Console {
+    line: String,
+    history: Vec<String>,
+    /// The history line shown, while going back through the history.
+    recalled: Option<usize>,
+    /// What the last Tab could have completed, when there was more than one choice.
+    choices: Vec<String>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            line: String::new(),
+            history: Vec::new(),
+            recalled: None,
+            choices: Vec::new(),
+        }
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// The status line while typing: the line, and the choices from the last Tab.
+    pub fn prompt(&self) -> String {
+        let mut prompt = format!(":{}_", self.line);
+        if !self.choices.is_empty() {
+            prompt += &format!("   {}", self.choices.join(" "));
+        }
+        prompt
+    }
+
+    /// Starts a new, empty line.
+    pub fn open(&mut self) {
+        self.line.clear();
+        self.recalled = None;
+        self.choices.clear();
+    }
+
+    /// Finishes the line, remembering it in the history unless it's empty or the same
+    /// as the last one.
+    pub fn take_line(&mut self) -> String {
+        let line = self.line.trim().to_owned();
+        self.open();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            if self.history.len() == HISTORY_LIMIT {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        line
+    }
+
+    pub fn apply_action(&mut self, action: ConsoleAction) {
+        if action != ConsoleAction::Complete {
+            self.choices.clear();
+        }
+        match action {
+            ConsoleAction::Type(c) => {
+                self.line.push(c);
+                self.recalled = None;
+            }
+            ConsoleAction::Backspace => {
+                self.line.pop();
+                self.recalled = None;
+            }
+            ConsoleAction::Complete => self.complete(),
+            ConsoleAction::Previous => {
+                let i = match self.recalled {
+                    Some(i) => i.saturating_sub(1),
+                    None if self.history.is_empty() => return,
+                    None => self.history.len() - 1,
+                };
+                self.recalled = Some(i);
+                self.line = self.history[i].clone();
+            }
+            ConsoleAction::Next => {
+                self.recalled = self
+                    .recalled
+                    .map(|i| i + 1)
+                    .filter(|&i| i < self.history.len());
+                self.line = match self.recalled {
+                    Some(i) => self.history[i].clone(),
+                    None => String::new(),
+                };
+            }
+            ConsoleAction::Run | ConsoleAction::Cancel => {}
+        }
+    }
+
+    /// Completes the word being typed: a command name, or a file name after a command
+    /// that takes one. If more than one would do, it's completed as far as they agree, and the
+    /// choices are shown.
+    fn complete(&mut self) {
+        let start = self.line.rfind(' ').map_or(0, |i| i + 1);
+        let word = &self.line[start..];
+        let command = self.line.split_whitespace().next().unwrap_or("");
+        let (dir, names) = if start == 0 {
+            let names = COMMANDS
+                .iter()
+                .map(|&(name, _)| name.to_owned())
+                .filter(|name| name.starts_with(word))
+                .collect();
+            ("", names)
+        } else if FILE_COMMANDS.contains(&command) && self.line[..start].trim() == command {
+            let dir = word.rfind('/').map_or("", |i| &word[..=i]);
+            (dir, file_names(dir, &word[dir.len()..]))
+        } else {
+            return;
+        };
+        let completed = match names.as_slice() {
+            [] => return,
+            // a finished word is followed by a space, but not a directory
+            [name] if name.ends_with('/') => name.clone(),
+            [name] => format!("{} ", name),
+            [first, rest @ ..] => {
+                let mut common = first.len();
+                for name in rest {
+                    common = first
+                        .char_indices()
+                        .zip(name.chars())
+                        .take_while(|((_, a), b)| a == b)
+                        .map(|((i, a), _)| i + a.len_utf8())
+                        .last()
+                        .unwrap_or(0)
+                        .min(common);
+                }
+                self.choices = names.clone();
+                first[..common].to_owned()
+            }
+        };
+        let line = format!("{}{}{}", &self.line[..start], dir, completed);
+        self.line = line;
+    }
+}
+
+/// The names in `dir` that start with `prefix`, sorted, with a `/` after directories.
+/// Hidden files are left out unless the prefix asks for them.
+fn file_names(dir: &str, prefix: &str) -> Vec<String> {
+    let entries = match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            Some(if is_dir { name + "/" } else { name })
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn parse(line: &str) -> Command {
+        line.parse().unwrap()
+    }
+
+    fn usage_of(line: &str) -> Option<&'static str> {
+        match line.parse::<Command>() {
+            Err(Error::Usage(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn typed(console: &mut Console, text: &str) {
+        for c in text.chars() {
+            console.apply_action(ConsoleAction::Type(c));
+        }
+    }
+
+    #[test]
+    fn commands_are_read_with_their_arguments() {
+        assert!(matches!(
+            parse("pause"),
+            Command::App(AppAction::TogglePause)
+        ));
+        assert!(matches!(parse("  undo "), Command::Edit(EditAction::Undo)));
+        assert!(matches!(parse("step"), Command::Step(1)));
+        assert!(matches!(parse("step 1000"), Command::Step(1000)));
+        assert!(matches!(
+            parse("resize 200 100"),
+            Command::Resize {
+                width: 200,
+                height: 100
+            }
+        ));
+        assert!(matches!(
+            parse("load glider.rle 10 20"),
+            Command::Load {
+                at: Some((10, 20)),
+                ..
+            }
+        ));
+        match parse("rule B36/S23") {
+            Command::Rule(rule) => assert_eq!(rule.to_string(), "B36/S23"),
+            command => panic!("{:?}", command),
+        }
+        match parse("random 0.3 seed=42 symmetry=d4") {
+            Command::Edit(EditAction::RandomSoup {
+                seed,
+                density,
+                symmetry,
+            }) => {
+                assert_eq!((seed, density), (Some(42), Some(0.3)));
+                assert_eq!(symmetry, Some(Symmetry::D4));
+            }
+            command => panic!("{:?}", command),
+        }
+    }
+
+    #[test]
+    fn bad_arguments_show_the_usage() {
+        assert_eq!(usage_of("step ten"), Some("step"));
+        assert_eq!(usage_of("step -1"), Some("step"));
+        assert_eq!(usage_of("resize 0 10"), Some("resize"));
+        assert_eq!(usage_of("resize 100000 1"), Some("resize"));
+        assert_eq!(usage_of("resize 10 10001"), Some("resize"));
+        assert_eq!(usage_of("load glider.rle 10"), Some("load"));
+        assert_eq!(usage_of("random 1.5"), Some("random"));
+        assert_eq!(usage_of("pause now"), Some("pause"));
+        assert_eq!(
+            Error::Usage("resize").to_string(),
+            "Usage: :resize WIDTH HEIGHT"
+        );
+        assert!(matches!(
+            "fly".parse::<Command>(),
+            Err(Error::UnknownCommand(_))
+        ));
+        assert!(matches!("rule B9".parse::<Command>(), Err(Error::Rule(_))));
+    }
+
+    #[test]
+    fn command_names_complete_as_far_as_they_agree() {
+        let mut console = Console::new();
+        typed(&mut console, "st");
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), "step ");
+
+        console.open();
+        typed(&mut console, "s");
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), "s");
+        assert_eq!(console.prompt(), ":s_   save script slower snapshot step");
+
+        console.open();
+        typed(&mut console, "re");
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), "re");
+        typed(&mut console, "s");
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), "resize ");
+    }
+
+    #[test]
+    fn file_names_complete_after_commands_that_take_them() {
+        let dir = env::temp_dir().join(format!("gol-console-{}", std::process::id()));
+        fs::create_dir_all(dir.join("patterns")).unwrap();
+        fs::write(dir.join("glider.rle"), "").unwrap();
+        fs::write(dir.join("gosper.rle"), "").unwrap();
+        let dir_name = format!("{}/", dir.display());
+
+        let mut console = Console::new();
+        typed(&mut console, &format!("load {}gl", dir_name));
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), format!("load {}glider.rle ", dir_name));
+
+        console.open();
+        typed(&mut console, &format!("save {}g", dir_name));
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), format!("save {}g", dir_name));
+        assert!(console.prompt().ends_with("   glider.rle gosper.rle"));
+
+        console.open();
+        typed(&mut console, &format!("script {}pat", dir_name));
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), format!("script {}patterns/", dir_name));
+
+        // only commands that take a file
+        console.open();
+        typed(&mut console, &format!("rule {}gl", dir_name));
+        console.apply_action(ConsoleAction::Complete);
+        assert_eq!(console.line(), format!("rule {}gl", dir_name));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
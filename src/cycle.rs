@@ -0,0 +1,423 @@
+//! Noticing when the board repeats itself, possibly somewhere else.
+
+use grid::Grid;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use topology::Topology;
+
+/// How many recent generations are remembered, which is the longest period that can be
+/// detected.
+const WINDOW: usize = 1024;
+
+/// The board repeats every `period` generations, starting from generation `start`, moved
+/// by `(dx, dy)` each time.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct Cycle {
+    pub start: u64,
+    pub period: u64,
+    pub dx: isize,
+    pub dy: isize,
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Cycle {
+    /// The speed and direction of a moving cycle in the usual notation, e.g. `c/4
+    /// diagonal` for a glider or `(2,1)c/6 oblique` for a knightship.
+    pub fn velocity(&self) -> Option<String> {
+        let (x, y) = (self.dx.unsigned_abs() as u64, self.dy.unsigned_abs() as u64);
+        let (distance, direction) = match (x, y) {
+            (0, 0) => return None,
+            (d, 0) | (0, d) => (d, "orthogonal"),
+            (a, b) if a == b => (a, "diagonal"),
+            (a, b) => {
+                let (a, b) = (a.max(b), a.min(b));
+                return Some(format!("({},{})c/{} oblique", a, b, self.period));
+            }
+        };
+        let divisor = gcd(distance, self.period);
+        let (distance, period) = (distance / divisor, self.period / divisor);
+        let speed = match (distance, period) {
+            (1, 1) => "c".to_owned(),
+            (1, p) => format!("c/{}", p),
+            (d, 1) => format!("{}c", d),
+            (d, p) => format!("{}c/{}", d, p),
+        };
+        Some(format!("{} {}", speed, direction))
+    }
+
+    /// Whether the board keeps going round this cycle for good on `topology`. A spaceship
+    /// only does if every edge it's heading for wraps round, as otherwise it's bound to
+    /// reach one and change.
+    pub fn is_settled(&self, topology: Topology) -> bool {
+        let (wraps_x, wraps_y) = topology.translates();
+        (self.dx == 0 || wraps_x) && (self.dy == 0 || wraps_y)
+    }
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.velocity() {
+            Some(velocity) => write!(
+                f,
+                "{} spaceship, period {} moving ({}, {}), from gen {}",
+                velocity, self.period, self.dx, self.dy, self.start
+            ),
+            None if self.period == 1 => write!(f, "stable from gen {}", self.start),
+            None => write!(f, "period {} from gen {}", self.period, self.start),
+        }
+    }
+}
+
+/// The live cells of one generation, relative to their bounding box.
+#[derive(Copy, Clone, Debug)]
+struct Snapshot {
+    /// A hash of the cells within the bounding box.
+    hash: u64,
+    /// The top left corner of the bounding box.
+    origin: (usize, usize),
+}
+
+/// Where the live cells start along one axis, and how far they extend. On an axis that
+/// wraps round, the cells are taken to start after the longest gap, so that a pattern
+/// crossing the edge is still in one piece.
+fn extent(occupied: &[bool], wraps: bool) -> (usize, usize) {
+    let n = occupied.len();
+    let first = match occupied.iter().position(|&o| o) {
+        Some(first) => first,
+        None => return (0, 0),
+    };
+    if !wraps {
+        let last = occupied.iter().rposition(|&o| o).unwrap_or(first);
+        return (first, last - first + 1);
+    }
+    // walk round once from the first occupied cell, looking for the longest gap
+    let (mut best_start, mut best_len, mut gap) = (first, 0, 0);
+    for i in 1..=n {
+        let index = (first + i) % n;
+        if occupied[index] {
+            if gap > best_len {
+                best_start = index;
+                best_len = gap;
+            }
+            gap = 0;
+        } else {
+            gap += 1;
+        }
+    }
+    (best_start, n - best_len)
+}
+
+impl Snapshot {
+    fn new(grid: &Grid<u8>, topology: Topology) -> Snapshot {
+        let (width, height) = (grid.width(), grid.height());
+        let (mut columns, mut rows) = (vec![false; width], vec![false; height]);
+        for (y, row) in rows.iter_mut().enumerate() {
+            for (x, column) in columns.iter_mut().enumerate() {
+                if grid.get(x, y) != 0 {
+                    *column = true;
+                    *row = true;
+                }
+            }
+        }
+        let (wraps_x, wraps_y) = topology.translates();
+        let (x0, box_width) = extent(&columns, wraps_x);
+        let (y0, box_height) = extent(&rows, wraps_y);
+        let mut hasher = DefaultHasher::new();
+        (box_width, box_height).hash(&mut hasher);
+        for j in 0..box_height {
+            for i in 0..box_width {
+                grid.get((x0 + i) % width, (y0 + j) % height)
+                    .hash(&mut hasher);
+            }
+        }
+        Snapshot {
+            hash: hasher.finish(),
+            origin: (x0, y0),
+        }
+    }
+}
+
+/// The difference between two positions along an axis, taking the short way round if
+/// the axis wraps.
+fn displacement(from: usize, to: usize, size: usize, wraps: bool) -> isize {
+    let d = to as isize - from as isize;
+    let size = size as isize;
+    if !wraps {
+        d
+    } else if d > size / 2 {
+        d - size
+    } else if d <= -size / 2 {
+        d + size
+    } else {
+        d
+    }
+}
+
+/// Keeps the shapes of the most recent generations, to spot the first one that comes
+/// round again, whether in the same place or not.
+#[derive(Debug, Default)]
+pub struct CycleDetector {
+    /// The first generation in the window with each shape.
+    first: HashMap<u64, u64>,
+    /// The shape of each generation in the window, most recent last.
+    recent: VecDeque<(u64, Snapshot)>,
+    cycle: Option<Cycle>,
+}
+
+impl CycleDetector {
+    /// A detector that hasn't seen any generations.
+    pub fn new() -> CycleDetector {
+        CycleDetector::default()
+    }
+
+    /// The cycle the board has entered, if one has been seen.
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    /// Forgets everything, e.g. after the board has been edited.
+    pub fn reset(&mut self) {
+        *self = CycleDetector::new();
+    }
+
+    /// Whether nothing has been recorded since the last reset.
+    pub fn is_empty(&self) -> bool {
+        self.recent.is_empty()
+    }
+
+    fn snapshot_at(&self, generation: u64) -> Option<Snapshot> {
+        let &(oldest, _) = self.recent.front()?;
+        let index = generation.checked_sub(oldest)? as usize;
+        self.recent.get(index).map(|&(_, snapshot)| snapshot)
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some((oldest, old)) = self.recent.pop_front() {
+            if self.first.get(&old.hash) == Some(&oldest) {
+                match self.recent.iter().find(|(_, s)| s.hash == old.hash) {
+                    Some(&(next, _)) => self.first.insert(old.hash, next),
+                    None => self.first.remove(&old.hash),
+                };
+            }
+        }
+    }
+
+    /// Records the board at `generation`, which must follow the last one recorded.
+    pub fn record(&mut self, generation: u64, grid: &Grid<u8>, topology: Topology) {
+        let snapshot = Snapshot::new(grid, topology);
+        let (wraps_x, wraps_y) = topology.translates();
+        let moved = |from: Snapshot| {
+            (
+                displacement(from.origin.0, snapshot.origin.0, grid.width(), wraps_x),
+                displacement(from.origin.1, snapshot.origin.1, grid.height(), wraps_y),
+            )
+        };
+        // a spaceship on a bounded grid stops being one when it reaches the edge
+        if let Some(cycle) = self.cycle {
+            let then = self.snapshot_at(generation - cycle.period);
+            let still_cycling = then.is_none_or(|then| {
+                then.hash == snapshot.hash && moved(then) == (cycle.dx, cycle.dy)
+            });
+            if !still_cycling {
+                self.cycle = None;
+            }
+        }
+        if self.cycle.is_none() {
+            let start = self.first.get(&snapshot.hash).cloned();
+            if let Some((start, then)) = start.and_then(|s| Some((s, self.snapshot_at(s)?))) {
+                let (dx, dy) = moved(then);
+                self.cycle = Some(Cycle {
+                    start,
+                    period: generation - start,
+                    dx,
+                    dy,
+                });
+            }
+        }
+        if self.recent.len() == WINDOW {
+            self.forget_oldest();
+        }
+        self.first.entry(snapshot.hash).or_insert(generation);
+        self.recent.push_back((generation, snapshot));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::Gol;
+    use topology::Twist;
+
+    /// Runs `gol` for `generations`, recording each one including the first, and gives
+    /// the cycle after each.
+    fn run(gol: &mut Gol, generations: u64) -> (CycleDetector, Vec<Option<Cycle>>) {
+        let mut detector = CycleDetector::new();
+        let mut cycles = Vec::new();
+        detector.record(gol.generation(), gol.grid(), gol.topology());
+        for _ in 0..generations {
+            gol.next_turn();
+            detector.record(gol.generation(), gol.grid(), gol.topology());
+            cycles.push(detector.cycle());
+        }
+        (detector, cycles)
+    }
+
+    fn glider(width: usize, height: usize, topology: Topology, x: usize, y: usize) -> Gol {
+        let mut gol = Gol::new(width, height, topology);
+        gol.init(&[
+            (x + 1, y),
+            (x + 2, y + 1),
+            (x, y + 2),
+            (x + 1, y + 2),
+            (x + 2, y + 2),
+        ]);
+        gol
+    }
+
+    /// A row of cells with the bits of `n` between two live cells, so that each `n` has
+    /// a different shape.
+    fn numbered(n: u64) -> Grid<u8> {
+        let mut grid = Grid::new(14, 1);
+        grid.set(0, 0, 1);
+        grid.set(13, 0, 1);
+        for bit in 0..12 {
+            grid.set(bit + 1, 0, (n >> bit & 1) as u8);
+        }
+        grid
+    }
+
+    fn moving(dx: isize, dy: isize) -> Cycle {
+        Cycle {
+            start: 0,
+            period: 4,
+            dx,
+            dy,
+        }
+    }
+
+    #[test]
+    fn oscillators_are_settled_on_any_board() {
+        for &topology in &[Topology::Plane, Topology::Torus, Topology::Sphere] {
+            assert!(moving(0, 0).is_settled(topology));
+        }
+    }
+
+    #[test]
+    fn spaceships_are_settled_only_where_their_edges_wrap() {
+        let glider = moving(1, 1);
+        assert!(glider.is_settled(Topology::Torus));
+        assert!(!glider.is_settled(Topology::Plane));
+        assert!(!glider.is_settled(Topology::HorizontalCylinder));
+        assert!(!glider.is_settled(Topology::KleinBottle(Twist::TopBottom)));
+
+        let lwss = moving(2, 0);
+        assert!(lwss.is_settled(Topology::HorizontalCylinder));
+        assert!(lwss.is_settled(Topology::KleinBottle(Twist::TopBottom)));
+        assert!(!lwss.is_settled(Topology::VerticalCylinder));
+        assert!(!lwss.is_settled(Topology::CrossSurface));
+    }
+
+    #[test]
+    fn a_blinker_is_a_still_period_2_cycle() {
+        let mut gol = Gol::new(5, 5, Topology::Plane);
+        gol.init(&[(1, 2), (2, 2), (3, 2)]);
+        let (detector, cycles) = run(&mut gol, 4);
+        assert_eq!(cycles[0], None);
+        let blinker = Cycle {
+            start: 0,
+            period: 2,
+            dx: 0,
+            dy: 0,
+        };
+        assert_eq!(cycles[1..], [Some(blinker); 3]);
+        assert_eq!(detector.cycle().unwrap().to_string(), "period 2 from gen 0");
+        assert!(blinker.is_settled(Topology::Plane));
+    }
+
+    #[test]
+    fn a_glider_keeps_its_cycle_across_the_edges_of_a_torus() {
+        let mut gol = glider(8, 8, Topology::Torus, 5, 5);
+        let (_, cycles) = run(&mut gol, 64);
+        let expected = Cycle {
+            start: 0,
+            period: 4,
+            dx: 1,
+            dy: 1,
+        };
+        assert_eq!(cycles[..3], [None; 3]);
+        assert!(cycles[3..].iter().all(|&cycle| cycle == Some(expected)));
+    }
+
+    #[test]
+    fn a_glider_loses_its_cycle_at_the_edge_of_a_plane() {
+        let mut gol = glider(12, 12, Topology::Plane, 0, 0);
+        let (_, cycles) = run(&mut gol, 60);
+        let moving = |cycle: &Option<Cycle>| cycle.is_some_and(|cycle| cycle.dx == 1);
+        let last_moving = cycles.iter().rposition(moving).unwrap();
+        assert!(cycles[3..=last_moving].iter().all(moving));
+        // dropped once the glider reaches the edge, not just found again
+        assert_eq!(cycles[last_moving + 1], None);
+        // it ends up as a block in the corner
+        let block = cycles.last().unwrap().unwrap();
+        assert_eq!((block.period, block.dx, block.dy), (1, 0, 0));
+    }
+
+    #[test]
+    fn extents_on_wrapping_axes_start_after_the_longest_gap() {
+        let occupied = [true, false, false, false, true, true, false, true];
+        assert_eq!(extent(&occupied, false), (0, 8));
+        assert_eq!(extent(&occupied, true), (4, 5));
+        assert_eq!(extent(&[false, true, true, false], true), (1, 2));
+        assert_eq!(extent(&[true; 4], true), (0, 4));
+        assert_eq!(extent(&[false; 4], true), (0, 0));
+    }
+
+    #[test]
+    fn displacements_take_the_short_way_round() {
+        assert_eq!(displacement(7, 0, 8, true), 1);
+        assert_eq!(displacement(0, 7, 8, true), -1);
+        assert_eq!(displacement(7, 0, 8, false), -7);
+        assert_eq!(displacement(2, 5, 8, true), 3);
+        assert_eq!(displacement(0, 4, 8, true), 4);
+        assert_eq!(displacement(4, 0, 8, true), 4);
+    }
+
+    #[test]
+    fn generations_older_than_the_window_are_forgotten() {
+        let mut detector = CycleDetector::new();
+        for generation in 0..=WINDOW as u64 {
+            detector.record(generation, &numbered(generation), Topology::Plane);
+        }
+        assert_eq!(detector.cycle(), None);
+        // the first generation has gone, so coming back to it isn't a cycle
+        let next = WINDOW as u64 + 1;
+        detector.record(next, &numbered(0), Topology::Plane);
+        assert_eq!(detector.cycle(), None);
+        detector.record(next + 1, &numbered(2), Topology::Plane);
+        assert_eq!(
+            detector.cycle().map(|cycle| (cycle.start, cycle.period)),
+            Some((2, 1024))
+        );
+    }
+
+    #[test]
+    fn a_reset_forgets_the_cycle() {
+        let mut gol = Gol::new(5, 5, Topology::Plane);
+        gol.init(&[(1, 1), (2, 1), (1, 2), (2, 2)]);
+        let (mut detector, _) = run(&mut gol, 1);
+        assert!(detector.cycle().is_some());
+        detector.reset();
+        assert!(detector.is_empty());
+        assert_eq!(detector.cycle(), None);
+    }
+}
@@ -1,51 +1,584 @@
+//! Changing the board by hand: the cursor, selection, clipboard, stamps, drawing tools
+//! and undo.
+
 use grid::Grid;
-use presets::get_preset;
+use pattern::Pattern;
+use presets::Orientation;
+use shapes::Shape;
+use soup::{Soup, Symmetry};
+use std::collections::HashSet;
+use std::mem;
+use std::path::PathBuf;
+use topology::Topology;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+/// A change to the board or the editor. Cells off the board are ignored.
+#[derive(Clone, Debug, PartialEq)]
+#[allow(missing_docs)] // fields are described with their variants
 pub enum EditAction {
-    MoveCursorBy { x: isize, y: isize },
+    /// Moves the cursor, wrapping round the edges of the board.
+    MoveCursorBy {
+        /// Columns to the right, or to the left if negative.
+        x: isize,
+        /// Rows down, or up if negative.
+        y: isize,
+    },
+    /// Moves the cursor to the cell at `(x, y)`.
     MoveCursorTo { x: usize, y: usize },
+    /// Toggles the cell at the cursor, or places the stamp if there is one.
     ToggleCell,
+    /// Toggles the cell at `(x, y)`.
     ToggleCellAt { x: usize, y: usize },
+    /// Kills the cell at `(x, y)`.
     KillCellAt { x: usize, y: usize },
+    /// Brings the cell at `(x, y)` to life.
     AddCellAt { x: usize, y: usize },
+    /// Kills every cell.
     Clear,
-    AddPreset { index: u8 },
+    /// Picks up a pattern, to be placed at the cursor with `CommitStamp`.
+    AddPattern(Box<Pattern>),
+    /// Turns or mirrors the stamp.
+    TransformStamp(Transform),
+    /// Places the stamp with its top left corner at the cursor.
+    CommitStamp,
+    /// Puts the stamp down without placing it.
+    CancelStamp,
+    /// Starts a selection anchored at the cursor, or ends the current one.
+    ToggleSelection,
+    /// Copies the selection to the clipboard.
+    Copy,
+    /// Copies the selection to the clipboard and clears it.
+    Cut,
+    /// Picks up the clipboard, to be placed at the cursor with `CommitStamp`.
+    Paste,
+    /// Brings every cell in the selection to life.
+    FillSelection,
+    /// Kills every cell in the selection.
+    ClearSelection,
+    /// Kills every cell outside the selection.
+    ClearOutsideSelection,
+    /// Lifts the selected cells off the grid, to be placed elsewhere with `CommitStamp`.
+    MoveSelection,
+    /// Saves the clipboard as a pattern file.
+    ExportClipboard(PathBuf),
+    /// Draws a shape from the selection anchor to the cursor.
+    Draw(Shape),
+    /// Draws a shape from one cell to another.
+    DrawBetween {
+        shape: Shape,
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    /// Inverts the connected region of cells in the same state as the one at the cursor.
+    FloodFill,
+    /// Inverts the connected region of cells in the same state as the one at
+    /// `(x, y)`.
+    FloodFillAt { x: usize, y: usize },
+    /// Fills the selection, or the whole grid, with a random soup. Settings that aren't
+    /// given are taken from the editor, and a new seed is picked if there isn't one.
+    RandomSoup {
+        seed: Option<u64>,
+        density: Option<f64>,
+        symmetry: Option<Symmetry>,
+    },
+    /// Changes the symmetry of the next soup to the next one along.
+    CycleSoupSymmetry,
+    /// Changes the density of the next soup by the given amount.
+    ChangeSoupDensity(f64),
+    /// Undoes the last edit.
+    Undo,
+    /// Redoes the last edit undone.
+    Redo,
+}
+
+impl EditAction {
+    /// The action with a seed from `seed` if it's a random soup without one, so that doing
+    /// it again makes the same soup.
+    pub fn with_seed<F: FnOnce() -> u64>(self, seed: F) -> EditAction {
+        match self {
+            EditAction::RandomSoup {
+                seed: None,
+                density,
+                symmetry,
+            } => EditAction::RandomSoup {
+                seed: Some(seed()),
+                density,
+                symmetry,
+            },
+            action => action,
+        }
+    }
+
+    /// Whether the action can change any cells, so that the board has to be compared
+    /// before and after it for undo.
+    fn can_change_cells(&self) -> bool {
+        use EditAction::*;
+        !matches!(
+            self,
+            MoveCursorBy { .. }
+                | MoveCursorTo { .. }
+                | AddPattern(_)
+                | TransformStamp(_)
+                | CancelStamp
+                | ToggleSelection
+                | Copy
+                | Paste
+                | ExportClipboard(_)
+                | CycleSoupSymmetry
+                | ChangeSoupDensity(_)
+        )
+    }
+}
+
+/// The number of edits that can be undone.
+const UNDO_LIMIT: usize = 100;
+
+/// A `width` by `height` rectangle of the grid with its top left corner at `(x, y)`, between
+/// the selection anchor and the cursor inclusive.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct Selection {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Selection {
+    fn between((x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> Selection {
+        Selection {
+            x: x1.min(x2),
+            y: y1.min(y2),
+            width: x1.max(x2) - x1.min(x2) + 1,
+            height: y1.max(y2) - y1.min(y2) + 1,
+        }
+    }
+
+    /// Whether a cell is in the selection.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
+/// A way of turning or mirroring a stamp.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transform {
+    /// A quarter turn clockwise.
+    RotateClockwise,
+    /// A quarter turn anticlockwise.
+    RotateAnticlockwise,
+    /// Mirrored left to right.
+    FlipHorizontal,
+    /// Mirrored top to bottom.
+    FlipVertical,
+}
+
+/// A pattern waiting to be stamped onto the grid at the cursor.
+pub struct Stamp {
+    original: Pattern,
+    orientation: Orientation,
+    pattern: Pattern,
+    /// Whether the stamp was lifted off the board by the last edit that can be undone, so
+    /// that putting it down again is undone with it.
+    lifted: bool,
 }
 
+impl Stamp {
+    fn new(pattern: Pattern) -> Stamp {
+        Stamp {
+            original: pattern.clone(),
+            orientation: Orientation::default(),
+            pattern,
+            lifted: false,
+        }
+    }
+
+    fn transform(&mut self, transform: Transform) {
+        self.orientation = match transform {
+            Transform::RotateClockwise => self.orientation.rotate_clockwise(),
+            Transform::RotateAnticlockwise => self.orientation.rotate_anticlockwise(),
+            Transform::FlipHorizontal => self.orientation.flip_horizontal(),
+            Transform::FlipVertical => self.orientation.flip_vertical(),
+        };
+        self.pattern = self.original.transformed(self.orientation);
+    }
+
+    /// The pattern in its current orientation.
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+}
+
+/// The cells an edit changed, with the values they had on the other side of it, so that
+/// undo keeps only what changed rather than the whole board.
+#[derive(Clone, Debug, PartialEq)]
+struct Change {
+    cells: Vec<(usize, usize, u8)>,
+}
+
+impl Change {
+    /// The cells that differ between two grids of the same size, with their values in
+    /// `from`.
+    fn between(from: &Grid<u8>, to: &Grid<u8>) -> Change {
+        let mut cells = Vec::new();
+        for y in 0..to.height() {
+            for x in 0..to.width() {
+                let value = from.get(x, y);
+                if value != to.get(x, y) {
+                    cells.push((x, y, value));
+                }
+            }
+        }
+        Change { cells }
+    }
+
+    /// The changes that take `grid` back through each of `grids` in turn, last first,
+    /// in the same order as the grids.
+    fn leading_to(grids: &[Grid<u8>], grid: &Grid<u8>) -> Vec<Change> {
+        let mut changes = Vec::with_capacity(grids.len());
+        let mut next = grid;
+        for previous in grids.iter().rev() {
+            changes.push(Change::between(previous, next));
+            next = previous;
+        }
+        changes.reverse();
+        changes
+    }
+
+    /// Makes the change to `grid`, giving back the change that reverses it.
+    fn apply(&self, grid: &mut Grid<u8>) -> Change {
+        let cells = self
+            .cells
+            .iter()
+            .map(|&(x, y, value)| {
+                let old = grid.get(x, y);
+                grid.set(x, y, value);
+                (x, y, old)
+            })
+            .collect();
+        Change { cells }
+    }
+
+    /// Adds a change made after this one, so that both are undone together.
+    fn join(&mut self, later: Change) {
+        let seen: HashSet<_> = self.cells.iter().map(|&(x, y, _)| (x, y)).collect();
+        self.cells.extend(
+            later
+                .cells
+                .into_iter()
+                .filter(|&(x, y, _)| !seen.contains(&(x, y))),
+        );
+    }
+
+    /// The grids each of `changes` leads to from `grid`, last change first, in the same
+    /// order as the changes.
+    fn grids(changes: &[Change], grid: &Grid<u8>) -> Vec<Grid<u8>> {
+        let mut grid = grid.clone();
+        let mut grids: Vec<_> = changes
+            .iter()
+            .rev()
+            .map(|change| {
+                change.apply(&mut grid);
+                grid.clone()
+            })
+            .collect();
+        grids.reverse();
+        grids
+    }
+}
+
+/// An editor's undo and redo history, set aside while making changes that should be
+/// undone all at once.
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+/// The state of editing that isn't on the board itself. The board is passed in to each
+/// action, so that it can be kept elsewhere.
 pub struct Editor {
     cursor: (usize, usize),
+    stamp: Option<Stamp>,
+    anchor: Option<(usize, usize)>,
+    clipboard: Option<Pattern>,
+    message: Option<String>,
+    soup_density: f64,
+    soup_symmetry: Symmetry,
+    last_soup: Option<Soup>,
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    /// How the board's edges are joined, which flood fills follow.
+    topology: Topology,
+    /// The board before the action being carried out, kept to save allocating it each time.
+    before: Grid<u8>,
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor::new()
+    }
 }
 
 impl Editor {
+    /// An editor with the cursor in the top left corner and nothing to undo.
     pub fn new() -> Editor {
-        Editor { cursor: (0, 0) }
+        Editor {
+            cursor: (0, 0),
+            stamp: None,
+            anchor: None,
+            clipboard: None,
+            message: None,
+            soup_density: 0.5,
+            soup_symmetry: Symmetry::C1,
+            last_soup: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            topology: Topology::Plane,
+            before: Grid::new(0, 0),
+        }
+    }
+
+    /// The pattern waiting to be placed, if there is one.
+    pub fn stamp(&self) -> Option<&Stamp> {
+        self.stamp.as_ref()
+    }
+
+    /// The rectangle between the selection anchor and the cursor, while selecting.
+    pub fn selection(&self) -> Option<Selection> {
+        self.anchor
+            .map(|anchor| Selection::between(anchor, self.cursor))
+    }
+
+    /// The last pattern copied or cut.
+    pub fn clipboard(&self) -> Option<&Pattern> {
+        self.clipboard.as_ref()
+    }
+
+    /// The most recent random soup, so that it can be recreated.
+    pub fn last_soup(&self) -> Option<Soup> {
+        self.last_soup
+    }
+
+    /// A short description of the result of the last action, if there's anything to say.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
     }
 
+    /// Moves the cursor, which should be on the board.
     pub fn set_cursor(&mut self, x: usize, y: usize) {
         self.cursor = (x, y);
     }
 
+    /// The cell the cursor is on.
     pub fn get_cursor(&self) -> (usize, usize) {
         self.cursor
     }
 
+    /// Joins the board's edges as `topology` joins them, for edits that cross them.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    /// Starts afresh for a board that's been resized to `width` by `height`, apart from the
+    /// cursor, which is kept on the board, and the topology. The history and selection are for the old size.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let (x, y) = self.cursor;
+        *self = Editor {
+            topology: self.topology,
+            ..Editor::new()
+        };
+        self.cursor = (
+            x.min(width.saturating_sub(1)),
+            y.min(height.saturating_sub(1)),
+        );
+    }
+
+    /// The grids before each edit that can be undone, oldest first, and the grids that
+    /// were undone and can be redone, most recently undone last, from the board as it is.
+    pub fn history(&self, grid: &Grid<u8>) -> (Vec<Grid<u8>>, Vec<Grid<u8>>) {
+        (
+            Change::grids(&self.undo, grid),
+            Change::grids(&self.redo, grid),
+        )
+    }
+
+    /// Replaces the undo and redo history, as returned by `history` for the board `grid`.
+    /// The grids must all be the same size as the board.
+    pub fn set_history(&mut self, undo: &[Grid<u8>], redo: &[Grid<u8>], grid: &Grid<u8>) {
+        self.undo = Change::leading_to(undo, grid);
+        self.redo = Change::leading_to(redo, grid);
+    }
+
+    /// Takes the undo and redo history, leaving none.
+    pub fn take_history(&mut self) -> History {
+        History {
+            undo: mem::take(&mut self.undo),
+            redo: mem::take(&mut self.redo),
+        }
+    }
+
+    /// Puts back a history from `take_history`, for the board as it was then.
+    pub fn restore_history(&mut self, history: History) {
+        self.undo = history.undo;
+        self.redo = history.redo;
+    }
+
+    /// Makes the last change in `from`, saving its reverse in `to`.
+    fn restore(from: &mut Vec<Change>, to: &mut Vec<Change>, grid: &mut Grid<u8>) -> bool {
+        match from.pop() {
+            Some(change) => {
+                to.push(change.apply(grid));
+                true
+            }
+            None => false,
+        }
+    }
+
     fn edit<'a>(&'a mut self, grid: &'a mut Grid<u8>) -> EditSteps<'a> {
         EditSteps { editor: self, grid }
     }
 
+    /// Carries out an action on `grid`, remembering the grid before it for undo if it
+    /// changed.
     pub fn apply_action(&mut self, action: EditAction, grid: &mut Grid<u8>) {
+        use EditAction::*;
+        self.message = None;
+        if matches!(action, Undo | Redo) {
+            if let Some(stamp) = self.stamp.as_mut() {
+                stamp.lifted = false;
+            }
+        }
+        match action {
+            Undo => {
+                if !Editor::restore(&mut self.undo, &mut self.redo, grid) {
+                    self.message = Some("Nothing to undo".to_owned());
+                }
+                return;
+            }
+            Redo => {
+                if !Editor::restore(&mut self.redo, &mut self.undo, grid) {
+                    self.message = Some("Nothing to redo".to_owned());
+                }
+                return;
+            }
+            _ => {}
+        }
+        if !action.can_change_cells() {
+            self.apply_edit(action, grid);
+            return;
+        }
+        let placing_lifted = matches!(action, ToggleCell | CommitStamp)
+            && self.stamp.as_ref().is_some_and(|stamp| stamp.lifted);
+        let lifting = action == MoveSelection;
+        let mut before = mem::replace(&mut self.before, Grid::new(0, 0));
+        before.clone_from(grid);
+        self.apply_edit(action, grid);
+        if placing_lifted {
+            // a moved selection is undone in one step, lifting and placing together
+            let placed = Change::between(&before, grid);
+            if let Some(lift) = self.undo.last_mut() {
+                lift.join(placed);
+            }
+        } else if lifting {
+            self.record(&before, grid);
+        } else {
+            self.remember(&before, grid);
+        }
+        self.before = before;
+    }
+
+    /// Remembers the grid as it was `before` for undo if `grid` has changed since, as
+    /// `apply_action` does, for changes made some other way. A change of size can't be
+    /// undone, and forgets the history.
+    pub fn remember(&mut self, before: &Grid<u8>, grid: &Grid<u8>) {
+        if self.record(before, grid) {
+            if let Some(stamp) = self.stamp.as_mut() {
+                stamp.lifted = false;
+            }
+        }
+    }
+
+    /// Remembers the grid `before` for undo, saying whether there was a change to remember.
+    fn record(&mut self, before: &Grid<u8>, grid: &Grid<u8>) -> bool {
+        if (before.width(), before.height()) != (grid.width(), grid.height()) {
+            self.undo.clear();
+            self.redo.clear();
+            return false;
+        }
+        let change = Change::between(before, grid);
+        if change.cells.is_empty() {
+            return false;
+        }
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(change);
+        self.redo.clear();
+        true
+    }
+
+    fn apply_edit(&mut self, action: EditAction, grid: &mut Grid<u8>) {
         use EditAction::*;
         let mut edit_steps = self.edit(grid);
         match action {
             Clear => edit_steps.clear_all(),
+            ToggleCell | CommitStamp if edit_steps.editor.stamp.is_some() => {
+                edit_steps.commit_stamp()
+            }
             ToggleCell => edit_steps.toggle_current(),
             ToggleCellAt { x, y } => edit_steps.toggle_at(x, y),
             KillCellAt { x, y } => edit_steps.set_cell_at(false, x, y),
             AddCellAt { x, y } => edit_steps.set_cell_at(true, x, y),
             MoveCursorBy { x, y } => edit_steps.move_cursor_by(x, y),
             MoveCursorTo { x, y } => edit_steps.move_cursor_to(x, y),
-            AddPreset { index } => edit_steps.add_preset(get_preset(index)),
+            AddPattern(pattern) => edit_steps.editor.stamp = Some(Stamp::new(*pattern)),
+            TransformStamp(transform) => edit_steps.transform_stamp(transform),
+            CommitStamp => {}
+            CancelStamp => edit_steps.editor.stamp = None,
+            ToggleSelection => edit_steps.toggle_selection(),
+            Copy => edit_steps.copy(),
+            Cut => {
+                edit_steps.copy();
+                edit_steps.fill_selection(0);
+            }
+            Paste => edit_steps.paste(),
+            FillSelection => edit_steps.fill_selection(1),
+            ClearSelection => edit_steps.fill_selection(0),
+            ClearOutsideSelection => edit_steps.clear_outside_selection(),
+            MoveSelection => edit_steps.move_selection(),
+            ExportClipboard(path) => edit_steps.export_clipboard(path),
+            Draw(shape) => edit_steps.draw_from_anchor(shape),
+            DrawBetween { shape, from, to } => edit_steps.draw(shape, from, to),
+            FloodFill => {
+                let (x, y) = edit_steps.editor.cursor;
+                edit_steps.flood_fill(x, y);
+            }
+            FloodFillAt { x, y } => edit_steps.flood_fill(x, y),
+            RandomSoup {
+                seed,
+                density,
+                symmetry,
+            } => {
+                let editor = &edit_steps.editor;
+                let soup = Soup::new(
+                    seed.unwrap_or_else(Soup::random_seed),
+                    density.unwrap_or(editor.soup_density),
+                    symmetry.unwrap_or(editor.soup_symmetry),
+                );
+                edit_steps.random_soup(soup);
+            }
+            CycleSoupSymmetry => {
+                let editor = &mut edit_steps.editor;
+                editor.soup_symmetry = editor.soup_symmetry.next();
+                editor.message = Some(format!("Soup symmetry: {}", editor.soup_symmetry));
+            }
+            ChangeSoupDensity(by) => {
+                let editor = &mut edit_steps.editor;
+                // rounded to avoid drifting away from whole percentages
+                let density = ((editor.soup_density + by) * 100.0).round() / 100.0;
+                editor.soup_density = density.clamp(0.0, 1.0);
+                editor.message = Some(format!("Soup density: {:.0}%", editor.soup_density * 100.0));
+            }
+            Undo | Redo => {}
         }
     }
 }
@@ -56,11 +589,20 @@ struct EditSteps<'a> {
 }
 
 impl<'a> EditSteps<'a> {
+    fn on_grid(&self, x: usize, y: usize) -> bool {
+        x < self.grid.width() && y < self.grid.height()
+    }
+
     pub fn set_cell_at(&mut self, value: bool, x: usize, y: usize) {
-        self.grid.set(x, y, value as u8);
+        if self.on_grid(x, y) {
+            self.grid.set(x, y, value as u8);
+        }
     }
 
     pub fn toggle_at(&mut self, x: usize, y: usize) {
+        if !self.on_grid(x, y) {
+            return;
+        }
         let val = if self.grid.get(x, y) == 0 { 1 } else { 0 };
         self.grid.set(x, y, val);
     }
@@ -70,14 +612,169 @@ impl<'a> EditSteps<'a> {
         self.toggle_at(x, y);
     }
 
-    pub fn add_preset(&mut self, cells: impl Iterator<Item = (usize, usize)>) {
+    pub fn add_pattern(&mut self, pattern: &Pattern) {
         let (x, y) = self.editor.get_cursor();
         let (w, h) = (self.grid.width(), self.grid.height());
-        for (i, j) in cells {
+        for &(i, j) in &pattern.cells {
             self.grid.set((x + i + w) % w, (y + j + h) % h, 1);
         }
     }
 
+    pub fn transform_stamp(&mut self, transform: Transform) {
+        if let Some(stamp) = self.editor.stamp.as_mut() {
+            stamp.transform(transform);
+        }
+    }
+
+    pub fn commit_stamp(&mut self) {
+        if let Some(stamp) = self.editor.stamp.take() {
+            self.add_pattern(stamp.pattern());
+        }
+    }
+
+    pub fn toggle_selection(&mut self) {
+        self.editor.anchor = match self.editor.anchor {
+            Some(_) => None,
+            None => Some(self.editor.cursor),
+        };
+    }
+
+    fn selected_pattern(&self, selection: Selection) -> Pattern {
+        let mut cells = Vec::new();
+        for y in 0..selection.height {
+            for x in 0..selection.width {
+                if self.grid.get(selection.x + x, selection.y + y) != 0 {
+                    cells.push((x, y));
+                }
+            }
+        }
+        let mut pattern = Pattern::new(String::new(), cells);
+        pattern.width = selection.width;
+        pattern.height = selection.height;
+        pattern
+    }
+
+    pub fn copy(&mut self) {
+        if let Some(selection) = self.editor.selection() {
+            let pattern = self.selected_pattern(selection);
+            self.editor.message = Some(format!(
+                "Copied {} cells ({}x{})",
+                pattern.cells.len(),
+                pattern.width,
+                pattern.height
+            ));
+            self.editor.clipboard = Some(pattern);
+        }
+    }
+
+    pub fn paste(&mut self) {
+        if let Some(pattern) = self.editor.clipboard.clone() {
+            self.editor.anchor = None;
+            self.editor.stamp = Some(Stamp::new(pattern));
+        }
+    }
+
+    pub fn fill_selection(&mut self, value: u8) {
+        if let Some(selection) = self.editor.selection() {
+            for y in selection.y..selection.y + selection.height {
+                for x in selection.x..selection.x + selection.width {
+                    self.grid.set(x, y, value);
+                }
+            }
+        }
+    }
+
+    pub fn clear_outside_selection(&mut self) {
+        if let Some(selection) = self.editor.selection() {
+            for y in 0..self.grid.height() {
+                for x in 0..self.grid.width() {
+                    if !selection.contains(x, y) {
+                        self.grid.set(x, y, 0);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn move_selection(&mut self) {
+        if let Some(selection) = self.editor.selection() {
+            let pattern = self.selected_pattern(selection);
+            self.fill_selection(0);
+            self.editor.anchor = None;
+            self.editor.set_cursor(selection.x, selection.y);
+            let mut stamp = Stamp::new(pattern);
+            // nothing is lifted from an empty selection, so there's no lift to undo with it
+            stamp.lifted = !stamp.pattern.cells.is_empty();
+            self.editor.stamp = Some(stamp);
+        }
+    }
+
+    pub fn export_clipboard(&mut self, path: PathBuf) {
+        self.editor.message = Some(match &self.editor.clipboard {
+            None => "The clipboard is empty".to_owned(),
+            Some(pattern) => match pattern.save(&path) {
+                Ok(()) => format!("Saved clipboard to {}", path.display()),
+                Err(err) => format!("Couldn't save clipboard: {}", err),
+            },
+        });
+    }
+
+    pub fn draw(&mut self, shape: Shape, from: (usize, usize), to: (usize, usize)) {
+        let (w, h) = (self.grid.width(), self.grid.height());
+        for (x, y) in shape.cells(from, to) {
+            if x < w && y < h {
+                self.grid.set(x, y, 1);
+            }
+        }
+    }
+
+    /// Draws between the anchor and the cursor. Lines leave the anchor at the cursor, so
+    /// that the next line continues from there.
+    pub fn draw_from_anchor(&mut self, shape: Shape) {
+        if let Some(anchor) = self.editor.anchor {
+            let cursor = self.editor.cursor;
+            self.draw(shape, anchor, cursor);
+            self.editor.anchor = match shape {
+                Shape::Line => Some(cursor),
+                _ => None,
+            };
+        }
+    }
+
+    /// Inverts the region connected to `(x, y)`, across the edges the topology joins.
+    pub fn flood_fill(&mut self, x: usize, y: usize) {
+        if !self.on_grid(x, y) {
+            return;
+        }
+        let (w, h) = (self.grid.width(), self.grid.height());
+        let topology = self.editor.topology;
+        let target = self.grid.get(x, y);
+        let value = if target == 0 { 1 } else { 0 };
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            if self.grid.get(x, y) != target {
+                continue;
+            }
+            self.grid.set(x, y, value);
+            let (x, y) = (x as isize, y as isize);
+            for (nx, ny) in [(x - 1, y), (x, y - 1), (x + 1, y), (x, y + 1)] {
+                stack.extend(topology.wrap(nx, ny, w, h));
+            }
+        }
+    }
+
+    pub fn random_soup(&mut self, soup: Soup) {
+        let (x, y, w, h) = match self.editor.selection() {
+            Some(s) => (s.x, s.y, s.width, s.height),
+            None => (0, 0, self.grid.width(), self.grid.height()),
+        };
+        soup.fill(self.grid, x, y, w, h);
+        self.editor.soup_density = soup.density;
+        self.editor.soup_symmetry = soup.symmetry;
+        self.editor.last_soup = Some(soup);
+        self.editor.message = Some(format!("Soup: {}", soup));
+    }
+
     pub fn clear_all(&mut self) {
         self.grid.set_all(0);
     }
@@ -85,12 +782,308 @@ impl<'a> EditSteps<'a> {
     pub fn move_cursor_by(&mut self, by_x: isize, by_y: isize) {
         let (mut x, mut y) = self.editor.cursor;
         let (w, h) = (self.grid.width(), self.grid.height());
-        x = (x as isize + by_x + w as isize) as usize % w;
-        y = (y as isize + by_y + h as isize) as usize % h;
+        x = (x as isize + by_x % w as isize).rem_euclid(w as isize) as usize;
+        y = (y as isize + by_y % h as isize).rem_euclid(h as isize) as usize;
         self.editor.set_cursor(x, y);
     }
 
     pub fn move_cursor_to(&mut self, x: usize, y: usize) {
-        self.editor.set_cursor(x, y);
+        if self.on_grid(x, y) {
+            self.editor.set_cursor(x, y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toggle(editor: &mut Editor, grid: &mut Grid<u8>, x: usize, y: usize) {
+        editor.apply_action(EditAction::ToggleCellAt { x, y }, grid);
+    }
+
+    fn alive(grid: &Grid<u8>) -> Vec<(usize, usize)> {
+        (0..grid.height())
+            .flat_map(|y| (0..grid.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| grid.get(x, y) != 0)
+            .collect()
+    }
+
+    /// A board with `cells` alive, and an editor selecting from `from` to `to`.
+    fn selecting(
+        (width, height): (usize, usize),
+        cells: &[(usize, usize)],
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> (Editor, Grid<u8>) {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(width, height));
+        for &(x, y) in cells {
+            grid.set(x, y, 1);
+        }
+        editor.apply_action(
+            EditAction::MoveCursorTo {
+                x: from.0,
+                y: from.1,
+            },
+            &mut grid,
+        );
+        editor.apply_action(EditAction::ToggleSelection, &mut grid);
+        editor.apply_action(EditAction::MoveCursorTo { x: to.0, y: to.1 }, &mut grid);
+        (editor, grid)
+    }
+
+    #[test]
+    fn undo_and_redo_go_back_and_forth_through_the_edits() {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(4, 3));
+        toggle(&mut editor, &mut grid, 1, 1);
+        let once = grid.clone();
+        toggle(&mut editor, &mut grid, 2, 0);
+        let twice = grid.clone();
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, once);
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, Grid::new(4, 3));
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(editor.message(), Some("Nothing to undo"));
+        editor.apply_action(EditAction::Redo, &mut grid);
+        editor.apply_action(EditAction::Redo, &mut grid);
+        assert_eq!(grid, twice);
+    }
+
+    #[test]
+    fn only_the_cells_an_edit_changed_are_kept() {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(100, 100));
+        toggle(&mut editor, &mut grid, 10, 20);
+        editor.apply_action(EditAction::MoveCursorBy { x: 1, y: 0 }, &mut grid);
+        assert_eq!(
+            editor.undo,
+            [Change {
+                cells: vec![(10, 20, 0)]
+            }]
+        );
+    }
+
+    #[test]
+    fn actions_that_cant_change_cells_dont_copy_the_board() {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(100, 100));
+        editor.apply_action(EditAction::MoveCursorBy { x: 1, y: 0 }, &mut grid);
+        editor.apply_action(EditAction::ToggleSelection, &mut grid);
+        editor.apply_action(EditAction::Copy, &mut grid);
+        assert_eq!(editor.before.width(), 0);
+        editor.apply_action(EditAction::ToggleCell, &mut grid);
+        assert_eq!(editor.before.width(), 100);
+    }
+
+    #[test]
+    fn the_cursor_wraps_round_by_more_than_the_width() {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(5, 3));
+        editor.apply_action(EditAction::MoveCursorBy { x: -8, y: 0 }, &mut grid);
+        assert_eq!(editor.cursor, (2, 0));
+        editor.apply_action(EditAction::MoveCursorBy { x: 8, y: -7 }, &mut grid);
+        assert_eq!(editor.cursor, (0, 2));
+    }
+
+    #[test]
+    fn a_turned_stamp_is_placed_at_the_cursor() {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(6, 6));
+        let glider = Pattern::new(
+            "Glider".to_owned(),
+            vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)],
+        );
+        editor.apply_action(EditAction::AddPattern(Box::new(glider)), &mut grid);
+        let turn = EditAction::TransformStamp(Transform::RotateClockwise);
+        editor.apply_action(turn, &mut grid);
+        editor.apply_action(EditAction::MoveCursorTo { x: 2, y: 1 }, &mut grid);
+        editor.apply_action(EditAction::CommitStamp, &mut grid);
+        assert_eq!(alive(&grid), [(2, 1), (2, 2), (4, 2), (2, 3), (3, 3)]);
+        assert!(editor.stamp().is_none());
+    }
+
+    #[test]
+    fn a_copied_region_is_pasted_at_the_cursor_and_undone_in_one_step() {
+        let cells = [(1, 1), (2, 1), (2, 2)];
+        let (mut editor, mut grid) = selecting((6, 6), &cells, (2, 2), (1, 1));
+        let original = grid.clone();
+        editor.apply_action(EditAction::Copy, &mut grid);
+        assert_eq!(editor.message(), Some("Copied 3 cells (2x2)"));
+        let clipboard = editor.clipboard().unwrap();
+        assert_eq!(clipboard.cells, [(0, 0), (1, 0), (1, 1)]);
+        assert_eq!((clipboard.width, clipboard.height), (2, 2));
+        assert_eq!(grid, original);
+
+        editor.apply_action(EditAction::Paste, &mut grid);
+        assert!(editor.selection().is_none());
+        editor.apply_action(EditAction::MoveCursorTo { x: 3, y: 3 }, &mut grid);
+        editor.apply_action(EditAction::CommitStamp, &mut grid);
+        assert_eq!(
+            alive(&grid),
+            [(1, 1), (2, 1), (2, 2), (3, 3), (4, 3), (4, 4)]
+        );
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, original);
+    }
+
+    #[test]
+    fn a_cut_leaves_a_hole_and_is_undone_in_one_step() {
+        let cells = [(0, 0), (1, 1), (2, 1), (2, 2)];
+        let (mut editor, mut grid) = selecting((6, 6), &cells, (1, 1), (2, 2));
+        let original = grid.clone();
+        editor.apply_action(EditAction::Cut, &mut grid);
+        assert_eq!(alive(&grid), [(0, 0)]);
+        assert_eq!(editor.clipboard().unwrap().cells, [(0, 0), (1, 0), (1, 1)]);
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, original);
+    }
+
+    #[test]
+    fn filling_and_clearing_the_selection_are_each_undone_in_one_step() {
+        let cells = [(0, 0), (1, 1), (5, 5)];
+        let (mut editor, mut grid) = selecting((6, 6), &cells, (1, 1), (2, 2));
+        let original = grid.clone();
+        let edits = [
+            (
+                EditAction::FillSelection,
+                vec![(0, 0), (1, 1), (2, 1), (1, 2), (2, 2), (5, 5)],
+            ),
+            (EditAction::ClearSelection, vec![(0, 0), (5, 5)]),
+            (EditAction::ClearOutsideSelection, vec![(1, 1)]),
+        ];
+        for (action, expected) in edits {
+            editor.apply_action(action.clone(), &mut grid);
+            assert_eq!(alive(&grid), expected, "{:?}", action);
+            editor.apply_action(EditAction::Undo, &mut grid);
+            assert_eq!(grid, original, "undoing {:?}", action);
+        }
+    }
+
+    #[test]
+    fn a_selection_moved_onto_itself_is_undone_in_one_step() {
+        let cells = [(0, 1), (1, 1), (2, 1)];
+        let (mut editor, mut grid) = selecting((6, 3), &cells, (0, 1), (2, 1));
+        let original = grid.clone();
+        editor.apply_action(EditAction::MoveSelection, &mut grid);
+        assert!(alive(&grid).is_empty());
+        assert!(editor.selection().is_none());
+        assert_eq!(editor.get_cursor(), (0, 1));
+        assert_eq!(
+            editor.stamp().unwrap().pattern().cells,
+            cells.map(|(x, _)| (x, 0))
+        );
+
+        editor.apply_action(EditAction::MoveCursorBy { x: 1, y: 0 }, &mut grid);
+        editor.apply_action(EditAction::CommitStamp, &mut grid);
+        let moved = grid.clone();
+        assert_eq!(alive(&moved), [(1, 1), (2, 1), (3, 1)]);
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, original);
+        editor.apply_action(EditAction::Redo, &mut grid);
+        assert_eq!(grid, moved);
+    }
+
+    #[test]
+    fn a_selection_moved_after_another_edit_is_undone_apart_from_it() {
+        let cells = [(0, 0), (1, 0)];
+        let (mut editor, mut grid) = selecting((6, 3), &cells, (0, 0), (1, 0));
+        editor.apply_action(EditAction::MoveSelection, &mut grid);
+        toggle(&mut editor, &mut grid, 5, 2);
+        let toggled = grid.clone();
+        editor.apply_action(EditAction::MoveCursorTo { x: 2, y: 1 }, &mut grid);
+        editor.apply_action(EditAction::CommitStamp, &mut grid);
+        assert_eq!(alive(&grid), [(2, 1), (3, 1), (5, 2)]);
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, toggled);
+    }
+
+    #[test]
+    fn a_flood_fill_stops_at_live_cells_and_is_undone_in_one_step() {
+        let wall = [(2, 0), (2, 1), (2, 2), (0, 1), (1, 1)];
+        let (mut editor, mut grid) = selecting((5, 3), &wall, (0, 0), (0, 0));
+        let original = grid.clone();
+        editor.apply_action(EditAction::FloodFillAt { x: 0, y: 0 }, &mut grid);
+        assert_eq!(
+            alive(&grid),
+            [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1), (2, 2)]
+        );
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, original);
+    }
+
+    #[test]
+    fn a_flood_fill_crosses_the_edges_a_torus_joins_but_not_a_plane() {
+        let wall = [(2, 0), (2, 1), (2, 2)];
+        let (mut editor, mut grid) = selecting((5, 3), &wall, (0, 0), (0, 0));
+        let original = grid.clone();
+        editor.apply_action(EditAction::FloodFill, &mut grid);
+        assert_eq!(alive(&grid).len(), 3 + 6);
+        assert_eq!(grid.get(3, 0), 0);
+        editor.apply_action(EditAction::Undo, &mut grid);
+
+        editor.set_topology(Topology::Torus);
+        editor.apply_action(EditAction::FloodFill, &mut grid);
+        assert_eq!(alive(&grid).len(), 15);
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, original);
+    }
+
+    #[test]
+    fn lines_are_drawn_on_from_the_anchor_and_each_undone_in_one_step() {
+        let (mut editor, mut grid) = selecting((5, 5), &[], (0, 0), (3, 3));
+        editor.apply_action(EditAction::Draw(Shape::Line), &mut grid);
+        let first = grid.clone();
+        assert_eq!(alive(&first), [(0, 0), (1, 1), (2, 2), (3, 3)]);
+        assert_eq!(
+            editor.selection().map(|s| (s.x, s.y, s.width, s.height)),
+            Some((3, 3, 1, 1))
+        );
+
+        editor.apply_action(EditAction::MoveCursorTo { x: 3, y: 0 }, &mut grid);
+        editor.apply_action(EditAction::Draw(Shape::Line), &mut grid);
+        assert_eq!(
+            alive(&grid),
+            [(0, 0), (3, 0), (1, 1), (3, 1), (2, 2), (3, 2), (3, 3)]
+        );
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, first);
+        editor.apply_action(EditAction::Undo, &mut grid);
+        assert_eq!(grid, Grid::new(5, 5));
+    }
+
+    #[test]
+    fn other_shapes_end_the_selection() {
+        let (mut editor, mut grid) = selecting((5, 5), &[], (1, 1), (3, 2));
+        editor.apply_action(EditAction::Draw(Shape::FilledRectangle), &mut grid);
+        assert_eq!(
+            alive(&grid),
+            [(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]
+        );
+        assert!(editor.selection().is_none());
+        editor.apply_action(EditAction::Draw(Shape::Line), &mut grid);
+        assert_eq!(alive(&grid).len(), 6);
+    }
+
+    #[test]
+    fn history_is_given_and_taken_as_whole_grids() {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(4, 3));
+        toggle(&mut editor, &mut grid, 0, 0);
+        toggle(&mut editor, &mut grid, 1, 0);
+        toggle(&mut editor, &mut grid, 2, 0);
+        editor.apply_action(EditAction::Undo, &mut grid);
+        let (undo, redo) = editor.history(&grid);
+        assert_eq!((undo.len(), redo.len()), (2, 1));
+        assert_eq!(undo[0], Grid::new(4, 3));
+
+        let mut restored = Editor::new();
+        restored.set_history(&undo, &redo, &grid);
+        assert_eq!(restored.history(&grid), (undo, redo));
+        assert_eq!(restored.undo, editor.undo);
+        assert_eq!(restored.redo, editor.redo);
+    }
+
+    #[test]
+    fn a_change_of_size_forgets_the_history() {
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(4, 3));
+        toggle(&mut editor, &mut grid, 0, 0);
+        editor.remember(&grid, &Grid::new(5, 3));
+        assert_eq!(editor.history(&grid), (Vec::new(), Vec::new()));
     }
 }
@@ -0,0 +1,192 @@
+//! Recording generations as an animated GIF.
+
+use export::Style;
+use gif::{self, Encoder, Frame, Repeat};
+use grid::Grid;
+use std::borrow::Cow;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Gif(gif::EncodingError),
+    /// The picture would be bigger than a GIF can be.
+    TooBig(usize, usize),
+    /// The board changed size part way through.
+    SizeChanged,
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Gif(err) => write!(f, "{}", err),
+            Error::TooBig(width, height) => {
+                write!(f, "A {} by {} picture is too big for a GIF", width, height)
+            }
+            Error::SizeChanged => write!(f, "The board changed size while recording"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Error {
+        Error::Io(other)
+    }
+}
+
+impl From<gif::EncodingError> for Error {
+    fn from(other: gif::EncodingError) -> Error {
+        Error::Gif(other)
+    }
+}
+
+/// Writes each generation it's given to an animated GIF that loops forever.
+pub struct Recorder {
+    encoder: Encoder<BufWriter<File>>,
+    style: Style,
+    /// The size of the board in cells.
+    size: (usize, usize),
+    /// In hundredths of a second, as GIFs have it.
+    delay: u16,
+    frames: usize,
+    /// The first thing that went wrong, after which no more frames are written.
+    error: Option<Error>,
+}
+
+impl Recorder {
+    /// Starts a GIF of a board `width` by `height` cells, showing each generation for
+    /// `delay`.
+    pub fn new(
+        path: &Path,
+        width: usize,
+        height: usize,
+        style: Style,
+        delay: Duration,
+    ) -> Result<Recorder, Error> {
+        let (image_width, image_height) = style.image_size(width, height);
+        if image_width > u16::MAX as usize || image_height > u16::MAX as usize {
+            return Err(Error::TooBig(image_width, image_height));
+        }
+        let palette: Vec<u8> = style
+            .palette()
+            .iter()
+            .flat_map(|colour| vec![colour.0, colour.1, colour.2])
+            .collect();
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = Encoder::new(file, image_width as u16, image_height as u16, &palette)?;
+        encoder.set_repeat(Repeat::Infinite)?;
+        Ok(Recorder {
+            encoder,
+            style,
+            size: (width, height),
+            delay: (delay.as_millis() / 10).min(u16::MAX as u128) as u16,
+            frames: 0,
+            error: None,
+        })
+    }
+
+    /// Adds a frame showing `grid`. If that goes wrong the error is kept for `finish`,
+    /// so that the game doesn't have to stop for it.
+    pub fn record(&mut self, grid: &Grid<u8>) {
+        if self.error.is_some() {
+            return;
+        }
+        if (grid.width(), grid.height()) != self.size {
+            self.error = Some(Error::SizeChanged);
+            return;
+        }
+        let (width, height) = self.style.image_size(grid.width(), grid.height());
+        let frame = Frame {
+            width: width as u16,
+            height: height as u16,
+            delay: self.delay,
+            buffer: Cow::Owned(self.style.rasterise(grid)),
+            ..Frame::default()
+        };
+        match self.encoder.write_frame(&frame) {
+            Ok(()) => self.frames += 1,
+            Err(err) => self.error = Some(err.into()),
+        }
+    }
+
+    /// Ends the GIF, returning how many frames it has, or the first error.
+    pub fn finish(self) -> Result<usize, Error> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.encoder.into_inner()?.flush()?;
+        Ok(self.frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn each_generation_is_a_frame_of_the_gif() {
+        let path = env::temp_dir().join(format!("gol-animation-{}.gif", std::process::id()));
+        let style = Style {
+            cell_size: 2,
+            ..Style::default()
+        };
+        let mut recorder = Recorder::new(&path, 3, 2, style, Duration::from_millis(250)).unwrap();
+        let mut grid = Grid::new(3, 2);
+        let mut rasters = Vec::new();
+        for x in 0..3 {
+            grid.set(x, x % 2, 1);
+            recorder.record(&grid);
+            rasters.push(style.rasterise(&grid));
+        }
+        assert_eq!(recorder.finish().unwrap(), 3);
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (6, 4));
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height, frame.delay), (6, 4, 25));
+            frames.push(frame.buffer.to_vec());
+        }
+        assert_eq!(frames, rasters);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_board_that_changes_size_spoils_the_recording() {
+        let path = env::temp_dir().join(format!("gol-resized-{}.gif", std::process::id()));
+        let delay = Duration::from_millis(100);
+        let mut recorder = Recorder::new(&path, 3, 2, Style::default(), delay).unwrap();
+        recorder.record(&Grid::new(3, 2));
+        recorder.record(&Grid::new(4, 2));
+        recorder.record(&Grid::new(3, 2));
+        match recorder.finish() {
+            Err(Error::SizeChanged) => {}
+            result => panic!("expected the size to have changed, got {:?}", result),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_picture_too_big_for_a_gif_is_refused() {
+        let path = env::temp_dir().join(format!("gol-too-big-{}.gif", std::process::id()));
+        let delay = Duration::from_millis(100);
+        match Recorder::new(&path, 20_000, 2, Style::default(), delay) {
+            Err(Error::TooBig(80_000, 8)) => {}
+            Err(err) => panic!("expected the picture to be too big, got {}", err),
+            Ok(_) => panic!("expected the picture to be too big"),
+        }
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,230 @@
+//! Drawing the board as pictures, for sharing outside the terminal.
+
+pub mod animation;
+pub mod picture;
+
+use editor::Selection;
+use export::animation::Recorder;
+use game::Gol;
+use grid::Grid;
+use options::Options;
+use stats::Stats;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+/// An RGB colour.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Colour(pub u8, pub u8, pub u8);
+
+static COLOUR_NAMES: &[(&str, Colour)] = &[
+    ("black", Colour(0, 0, 0)),
+    ("red", Colour(255, 0, 0)),
+    ("green", Colour(0, 255, 0)),
+    ("yellow", Colour(255, 255, 0)),
+    ("blue", Colour(0, 0, 255)),
+    ("magenta", Colour(255, 0, 255)),
+    ("cyan", Colour(0, 255, 255)),
+    ("white", Colour(255, 255, 255)),
+    ("grey", Colour(128, 128, 128)),
+    ("gray", Colour(128, 128, 128)),
+];
+
+/// A colour that is neither a name nor six hex digits, as it was given.
+#[derive(Debug)]
+pub struct ParseColourError(String);
+
+impl error::Error for ParseColourError {}
+
+impl Display for ParseColourError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Invalid colour: {}", self.0)
+    }
+}
+
+impl FromStr for Colour {
+    type Err = ParseColourError;
+
+    /// A colour name such as `red`, or hex digits such as `#ff8000`.
+    fn from_str(s: &str) -> Result<Colour, ParseColourError> {
+        let lower = s.to_lowercase();
+        if let Some(&(_, colour)) = COLOUR_NAMES.iter().find(|&&(name, _)| name == lower) {
+            return Ok(colour);
+        }
+        let hex = lower.trim_start_matches('#');
+        let component = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| ParseColourError(s.to_owned()))
+        };
+        if hex.len() != 6 {
+            return Err(ParseColourError(s.to_owned()));
+        }
+        Ok(Colour(component(0)?, component(2)?, component(4)?))
+    }
+}
+
+impl Display for Colour {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// The biggest a cell can be drawn, in pixels on a side.
+pub const MAX_CELL_SIZE: u64 = 64;
+
+/// How cells are drawn. The default colours are the same as in the terminal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Style {
+    /// The width and height of a cell in pixels.
+    pub cell_size: usize,
+    pub alive: Colour,
+    pub dead: Colour,
+    /// The colour of the lines between cells, if there are any.
+    pub grid_lines: Option<Colour>,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            cell_size: 4,
+            alive: Colour(255, 0, 0),
+            dead: Colour(0, 0, 0),
+            grid_lines: None,
+        }
+    }
+}
+
+/// The colour index of a dead cell in a rasterised grid.
+pub const DEAD: u8 = 0;
+/// The colour index of a live cell.
+pub const ALIVE: u8 = 1;
+/// The colour index of the lines between cells.
+pub const LINE: u8 = 2;
+
+impl Style {
+    /// The dead, alive and grid line colours, in the order of their indexes.
+    pub fn palette(&self) -> [Colour; 3] {
+        [self.dead, self.alive, self.grid_lines.unwrap_or(self.dead)]
+    }
+
+    /// The distance in pixels from the start of one cell to the start of the next.
+    fn pitch(&self) -> usize {
+        self.cell_size + self.grid_lines.is_some() as usize
+    }
+
+    /// The size in pixels of a picture of `width` by `height` cells.
+    pub fn image_size(&self, width: usize, height: usize) -> (usize, usize) {
+        let border = self.grid_lines.is_some() as usize;
+        (
+            width * self.pitch() + border,
+            height * self.pitch() + border,
+        )
+    }
+
+    /// The grid as one colour index per pixel, row by row.
+    pub fn rasterise(&self, grid: &Grid<u8>) -> Vec<u8> {
+        let (width, height) = self.image_size(grid.width(), grid.height());
+        let pitch = self.pitch();
+        let lines = self.grid_lines.is_some();
+        let mut pixels = Vec::with_capacity(width * height);
+        for py in 0..height {
+            for px in 0..width {
+                let pixel = if lines && (px % pitch == 0 || py % pitch == 0) {
+                    LINE
+                } else if grid.get(px / pitch, py / pitch) != 0 {
+                    ALIVE
+                } else {
+                    DEAD
+                };
+                pixels.push(pixel);
+            }
+        }
+        pixels
+    }
+}
+
+/// Starts recording a GIF of a board the size of `grid`, as the options say.
+pub fn recorder_for(
+    path: &Path,
+    grid: &Grid<u8>,
+    options: &Options,
+) -> Result<Recorder, animation::Error> {
+    let (width, height) = (grid.width(), grid.height());
+    Recorder::new(path, width, height, options.style, options.frame_delay)
+}
+
+/// Saves a picture of the selection if there is one, or else the board, cropped to the
+/// live cells if the options say so.
+pub fn save_snapshot(
+    path: &Path,
+    game: &Gol,
+    selection: Option<Selection>,
+    options: &Options,
+) -> Result<(), picture::Error> {
+    let grid = game.grid();
+    let area = match selection {
+        Some(selection) => Some((selection.x, selection.y, selection.width, selection.height)),
+        None if options.crop => Stats::of(game.generation(), grid).bounding_box,
+        None => None,
+    };
+    match area {
+        Some((x, y, width, height)) => {
+            picture::save(path, &grid.crop(x, y, width, height), &options.style)
+        }
+        None => picture::save(path, grid, &options.style),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_are_read_by_name_or_hex_and_written_as_hex() {
+        assert_eq!("Cyan".parse::<Colour>().unwrap(), Colour(0, 255, 255));
+        assert_eq!("#ff8000".parse::<Colour>().unwrap(), Colour(255, 128, 0));
+        assert_eq!("FF8000".parse::<Colour>().unwrap(), Colour(255, 128, 0));
+        let colour = Colour(18, 52, 171);
+        assert_eq!(colour.to_string(), "#1234ab");
+        assert_eq!(colour.to_string().parse::<Colour>().unwrap(), colour);
+        for s in ["", "pink", "#ff80", "#ff80000", "#gg8000", "#ff80\u{e9}"] {
+            assert!(s.parse::<Colour>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn grid_lines_go_round_and_between_the_cells() {
+        let grid = Grid::with_data(2, 2, vec![1, 0, 0, 1]);
+        let style = Style {
+            cell_size: 2,
+            grid_lines: Some(Colour(128, 128, 128)),
+            ..Style::default()
+        };
+        assert_eq!(style.image_size(2, 2), (7, 7));
+        let (d, a, l) = (DEAD, ALIVE, LINE);
+        #[rustfmt::skip]
+        let pixels = vec![
+            l, l, l, l, l, l, l,
+            l, a, a, l, d, d, l,
+            l, a, a, l, d, d, l,
+            l, l, l, l, l, l, l,
+            l, d, d, l, a, a, l,
+            l, d, d, l, a, a, l,
+            l, l, l, l, l, l, l,
+        ];
+        assert_eq!(style.rasterise(&grid), pixels);
+    }
+
+    #[test]
+    fn without_grid_lines_cells_fill_the_picture() {
+        let grid = Grid::with_data(2, 2, vec![0, 1, 0, 0]);
+        let style = Style {
+            cell_size: 1,
+            ..Style::default()
+        };
+        assert_eq!(style.image_size(2, 2), (2, 2));
+        assert_eq!(style.rasterise(&grid), [DEAD, ALIVE, DEAD, DEAD]);
+    }
+}
@@ -0,0 +1,259 @@
+//! Saving a single generation as a PNG or SVG picture.
+
+use export::{Colour, Style, ALIVE, DEAD, LINE};
+use grid::Grid;
+use pattern::extension;
+use png::{self, BitDepth, ColorType, Encoder};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// PNGs are drawn in memory, so they're kept to this many pixels.
+const MAX_PNG_PIXELS: usize = 1 << 28;
+
+/// Why a picture couldn't be saved.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Png(png::EncodingError),
+    /// The extension of the path, which isn't one of the `Format`s.
+    UnknownFormat(String),
+    /// The picture would have more pixels than a PNG is drawn with.
+    TooBig(usize, usize),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Png(err) => write!(f, "{}", err),
+            Error::UnknownFormat(ext) => write!(f, "Unknown picture format: {:?}", ext),
+            Error::TooBig(width, height) => {
+                write!(
+                    f,
+                    "A {} by {} picture is too big to draw as a PNG",
+                    width, height
+                )
+            }
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Error {
+        Error::Io(other)
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(other: png::EncodingError) -> Error {
+        Error::Png(other)
+    }
+}
+
+/// The kinds of picture that can be saved.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Png,
+    Svg,
+}
+
+impl Format {
+    /// The format for a file extension, `.png` or `.svg`.
+    pub fn from_path(path: &Path) -> Result<Format, Error> {
+        let ext = extension(path);
+        match ext.as_str() {
+            "png" => Ok(Format::Png),
+            "svg" => Ok(Format::Svg),
+            _ => Err(Error::UnknownFormat(ext)),
+        }
+    }
+}
+
+/// The size in pixels of a PNG of `grid`, if it isn't too big to draw.
+fn png_size(grid: &Grid<u8>, style: &Style) -> Result<(usize, usize), Error> {
+    let (width, height) = style.image_size(grid.width(), grid.height());
+    if width
+        .checked_mul(height)
+        .is_none_or(|pixels| pixels > MAX_PNG_PIXELS)
+    {
+        return Err(Error::TooBig(width, height));
+    }
+    Ok((width, height))
+}
+
+/// Draws each cell as a square of pixels, with a palette of the style's colours.
+pub fn write_png<W: Write>(out: W, grid: &Grid<u8>, style: &Style) -> Result<(), Error> {
+    let (width, height) = png_size(grid, style)?;
+    let palette: Vec<u8> = style
+        .palette()
+        .iter()
+        .flat_map(|colour| vec![colour.0, colour.1, colour.2])
+        .collect();
+    let mut encoder = Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(ColorType::Indexed);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.set_palette(palette);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&style.rasterise(grid))?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Draws each run of live cells in a row as one rectangle, and the grid lines as one path,
+/// in the same places as the pixels of a PNG.
+pub fn write_svg<W: Write>(mut out: W, grid: &Grid<u8>, style: &Style) -> Result<(), Error> {
+    let (width, height) = style.image_size(grid.width(), grid.height());
+    let palette = style.palette();
+    let colour = |index: u8| -> Colour { palette[index as usize] };
+    let pitch = style.pitch();
+    let border = style.grid_lines.is_some() as usize;
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\" shape-rendering=\"crispEdges\">",
+        width, height
+    )?;
+    writeln!(
+        out,
+        "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        width,
+        height,
+        colour(DEAD)
+    )?;
+    writeln!(out, "<g fill=\"{}\">", colour(ALIVE))?;
+    for y in 0..grid.height() {
+        let mut x = 0;
+        while x < grid.width() {
+            if grid.get(x, y) == 0 {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < grid.width() && grid.get(x, y) != 0 {
+                x += 1;
+            }
+            // the lines between the cells of a run are drawn over it afterwards
+            writeln!(
+                out,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                start * pitch + border,
+                y * pitch + border,
+                (x - start) * pitch - border,
+                style.cell_size
+            )?;
+        }
+    }
+    writeln!(out, "</g>")?;
+    if style.grid_lines.is_some() {
+        let mut path = String::new();
+        for x in 0..=grid.width() {
+            path += &format!("M{}.5 0V{}", x * pitch, height);
+        }
+        for y in 0..=grid.height() {
+            path += &format!("M0 {}.5H{}", y * pitch, width);
+        }
+        writeln!(
+            out,
+            "<path d=\"{}\" stroke=\"{}\" stroke-width=\"1\"/>",
+            path,
+            colour(LINE)
+        )?;
+    }
+    writeln!(out, "</svg>")?;
+    Ok(())
+}
+
+/// Saves `grid` as a PNG or SVG, depending on the extension of `path`.
+pub fn save(path: &Path, grid: &Grid<u8>, style: &Style) -> Result<(), Error> {
+    let format = Format::from_path(path)?;
+    // checked before the file is created, so that nothing is left behind
+    if format == Format::Png {
+        png_size(grid, style)?;
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Png => write_png(&mut out, grid, style)?,
+        Format::Svg => write_svg(&mut out, grid, style)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svg(grid: &Grid<u8>, style: &Style) -> String {
+        let mut out = Vec::new();
+        write_svg(&mut out, grid, style).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn runs_cover_the_same_pixels_as_a_png() {
+        let grid = Grid::with_data(3, 2, vec![1, 1, 0, 0, 0, 1]);
+        let style = Style {
+            cell_size: 2,
+            grid_lines: Some(Colour(128, 128, 128)),
+            ..Style::default()
+        };
+        let expected = [
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"7\" \
+             viewBox=\"0 0 10 7\" shape-rendering=\"crispEdges\">",
+            "<rect width=\"10\" height=\"7\" fill=\"#000000\"/>",
+            "<g fill=\"#ff0000\">",
+            // pixels 1 to 5 across, with the line at 3 drawn over them
+            "<rect x=\"1\" y=\"1\" width=\"5\" height=\"2\"/>",
+            "<rect x=\"7\" y=\"4\" width=\"2\" height=\"2\"/>",
+            "</g>",
+            "<path d=\"M0.5 0V7M3.5 0V7M6.5 0V7M9.5 0V7M0 0.5H10M0 3.5H10M0 6.5H10\" \
+             stroke=\"#808080\" stroke-width=\"1\"/>",
+            "</svg>",
+        ];
+        assert_eq!(svg(&grid, &style).lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn without_grid_lines_runs_are_whole_cells() {
+        let grid = Grid::with_data(3, 1, vec![0, 1, 1]);
+        let style = Style {
+            cell_size: 3,
+            ..Style::default()
+        };
+        let svg = svg(&grid, &style);
+        assert!(svg.contains("<rect x=\"3\" y=\"0\" width=\"6\" height=\"3\"/>"));
+        assert!(!svg.contains("<path"));
+    }
+
+    #[test]
+    fn the_format_is_chosen_by_extension() {
+        assert_eq!(Format::from_path(Path::new("a.PNG")).unwrap(), Format::Png);
+        assert_eq!(
+            Format::from_path(Path::new("b/c.svg")).unwrap(),
+            Format::Svg
+        );
+        match Format::from_path(Path::new("a.jpg")) {
+            Err(Error::UnknownFormat(ext)) => assert_eq!(ext, "jpg"),
+            format => panic!("{:?}", format),
+        }
+    }
+
+    #[test]
+    fn a_png_too_big_to_draw_is_refused_without_making_a_file() {
+        let path = std::env::temp_dir().join(format!("gol-too-big-{}.png", std::process::id()));
+        let style = Style {
+            cell_size: 64,
+            ..Style::default()
+        };
+        match save(&path, &Grid::new(300, 300), &style) {
+            Err(Error::TooBig(19_200, 19_200)) => {}
+            result => panic!("expected the picture to be too big, got {:?}", result),
+        }
+        assert!(!path.exists());
+    }
+}
@@ -1,68 +1,279 @@
+//! A board running a rule, with its history.
+
+use cycle::{Cycle, CycleDetector};
 use grid::{Game, Grid};
+use pattern::{self, Pattern};
+use rule::Rule;
+use stats::Stats;
+use std::collections::VecDeque;
+use std::path::Path;
+use topology::Topology;
+
+/// How many generations of statistics are kept.
+pub const STATS_LIMIT: usize = 100_000;
 
+/// A board of live and dead cells, with a rule and a topology, that keeps track of its
+/// generation, any cycle it has settled into and its statistics.
 #[derive(Debug)]
 pub struct Gol {
     grid: Game<u8>,
-    wrapped: bool,
+    topology: Topology,
+    rule: Rule,
+    generation: u64,
+    cycles: CycleDetector,
+    /// The statistics of recent generations, oldest first.
+    stats: VecDeque<Stats>,
 }
 
 impl Gol {
-    pub fn new(width: usize, height: usize, wrapped: bool) -> Gol {
+    /// An empty board running Conway's Game of Life.
+    pub fn new(width: usize, height: usize, topology: Topology) -> Gol {
         Gol {
             grid: Game::new(width, height),
-            wrapped,
+            topology,
+            rule: Rule::life(),
+            generation: 0,
+            cycles: CycleDetector::new(),
+            stats: VecDeque::new(),
         }
     }
 
-    pub fn new_from_grid(grid: Grid<u8>, wrapped: bool) -> Gol {
+    /// A board with the given cells, 1 for alive and 0 for dead, running Conway's Game
+    /// of Life.
+    pub fn new_from_grid(grid: Grid<u8>, topology: Topology) -> Gol {
         Gol {
             grid: grid.into(),
-            wrapped,
+            topology,
+            rule: Rule::life(),
+            generation: 0,
+            cycles: CycleDetector::new(),
+            stats: VecDeque::new(),
         }
     }
 
+    /// Brings the given cells to life. They must be on the board.
     pub fn init(&mut self, cells: &[(usize, usize)]) {
+        self.cycles.reset();
         for &(x, y) in cells {
             self.grid.grid_mut().set(x, y, 1);
         }
     }
 
+    /// Starts again from generation 0 with `pattern` in the middle of an empty board the
+    /// same size, running the rule it was designed for if it gives one. Cells that don't
+    /// fit are left out.
+    pub fn restart_with(&mut self, pattern: &Pattern) {
+        let (width, height) = (self.grid().width(), self.grid().height());
+        let rule = pattern.rule.clone().unwrap_or_else(|| self.rule.clone());
+        *self = Gol::new(width, height, self.topology);
+        self.set_rule(rule);
+        self.init(&pattern.centred_in(width, height));
+    }
+
+    /// Whether the cell at `(x, y)` is alive. Cells off the board are dead.
+    pub fn is_alive(&self, x: usize, y: usize) -> bool {
+        let grid = self.grid();
+        x < grid.width() && y < grid.height() && grid.get(x, y) != 0
+    }
+
+    /// Brings the cell at `(x, y)` to life or kills it. Cells off the board are ignored.
+    pub fn set_alive(&mut self, x: usize, y: usize, alive: bool) {
+        let grid = self.grid_mut();
+        if x < grid.width() && y < grid.height() {
+            grid.set(x, y, alive as u8);
+        }
+    }
+
+    /// The cells, 1 for alive and 0 for dead.
     pub fn grid(&self) -> &Grid<u8> {
         self.grid.grid()
     }
 
+    /// The grid, for editing. Any cycle that was found is forgotten.
     pub fn grid_mut(&mut self) -> &mut Grid<u8> {
+        self.cycles.reset();
         self.grid.grid_mut()
     }
 
+    /// How the edges of the board are joined.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Joins the edges of the board differently. Any cycle that was found is forgotten.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        self.cycles.reset();
+    }
+
+    /// Changes the size of the board, keeping the cells in the top left corner that still
+    /// fit. Any cycle that was found is forgotten.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let old = self.grid.grid();
+        let mut grid = Grid::new(width, height);
+        for y in 0..height.min(old.height()) {
+            for x in 0..width.min(old.width()) {
+                grid.set(x, y, old.get(x, y));
+            }
+        }
+        self.grid = grid.into();
+        self.cycles.reset();
+    }
+
+    /// Saves the whole board, with its rule, as a pattern named after the file.
+    pub fn save(&self, path: &Path) -> Result<(), pattern::Error> {
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        let mut pattern = Pattern::from_grid(name, self.grid());
+        pattern.rule = Some(self.rule.clone());
+        pattern.save(path)
+    }
+
+    /// The rule the board runs.
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    /// Changes the rule. Any cycle that was found is forgotten.
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        self.cycles.reset();
+    }
+
+    /// The number of generations run, counting from the start or the last
+    /// `set_generation`.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Changes the generation number, e.g. when a saved board is loaded. Any cycle that
+    /// was found is forgotten.
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+        self.cycles.reset();
+    }
+
+    /// The number of live cells.
+    pub fn population(&self) -> usize {
+        let grid = self.grid();
+        (0..grid.height())
+            .flat_map(|y| (0..grid.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| grid.get(x, y) != 0)
+            .count()
+    }
+
+    /// The cycle the board has settled into, if it has been seen to repeat since it was
+    /// last changed.
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycles.cycle()
+    }
+
+    /// The statistics of each generation run, oldest first, up to `STATS_LIMIT` of them.
+    pub fn stats(&self) -> &VecDeque<Stats> {
+        &self.stats
+    }
+
+    /// Runs one generation.
     pub fn next_turn(&mut self) {
+        if self.cycles.is_empty() {
+            self.cycles
+                .record(self.generation, self.grid.grid(), self.topology);
+        }
+        if self.stats.is_empty() {
+            self.stats
+                .push_back(Stats::of(self.generation, self.grid.grid()));
+        }
         self.grid.next_turn();
+        self.generation += 1;
 
         let width = self.grid.grid().width();
         let height = self.grid.grid().height();
 
+        let (mut births, mut deaths) = (0, 0);
         for x in 0..width {
             for y in 0..height {
-                let num_neighbours: u8 = {
-                    let prev = self.grid.old_grid();
-                    if self.wrapped {
-                        prev.neighbours_wrapped(x, y).sum()
-                    } else {
-                        prev.neighbours(x, y).sum()
-                    }
-                };
-                if self.grid.old_grid().get(x, y) == 1 {
-                    if num_neighbours < 2 || num_neighbours > 3 {
-                        self.grid.grid_mut().set(x, y, 0);
-                    } else {
-                        self.grid.grid_mut().set(x, y, 1);
-                    }
-                } else if num_neighbours == 3 {
-                    self.grid.grid_mut().set(x, y, 1);
-                } else {
-                    self.grid.grid_mut().set(x, y, 0);
+                let prev = self.grid.old_grid();
+                let alive = prev.get(x, y) != 0;
+                let neighbourhood = prev.neighbourhood(self.topology, x, y);
+                let next = self.rule.next_state(alive, neighbourhood);
+                match (alive, next) {
+                    (false, true) => births += 1,
+                    (true, false) => deaths += 1,
+                    _ => (),
                 }
+                self.grid.grid_mut().set(x, y, next as u8);
             }
         }
+        self.cycles
+            .record(self.generation, self.grid.grid(), self.topology);
+        if self.stats.len() == STATS_LIMIT {
+            self.stats.pop_front();
+        }
+        self.stats.push_back(Stats {
+            births,
+            deaths,
+            ..Stats::of(self.generation, self.grid.grid())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use topology::Twist;
+
+    fn alive(gol: &Gol) -> Vec<(usize, usize)> {
+        let grid = gol.grid();
+        (0..grid.height())
+            .flat_map(|y| (0..grid.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| gol.is_alive(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn cells_off_the_board_are_dead_and_stay_dead() {
+        let mut gol = Gol::new(4, 3, Topology::Torus);
+        gol.set_alive(3, 2, true);
+        gol.set_alive(4, 0, true);
+        gol.set_alive(0, 3, true);
+        assert_eq!(alive(&gol), [(3, 2)]);
+        assert!(!gol.is_alive(4, 0));
+        assert!(!gol.is_alive(usize::MAX, 0));
+        gol.set_alive(3, 2, false);
+        assert_eq!(gol.population(), 0);
+    }
+
+    #[test]
+    fn a_pattern_restarts_the_board_in_the_middle() {
+        let mut gol = Gol::new(7, 6, Topology::KleinBottle(Twist::TopBottom));
+        gol.set_alive(0, 0, true);
+        gol.next_turn();
+        let blinker = Pattern::from_rle("x = 3, y = 1\n3o!").unwrap();
+        gol.restart_with(&blinker);
+        assert_eq!(alive(&gol), [(2, 2), (3, 2), (4, 2)]);
+        assert_eq!(gol.generation(), 0);
+        assert_eq!((gol.grid().width(), gol.grid().height()), (7, 6));
+        assert_eq!(gol.topology(), Topology::KleinBottle(Twist::TopBottom));
+    }
+
+    #[test]
+    fn a_restart_runs_the_patterns_rule_or_keeps_the_last() {
+        let mut gol = Gol::new(5, 5, Topology::Torus);
+        let highlife: Rule = "B36/S23".parse().unwrap();
+        gol.set_rule(highlife.clone());
+        gol.restart_with(&Pattern::from_rle("x = 1, y = 1\no!").unwrap());
+        assert_eq!(gol.rule(), &highlife);
+        let seeds = Pattern::from_rle("x = 1, y = 1, rule = B2/S\no!").unwrap();
+        gol.restart_with(&seeds);
+        assert_eq!(gol.rule().to_string(), "B2/S");
+    }
+
+    #[test]
+    fn cells_that_dont_fit_are_left_out() {
+        let mut gol = Gol::new(3, 2, Topology::Plane);
+        let line = Pattern::from_rle("x = 5, y = 3\n5o$5o$5o!").unwrap();
+        gol.restart_with(&line);
+        assert_eq!(alive(&gol).len(), 6);
     }
 }
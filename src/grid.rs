@@ -1,13 +1,36 @@
+//! Rectangular grids of cells, and pairs of them for running generations.
+
 use std::mem;
+use topology::Topology;
 
-#[derive(Clone, Debug)]
+/// A `width` by `height` rectangle of cells, stored row by row. Coordinates start at the
+/// top left corner.
+#[derive(Debug, PartialEq, Hash)]
 pub struct Grid<T> {
     width: usize,
     height: usize,
     data: Vec<T>,
 }
 
-static NEIGHBOUR_POSITIONS: [(isize, isize); 8] = [
+impl<T: Clone> Clone for Grid<T> {
+    fn clone(&self) -> Grid<T> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            data: self.data.clone(),
+        }
+    }
+
+    // reuses the cells already allocated, for grids copied over and over
+    fn clone_from(&mut self, source: &Grid<T>) {
+        self.width = source.width;
+        self.height = source.height;
+        self.data.clone_from(&source.data);
+    }
+}
+
+/// The offsets of a cell's eight neighbours, in the order of the bits of a neighbourhood.
+pub static NEIGHBOUR_POSITIONS: [(isize, isize); 8] = [
     (-1, -1),
     (-1, 0),
     (-1, 1),
@@ -19,6 +42,7 @@ static NEIGHBOUR_POSITIONS: [(isize, isize); 8] = [
 ];
 
 impl<T> Grid<T> {
+    /// A grid with every cell set to the default value.
     pub fn new(width: usize, height: usize) -> Grid<T>
     where
         T: Default + Clone,
@@ -31,6 +55,11 @@ impl<T> Grid<T> {
         }
     }
 
+    /// A grid of the given cells, row by row.
+    ///
+    /// # Panics
+    ///
+    /// If there aren't exactly `width * height` cells.
     pub fn with_data(width: usize, height: usize, data: Vec<T>) -> Grid<T> {
         assert!(
             data.len() == width * height,
@@ -46,6 +75,7 @@ impl<T> Grid<T> {
         }
     }
 
+    /// The value of a cell, which must be on the grid.
     pub fn get(&self, x: usize, y: usize) -> T
     where
         T: Copy,
@@ -55,30 +85,36 @@ impl<T> Grid<T> {
         self.data[y * self.width + x]
     }
 
+    /// A reference to a cell, which must be on the grid.
     pub fn get_ref(&self, x: usize, y: usize) -> &T {
         debug_assert!(x < self.width, "w = {}, x = {}", self.width, x);
         debug_assert!(y < self.height, "h = {}, y = {}", self.height, y);
         &self.data[y * self.width + x]
     }
 
+    /// A mutable reference to a cell, which must be on the grid.
     pub fn get_mut(&mut self, x: usize, y: usize) -> &mut T {
         debug_assert!(x < self.width, "w = {}, x = {}", self.width, x);
         debug_assert!(y < self.height, "h = {}, y = {}", self.height, y);
         &mut self.data[y * self.width + x]
     }
 
+    /// The number of columns.
     pub fn width(&self) -> usize {
         self.width
     }
 
+    /// The number of rows.
     pub fn height(&self) -> usize {
         self.height
     }
 
+    /// Changes a cell, which must be on the grid.
     pub fn set(&mut self, x: usize, y: usize, value: T) {
         *self.get_mut(x, y) = value;
     }
 
+    /// Changes every cell.
     pub fn set_all(&mut self, value: T)
     where
         T: Clone,
@@ -88,37 +124,72 @@ impl<T> Grid<T> {
         }
     }
 
+    /// A copy of the `width` by `height` rectangle with its top left corner at `(x, y)`.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let data = (y..y + height)
+            .flat_map(|j| (x..x + width).map(move |i| (i, j)))
+            .map(|(i, j)| self.get_ref(i, j).clone())
+            .collect();
+        Grid::with_data(width, height, data)
+    }
+
+    /// The values of a cell's neighbours, leaving out those beyond the edges.
     pub fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = T> + '_
     where
         T: Copy,
     {
-        let (x, y) = (x as isize, y as isize);
-        let (w, h) = (self.width as isize, self.height as isize);
-        NEIGHBOUR_POSITIONS.iter().flat_map(move |(dx, dy)| {
-            let new_x = x + dx;
-            let new_y = y + dy;
-            if new_x >= 0 && new_y >= 0 && new_x < w && new_y < h {
-                Some(self.get(new_x as usize, new_y as usize))
-            } else {
-                None
-            }
-        })
+        self.neighbours_on(Topology::Plane, x, y)
     }
 
+    /// The values of a cell's neighbours, with the edges wrapping round.
     pub fn neighbours_wrapped(&self, x: usize, y: usize) -> impl Iterator<Item = T> + '_
+    where
+        T: Copy,
+    {
+        self.neighbours_on(Topology::Torus, x, y)
+    }
+
+    /// The values of a cell's neighbours, with the edges joined as `topology` says.
+    pub fn neighbours_on(
+        &self,
+        topology: Topology,
+        x: usize,
+        y: usize,
+    ) -> impl Iterator<Item = T> + '_
     where
         T: Copy,
     {
         let (x, y) = (x as isize, y as isize);
-        let (w, h) = (self.width as isize, self.height as isize);
         NEIGHBOUR_POSITIONS.iter().flat_map(move |(dx, dy)| {
-            let new_x = (x + dx + w) % w;
-            let new_y = (y + dy + h) % h;
-            Some(self.get(new_x as usize, new_y as usize))
+            topology
+                .wrap(x + dx, y + dy, self.width, self.height)
+                .map(|(new_x, new_y)| self.get(new_x, new_y))
         })
     }
 }
 
+impl Grid<u8> {
+    /// The live neighbours of a cell as a bitmask, with bit `i` set if the neighbour at
+    /// `NEIGHBOUR_POSITIONS[i]` is alive. Neighbours beyond an unjoined edge are dead.
+    pub fn neighbourhood(&self, topology: Topology, x: usize, y: usize) -> u8 {
+        let (x, y) = (x as isize, y as isize);
+        NEIGHBOUR_POSITIONS
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (i, (dx, dy))| {
+                match topology.wrap(x + dx, y + dy, self.width, self.height) {
+                    Some((x, y)) if self.get(x, y) != 0 => mask | 1 << i,
+                    _ => mask,
+                }
+            })
+    }
+}
+
+/// A grid and the one it replaced, so that the next generation can be worked out from
+/// the last without allocating.
 #[derive(Debug)]
 pub struct Game<T> {
     old_grid: Grid<T>,
@@ -138,24 +209,30 @@ where
 }
 
 impl<T: Default + Clone> Game<T> {
+    /// A game of two grids with every cell set to the default value.
     pub fn new(width: usize, height: usize) -> Game<T> {
         let grid = Grid::new(width, height);
         let old_grid = Grid::new(width, height);
         Game { old_grid, grid }
     }
 
+    /// The current generation.
     pub fn grid(&self) -> &Grid<T> {
         &self.grid
     }
 
+    /// The current generation, for changing.
     pub fn grid_mut(&mut self) -> &mut Grid<T> {
         &mut self.grid
     }
 
+    /// The previous generation.
     pub fn old_grid(&self) -> &Grid<T> {
         &self.old_grid
     }
 
+    /// Makes the current generation the previous one. The new current grid still holds
+    /// the generation before that, and has to be overwritten.
     pub fn next_turn(&mut self) {
         mem::swap(&mut self.grid, &mut self.old_grid);
     }
@@ -0,0 +1,124 @@
+//! Running the game without the terminal, printing what happened.
+
+use crate::Error;
+use census::Census;
+use editor::Editor;
+use export::animation::Recorder;
+use export::{self, picture};
+use game::Gol;
+use options::Options;
+use pattern::Pattern;
+use script;
+use session::Session;
+use setup::{game_from_session, new_game};
+use soup::Soup;
+use stats;
+
+/// The width and height of the board when the options don't give them, unless the
+/// pattern needs more room.
+pub const DEFAULT_SIZE: usize = 100;
+
+/// Space left around a loaded pattern when the board is sized to fit it.
+pub const MARGIN: usize = 20;
+
+/// Runs `gol` for up to `generations` generations, stopping early once it settles into a
+/// cycle for good if `stop_when_stable` is set, and then takes a census if `census` is set. Each
+/// generation is given to `recorder`, if there is one.
+pub fn run(
+    gol: &mut Gol,
+    generations: u64,
+    stop_when_stable: bool,
+    census: bool,
+    mut recorder: Option<&mut Recorder>,
+) {
+    println!("gen {}: population {}", gol.generation(), gol.population());
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.record(gol.grid());
+    }
+    let mut last_cycle = None;
+    for _ in 0..generations {
+        gol.next_turn();
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(gol.grid());
+        }
+        let cycle = gol.cycle();
+        if cycle != last_cycle {
+            if let Some(cycle) = cycle {
+                print!(
+                    "gen {}: cycle found, period {}, starting at gen {}",
+                    gol.generation(),
+                    cycle.period,
+                    cycle.start
+                );
+                match cycle.velocity() {
+                    Some(velocity) => println!(
+                        ", displacement ({}, {}), velocity {}",
+                        cycle.dx, cycle.dy, velocity
+                    ),
+                    None => println!(),
+                }
+                if stop_when_stable && cycle.is_settled(gol.topology()) {
+                    break;
+                }
+            }
+        }
+        last_cycle = cycle;
+    }
+    println!("gen {}: population {}", gol.generation(), gol.population());
+    if census {
+        println!("{}", Census::new(gol.grid(), gol.topology(), gol.rule()));
+    }
+}
+
+/// Runs the game the options set up without the terminal: the script if there is one, or
+/// else the generations asked for, saving whatever the options ask for afterwards.
+pub fn start(options: &Options, pattern: Option<&Pattern>) -> Result<(), Error> {
+    if let Some(path) = &options.stats {
+        // find out about a bad file name before the run rather than after
+        stats::Format::from_path(path)?;
+    }
+    if let Some(path) = &options.snapshot {
+        picture::Format::from_path(path)?;
+    }
+    let mut gol = if options.resume {
+        game_from_session(&Session::load(&options.session)?)
+    } else {
+        // big enough for the pattern, with room to spare
+        let size = |n| DEFAULT_SIZE.max(n + 2 * MARGIN);
+        let width = size(pattern.map_or(0, |p| p.width));
+        let height = size(pattern.map_or(0, |p| p.height));
+        new_game(options, width, height, pattern)
+    };
+    // a script runs its own generations, so the options have made sure there is no GIF
+    let mut recorder = match &options.gif {
+        Some(path) => Some(export::recorder_for(path, gol.grid(), options)?),
+        None => None,
+    };
+    match &options.script {
+        Some(path) => {
+            let print = |line: &str| println!("{}", line);
+            let seed = Soup::random_seed();
+            script::run(path, &mut gol, &mut Editor::new(), None, seed, print)?;
+            if options.census {
+                println!("{}", Census::new(gol.grid(), gol.topology(), gol.rule()));
+            }
+        }
+        None => run(
+            &mut gol,
+            options.generations,
+            options.stop_when_stable,
+            options.census,
+            recorder.as_mut(),
+        ),
+    }
+    if let Some(path) = &options.stats {
+        stats::save(path, gol.stats())?;
+    }
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(path) = &options.snapshot {
+        export::save_snapshot(path, &gol, None, options)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! The Game of Life engine: boards, rules, topologies, pattern files and the editor. It
+//! doesn't need a terminal or an operating system, so it can be built for WebAssembly.
+//!
+//! ```
+//! use gol::{Gol, Pattern, Topology};
+//!
+//! let glider = Pattern::from_rle("x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+//! let mut gol = Gol::new(20, 20, Topology::Torus);
+//! gol.init(&glider.cells);
+//! for _ in 0..8 {
+//!     gol.next_turn();
+//! }
+//! assert_eq!(gol.population(), 5);
+//! assert_eq!(gol.cycle().and_then(|cycle| cycle.velocity()), Some("c/4 diagonal".into()));
+//! ```
+
+#![warn(missing_docs)]
+
+extern crate serde_json;
+#[cfg(target_arch = "wasm32")]
+extern crate wasm_bindgen;
+
+pub mod census;
+pub mod cycle;
+pub mod editor;
+pub mod game;
+pub mod grid;
+pub mod pattern;
+pub mod presets;
+pub mod rule;
+pub mod shapes;
+pub mod soup;
+pub mod stats;
+pub mod topology;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub use editor::{EditAction, Editor};
+pub use game::Gol;
+pub use grid::{Game, Grid};
+pub use pattern::Pattern;
+pub use rule::Rule;
+pub use topology::{GridSpec, Topology};
@@ -0,0 +1,223 @@
+//! The pattern library, and the menu for searching it and choosing a pattern to stamp.
+
+use pattern::Pattern;
+use presets;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// What a key does while browsing the library.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BrowseAction {
+    /// Adds a character to the search.
+    Type(char),
+    Backspace,
+    /// Selects the next pattern down the list of matches.
+    Next,
+    Previous,
+    /// Picks up the selected pattern, to be stamped in Edit mode.
+    Choose,
+    /// Goes back to Edit mode without a pattern.
+    Cancel,
+}
+
+/// A collection of patterns: the built-in presets plus any loaded from directories.
+pub struct Library {
+    patterns: Vec<Pattern>,
+    skipped: usize,
+}
+
+impl Library {
+    /// A library of just the built-in presets.
+    pub fn new() -> Library {
+        Library {
+            patterns: presets::builtin(),
+            skipped: 0,
+        }
+    }
+
+    /// Adds every pattern file in `dir`. Files that can't be loaded are skipped.
+    pub fn add_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        for path in paths {
+            match Pattern::load(&path) {
+                Ok(pattern) => self.patterns.push(pattern),
+                Err(_) => self.skipped += 1,
+            }
+        }
+        Ok(())
+    }
+
+    /// The presets, followed by the patterns from each directory in the order they were
+    /// added, sorted by file name within a directory.
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// The number of files that couldn't be loaded.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+/// Searchable menu state for choosing a pattern from the library.
+pub struct Browser {
+    library: Library,
+    query: String,
+    selected: usize,
+}
+
+impl Browser {
+    /// A browser with an empty search, which matches every pattern.
+    pub fn new(library: Library) -> Browser {
+        Browser {
+            library,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// The library being browsed.
+    pub fn library(&self) -> &Library {
+        &self.library
+    }
+
+    /// What has been typed to search for.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The patterns whose name contains the query, ignoring case.
+    pub fn matches(&self) -> Vec<&Pattern> {
+        let query = self.query.to_lowercase();
+        self.library
+            .patterns()
+            .iter()
+            .filter(|pattern| pattern.name.to_lowercase().contains(&query))
+            .collect()
+    }
+
+    /// Index of the selected pattern within `matches()`.
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    /// The selected pattern, unless nothing matches the search.
+    pub fn selected(&self) -> Option<&Pattern> {
+        self.matches().get(self.selected).cloned()
+    }
+
+    /// Changes the search or the selection. Typing starts again from the top of the list,
+    /// and choosing or cancelling is left to the caller.
+    pub fn apply_action(&mut self, action: BrowseAction) {
+        match action {
+            BrowseAction::Type(c) => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            BrowseAction::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            BrowseAction::Next => {
+                if self.selected + 1 < self.matches().len() {
+                    self.selected += 1;
+                }
+            }
+            BrowseAction::Previous => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            BrowseAction::Choose | BrowseAction::Cancel => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn typed(browser: &mut Browser, text: &str) {
+        for c in text.chars() {
+            browser.apply_action(BrowseAction::Type(c));
+        }
+    }
+
+    fn names(browser: &Browser) -> Vec<&str> {
+        browser
+            .matches()
+            .iter()
+            .map(|pattern| pattern.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn the_search_matches_names_ignoring_case() {
+        let mut browser = Browser::new(Library::new());
+        assert_eq!(browser.matches().len(), browser.library().patterns().len());
+        typed(&mut browser, "WEIGHT");
+        assert_eq!(
+            names(&browser),
+            [
+                "Lightweight spaceship",
+                "Middleweight spaceship",
+                "Heavyweight spaceship"
+            ]
+        );
+        typed(&mut browser, "z");
+        assert!(browser.matches().is_empty());
+        assert!(browser.selected().is_none());
+        browser.apply_action(BrowseAction::Backspace);
+        assert_eq!(browser.query(), "WEIGHT");
+        assert_eq!(names(&browser).len(), 3);
+    }
+
+    #[test]
+    fn the_selection_stays_within_the_matches() {
+        let mut browser = Browser::new(Library::new());
+        typed(&mut browser, "weight");
+        browser.apply_action(BrowseAction::Previous);
+        assert_eq!(browser.selected_index(), 0);
+        for _ in 0..5 {
+            browser.apply_action(BrowseAction::Next);
+        }
+        assert_eq!(browser.selected_index(), 2);
+        assert_eq!(browser.selected().unwrap().name, "Heavyweight spaceship");
+        browser.apply_action(BrowseAction::Previous);
+        assert_eq!(browser.selected().unwrap().name, "Middleweight spaceship");
+        // choosing and cancelling are for the game to act on
+        browser.apply_action(BrowseAction::Choose);
+        browser.apply_action(BrowseAction::Cancel);
+        assert_eq!(browser.selected().unwrap().name, "Middleweight spaceship");
+        // typing starts again from the top
+        browser.apply_action(BrowseAction::Backspace);
+        assert_eq!(browser.selected_index(), 0);
+    }
+
+    #[test]
+    fn patterns_are_added_from_a_directory_in_name_order() {
+        let dir = env::temp_dir().join(format!("gol-library-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("b.rle"), "#N Second\nx = 1, y = 1\no!\n").unwrap();
+        fs::write(dir.join("a.rle"), "#N First\nx = 2, y = 1\n2o!\n").unwrap();
+        fs::write(dir.join("c.md"), "not a pattern").unwrap();
+
+        let mut library = Library::new();
+        let presets = library.patterns().len();
+        library.add_dir(&dir).unwrap();
+        let added: Vec<&str> = library.patterns()[presets..]
+            .iter()
+            .map(|pattern| pattern.name.as_str())
+            .collect();
+        assert_eq!(added, ["First", "Second"]);
+        assert_eq!(library.skipped(), 1);
+        assert!(library.add_dir(&dir.join("missing")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,22 +1,63 @@
-#![feature(drain_filter)]
-
+extern crate crossterm;
+extern crate gif;
+extern crate gol;
+extern crate libc;
+extern crate png;
+extern crate rhai;
 extern crate rustty;
+extern crate serde_json;
+extern crate tungstenite;
 
-use std::sync::mpsc::{channel, Receiver, SendError};
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 mod backend;
-mod editor;
-mod game;
-mod grid;
-mod presets;
+mod commands;
+mod console;
+mod export;
+mod headless;
+mod library;
+mod options;
+mod recovery;
+mod remote;
 mod runner;
+mod script;
+mod session;
+mod setup;
+mod status;
+
+// the engine is in the library, so that it can be built without a terminal
+use gol::{census, editor, game, grid, pattern, presets, rule, shapes, soup, stats, topology};
 
-use backend::terminal::Term;
+use backend::cross_term::CrossTerm;
+use backend::replay::{self, External, Record, Recording, Replay};
+use backend::terminal::{self, Term};
+use backend::web::Web;
 use backend::GameBackend;
+use census::Census;
+use commands::{
+    advance, answer_request, follow_screen, resize_board, run_command, screen_board_size, Board,
+};
+use console::{Command, Console, ConsoleAction};
 use editor::{EditAction, Editor};
+use export::animation;
+use export::picture;
 use game::Gol;
+use library::{BrowseAction, Browser, Library};
+use options::Options;
+use pattern::Pattern;
+use recovery::Autosave;
+use remote::{Remote, Request};
 use runner::Runner;
+use session::Session;
+use setup::{capture_session, new_game, restore_session, session_of};
+use status::{draw_current_state, Status};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum AppAction {
@@ -24,6 +65,16 @@ pub enum AppAction {
     TogglePause,
     EditMode,
     EditDone,
+    Browse,
+    SaveSession,
+    LoadSession,
+    Faster,
+    Slower,
+    Census,
+    ExportStats,
+    ToggleRecording,
+    Snapshot,
+    Console,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -31,6 +82,9 @@ pub enum GameState {
     Running,
     Paused,
     Editing,
+    Browsing,
+    /// Typing a command, with the game paused.
+    Console,
 }
 
 impl GameState {
@@ -45,126 +99,568 @@ impl GameState {
 
 #[derive(Debug)]
 enum Error {
-    Runner(runner::Error),
-    SendEditAction(SendError<editor::EditAction>),
+    Io(std::io::Error),
+    Animation(animation::Error),
+    Options(options::Error),
+    Pattern(pattern::Error),
+    Picture(picture::Error),
+    Replay(replay::Error),
+    Script(script::Error),
+    Session(session::Error),
+    Stats(stats::Error),
     UI(backend::Error),
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Animation(err) => write!(f, "{}", err),
+            Error::Options(err) => write!(f, "{}", err),
+            Error::Pattern(err) => write!(f, "{}", err),
+            Error::Picture(err) => write!(f, "{}", err),
+            Error::Replay(err) => write!(f, "{}", err),
+            Error::Script(err) => write!(f, "{}", err),
+            Error::Session(err) => write!(f, "{}", err),
+            Error::Stats(err) => write!(f, "{}", err),
+            Error::UI(err) => write!(f, "{}", err),
+        }
+    }
+}
+
 impl From<backend::Error> for Error {
     fn from(other: backend::Error) -> Error {
         Error::UI(other)
     }
 }
 
-impl From<runner::Error> for Error {
-    fn from(other: runner::Error) -> Error {
-        Error::Runner(other)
+impl From<std::io::Error> for Error {
+    fn from(other: std::io::Error) -> Error {
+        Error::Io(other)
+    }
+}
+
+impl From<animation::Error> for Error {
+    fn from(other: animation::Error) -> Error {
+        Error::Animation(other)
+    }
+}
+
+impl From<options::Error> for Error {
+    fn from(other: options::Error) -> Error {
+        Error::Options(other)
+    }
+}
+
+impl From<pattern::Error> for Error {
+    fn from(other: pattern::Error) -> Error {
+        Error::Pattern(other)
+    }
+}
+
+impl From<picture::Error> for Error {
+    fn from(other: picture::Error) -> Error {
+        Error::Picture(other)
+    }
+}
+
+impl From<replay::Error> for Error {
+    fn from(other: replay::Error) -> Error {
+        Error::Replay(other)
     }
 }
 
-impl From<SendError<editor::EditAction>> for Error {
-    fn from(other: SendError<editor::EditAction>) -> Error {
-        Error::SendEditAction(other)
+impl From<script::Error> for Error {
+    fn from(other: script::Error) -> Error {
+        Error::Script(other)
     }
 }
 
-fn main() -> Result<(), Error> {
+impl From<session::Error> for Error {
+    fn from(other: session::Error) -> Error {
+        Error::Session(other)
+    }
+}
+
+impl From<stats::Error> for Error {
+    fn from(other: stats::Error) -> Error {
+        Error::Stats(other)
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let options = Options::from_args(env::args().skip(1))?;
+    let pattern = match &options.load {
+        Some(path) => Some(Pattern::load(path)?),
+        None => None,
+    };
+    // a script runs without the terminal too
+    if options.headless || options.script.is_some() {
+        return headless::start(&options, pattern.as_ref());
+    }
+    let mut library = Library::new();
+    for dir in &options.pattern_dirs {
+        library.add_dir(dir)?;
+    }
+    let mut browser = Browser::new(library);
+    let mut console = Console::new();
     let mut state = GameState::Paused;
-    // TODO: handle resizing the terminal window
-    let mut ui = Term::new();
-    let game = Arc::new(Mutex::new(Gol::new(ui.num_cols(), ui.num_rows(), true)));
+    // the state to go back to when the console is closed
+    let mut resume_state = state;
+    let mut autosave = Autosave::new(options.session.with_extension("recovery"), options.autosave);
+    let mut message = None;
+    let replay = match &options.replay {
+        Some(path) => Some(Recording::load(path)?),
+        None => None,
+    };
+    // a replay starts from the board the recording did
+    let resumed = if let Some(recording) = &replay {
+        let path = options.replay.as_deref().unwrap_or(Path::new(""));
+        message = Some(format!("Replaying {}", path.display()));
+        Some(recording.session.clone())
+    } else if options.resume {
+        message = Some(format!("Resumed from {}", options.session.display()));
+        Some(Session::load(&options.session)?)
+    } else if recovery::offer_restore(autosave.path())? {
+        message = Some(format!("Restored from {}", autosave.path().display()));
+        Some(Session::load(autosave.path())?)
+    } else {
+        None
+    };
+    // before the terminal is taken over, so that a bad path is reported
+    let remote = match &options.control {
+        Some(path) => Some(Remote::new(path)?),
+        None => None,
+    };
+    let mut ui: Box<dyn GameBackend> = match options.backend {
+        backend::Kind::Rustty => Box::new(Term::new()?),
+        backend::Kind::Crossterm => {
+            Box::new(CrossTerm::new(options.style.alive, options.style.dead)?)
+        }
+        backend::Kind::Web => {
+            let web = Web::new(options.listen)?;
+            println!("Serving the game on http://{}/", options.listen);
+            Box::new(web)
+        }
+    };
+    // everything runs on this thread, but the panic hook saves the game if it crashes
+    let game = Arc::new(Mutex::new(Gol::new(0, 0, options.grid.topology)));
     let editor = Arc::new(Mutex::new(Editor::new()));
-    let (edit_actions, editor_recv) = channel();
-
-    let game_runner = run_game(Arc::clone(&game));
-    let editor_runner = run_editor(Arc::clone(&game), Arc::clone(&editor), editor_recv);
+    let delay = Arc::new(AtomicU64::new(0));
+    let recording = Arc::new(Mutex::new(None));
+    match resumed {
+        Some(session) => restore_session(session, &game, &editor, &*ui, &delay),
+        None => {
+            let (width, height) = screen_board_size(&*ui);
+            *game.lock().unwrap() = new_game(&options, width, height, pattern.as_ref());
+        }
+    }
+    let mut screen_size = screen_board_size(&*ui);
+    // the generation the runner is held at while a replay waits for its next action
+    let limit = Arc::new(AtomicU64::new(u64::MAX));
+    if let Some(replay) = replay {
+        let (game, gif) = (Arc::clone(&game), Arc::clone(&recording));
+        let catch_up = move |generation| {
+            let mut game = game.lock().unwrap();
+            while game.generation() < generation {
+                advance(&mut game, &gif);
+            }
+        };
+        ui = Box::new(Replay::new(replay, ui, Arc::clone(&limit), catch_up));
+    }
+    if let Some(path) = &options.record {
+        let session = capture_session(&game, &editor, &*ui, &delay);
+        ui = Box::new(Record::new(path, &session, ui, Arc::clone(&game))?);
+    }
+    install_panic_hook(
+        autosave.path().to_owned(),
+        Arc::clone(&game),
+        Arc::clone(&editor),
+        Arc::clone(&delay),
+    );
+    let mut runner = Runner::new();
+    let mut board = Board {
+        game: &game,
+        editor: &editor,
+        recording: &recording,
+        limit: &limit,
+        remote: remote.as_ref(),
+    };
+    let mut cycle = None;
+    // whether the key to load the session has been pressed once, and is waiting to be
+    // pressed again before the board is replaced
+    let mut confirming_load = false;
 
     loop {
         let mut new_state = state;
-        for action in ui.app_actions(state) {
-            new_state = match action {
-                AppAction::Quit => {
-                    editor_runner.finish()?;
-                    game_runner.finish()?;
-                    return Ok(());
+        // actions asked for at the console or by another program, rather than with their keys
+        let mut commanded = Vec::new();
+        if state == GameState::Browsing {
+            // every key is part of the search while browsing
+            for action in ui.browse_actions() {
+                match action {
+                    BrowseAction::Choose => {
+                        if let Some(pattern) = browser.selected() {
+                            runner.edit(EditAction::AddPattern(Box::new(pattern.clone())));
+                        }
+                        new_state = GameState::Editing;
+                    }
+                    BrowseAction::Cancel => new_state = GameState::Editing,
+                    action => browser.apply_action(action),
+                }
+            }
+        } else if state == GameState::Console {
+            // every key is part of the command line too
+            for action in ui.console_actions() {
+                match action {
+                    ConsoleAction::Run => {
+                        new_state = resume_state;
+                        let line = console.take_line();
+                        if !line.is_empty() {
+                            match line.parse() {
+                                Ok(Command::App(action)) => commanded.push(action),
+                                Ok(command) => {
+                                    message =
+                                        run_command(command, &game, &editor, &*ui, &mut runner)
+                                            .unwrap_or_else(Some);
+                                }
+                                Err(err) => message = Some(err.to_string()),
+                            }
+                        }
+                        break;
+                    }
+                    ConsoleAction::Backspace if console.line().is_empty() => {
+                        new_state = resume_state;
+                        break;
+                    }
+                    ConsoleAction::Cancel => {
+                        new_state = resume_state;
+                        break;
+                    }
+                    action => console.apply_action(action),
                 }
-                AppAction::EditDone => GameState::Paused,
-                AppAction::TogglePause => state.toggle_paused(),
-                AppAction::EditMode => GameState::Editing,
+            }
+        }
+        // the keys are read before anything else changes the game, so that a recording
+        // has everything in the order it happened
+        let keyed: Vec<AppAction> = match state {
+            GameState::Browsing | GameState::Console => Vec::new(),
+            _ => ui.app_actions(state).collect(),
+        };
+        let size = screen_board_size(&*ui);
+        if size != screen_size && ui.follows_screen() {
+            let mut game = game.lock().unwrap();
+            let board = (game.grid().width(), game.grid().height());
+            let (width, height) = follow_screen(screen_size, size, board);
+            if (width, height) != board {
+                ui.record(&External::Resize { width, height });
+                resize_board(&mut game, &editor, width, height);
+            }
+        }
+        screen_size = size;
+        for external in ui.replayed() {
+            match external {
+                External::Request(method, params) => answer_request(
+                    Request::replayed(method, params),
+                    state,
+                    &mut commanded,
+                    &game,
+                    &editor,
+                    &*ui,
+                    &mut runner,
+                ),
+                External::Resize { width, height } => {
+                    resize_board(&mut game.lock().unwrap(), &editor, width, height);
+                }
+            }
+        }
+        if let Some(remote) = &remote {
+            // an app action waits for the next frame, so that later requests see its state
+            let mut requests = remote.requests();
+            while commanded.is_empty() {
+                match requests.next() {
+                    Some(request) => {
+                        if let Some(external) = request.to_external() {
+                            ui.record(&external);
+                        }
+                        answer_request(
+                            request,
+                            state,
+                            &mut commanded,
+                            &game,
+                            &editor,
+                            &*ui,
+                            &mut runner,
+                        )
+                    }
+                    None => break,
+                }
+            }
+        }
+        // a stray key doesn't replace the board and its history, so the key to load the
+        // session has to be pressed twice in a row
+        if !keyed.is_empty() {
+            let presses = keyed
+                .iter()
+                .filter(|&&action| action == AppAction::LoadSession);
+            confirming_load = presses.count() == 1 && !confirming_load;
+            if confirming_load {
+                let path = options.session.display();
+                message = Some(format!("Press again to load {} over this board", path));
+            }
+        }
+        let keyed = keyed
+            .into_iter()
+            .filter(|&action| action != AppAction::LoadSession || !confirming_load);
+        let app_actions = keyed.chain(commanded);
+        {
+            // commands act on the state the console was opened from
+            let state = if state == GameState::Console {
+                resume_state
+            } else {
+                state
             };
+            for action in app_actions {
+                new_state = match action {
+                    AppAction::Quit => {
+                        if let Some(recorder) = recording.lock().unwrap().take() {
+                            recorder.finish()?;
+                        }
+                        autosave.remove()?;
+                        return Ok(());
+                    }
+                    AppAction::EditDone => GameState::Paused,
+                    // pausing stops a run of steps, rather than setting the game going
+                    AppAction::TogglePause if runner.steps_left() > 0 => {
+                        runner.cancel_steps();
+                        GameState::Paused
+                    }
+                    AppAction::TogglePause => state.toggle_paused(),
+                    AppAction::EditMode => GameState::Editing,
+                    AppAction::Browse => GameState::Browsing,
+                    AppAction::Console => {
+                        console.open();
+                        resume_state = state;
+                        GameState::Console
+                    }
+                    AppAction::SaveSession => {
+                        let path = &options.session;
+                        let session = capture_session(&game, &editor, &*ui, &delay);
+                        message = Some(match session.save(path) {
+                            Ok(()) => format!("Saved session to {}", path.display()),
+                            Err(err) => format!("Couldn't save session: {}", err),
+                        });
+                        state
+                    }
+                    AppAction::LoadSession => {
+                        let path = &options.session;
+                        message = Some(match Session::load(path) {
+                            Ok(session) => {
+                                restore_session(session, &game, &editor, &*ui, &delay);
+                                format!("Loaded session from {}", path.display())
+                            }
+                            Err(err) => format!("Couldn't load session: {}", err),
+                        });
+                        state
+                    }
+                    AppAction::Census => {
+                        let game = game.lock().unwrap();
+                        let census = Census::new(game.grid(), game.topology(), game.rule());
+                        message = Some(format!("Census: {}", census.summary()));
+                        state
+                    }
+                    AppAction::ExportStats => {
+                        let path = options.stats.as_deref().unwrap_or(Path::new(STATS_FILE));
+                        let game = game.lock().unwrap();
+                        message = Some(match stats::save(path, game.stats()) {
+                            Ok(()) => format!(
+                                "Exported {} generations of statistics to {}",
+                                game.stats().len(),
+                                path.display()
+                            ),
+                            Err(err) => format!("Couldn't export statistics: {}", err),
+                        });
+                        state
+                    }
+                    AppAction::ToggleRecording => {
+                        let path = options.gif.as_deref().unwrap_or(Path::new(GIF_FILE));
+                        // generations lock the game before the recording too
+                        let game = game.lock().unwrap();
+                        let mut recording = recording.lock().unwrap();
+                        message = Some(match recording.take() {
+                            Some(recorder) => match recorder.finish() {
+                                Ok(frames) => {
+                                    format!("Saved {} frames to {}", frames, path.display())
+                                }
+                                Err(err) => format!("Couldn't record: {}", err),
+                            },
+                            None => match export::recorder_for(path, game.grid(), &options) {
+                                Ok(mut recorder) => {
+                                    recorder.record(game.grid());
+                                    *recording = Some(recorder);
+                                    format!("Recording to {}", path.display())
+                                }
+                                Err(err) => format!("Couldn't record: {}", err),
+                            },
+                        });
+                        state
+                    }
+                    AppAction::Snapshot => {
+                        let path = options
+                            .snapshot
+                            .as_deref()
+                            .unwrap_or(Path::new(SNAPSHOT_FILE));
+                        let game = game.lock().unwrap();
+                        let selection = match state {
+                            GameState::Editing => editor.lock().unwrap().selection(),
+                            _ => None,
+                        };
+                        message = Some(
+                            match export::save_snapshot(path, &game, selection, &options) {
+                                Ok(()) => format!("Saved a picture to {}", path.display()),
+                                Err(err) => format!("Couldn't save a picture: {}", err),
+                            },
+                        );
+                        state
+                    }
+                    AppAction::Faster | AppAction::Slower => {
+                        let faster = action == AppAction::Faster;
+                        let new_delay = change_speed(delay.load(Ordering::Relaxed), faster);
+                        delay.store(new_delay, Ordering::Relaxed);
+                        state
+                    }
+                };
+            }
         }
         if state == GameState::Editing {
             for action in ui.edit_actions() {
-                edit_actions.send(action)?;
+                confirming_load = false;
+                runner.edit(action);
             }
         }
 
+        let last_cycle = cycle;
+        let topology = {
+            let game = game.lock().unwrap();
+            cycle = game.cycle();
+            game.topology()
+        };
+        let found = cycle.filter(|_| {
+            options.stop_when_stable && last_cycle.is_none() && state == GameState::Running
+        });
+        // a spaceship heading for an edge is only pointed out, as it'll change when it gets there
+        let stabilised = found.filter(|cycle| cycle.is_settled(topology));
+        if stabilised.is_some() {
+            new_state = GameState::Paused;
+        }
+
         if new_state != state {
-            match state {
-                GameState::Editing => {
-                    editor_runner.pause()?;
-                }
-                GameState::Running => {
-                    game_runner.pause()?;
-                }
-                _ => (),
+            // the console's reply is shown once it's closed
+            if state != GameState::Console {
+                message = None;
             }
             state = new_state;
-            match state {
-                GameState::Editing => {
-                    editor_runner.start()?;
-                }
-                GameState::Running => {
-                    game_runner.start()?;
-                }
-                _ => (),
+            runner.set_running(state == GameState::Running);
+        }
+
+        if let Some(cycle) = found {
+            message = Some(match stabilised {
+                Some(_) => format!("Paused, {}", cycle),
+                None => format!("Found a {}", cycle),
+            });
+        }
+
+        if autosave.is_due() {
+            let session = capture_session(&game, &editor, &*ui, &delay);
+            if let Err(err) = autosave.save(&session) {
+                message = Some(format!("Couldn't autosave: {}", err));
             }
         }
 
-        draw_current_state(state, &Arc::clone(&game), &Arc::clone(&editor), &mut ui)?;
-    }
-}
+        let status = Status {
+            state: if state == GameState::Console {
+                resume_state
+            } else {
+                state
+            },
+            prompt: if state == GameState::Console {
+                Some(console.prompt())
+            } else {
+                None
+            },
+            delay: delay.load(Ordering::Relaxed),
+            steps_left: runner.steps_left(),
+            recording: recording.lock().unwrap().is_some(),
+            message: message.as_deref(),
+        };
+        runner.set_delay(status.delay);
+        runner.run_frame(&mut board, Instant::now(), |board| {
+            draw_current_state(&status, board.game, board.editor, &browser, &mut *ui)
+        })?;
 
-fn run_editor(
-    game: Arc<Mutex<Gol>>,
-    editor: Arc<Mutex<Editor>>,
-    recv: Receiver<EditAction>,
-) -> Runner {
-    Runner::new(move || {
-        // await action before locking anything else
-        if let Ok(action) = recv.recv() {
-            let mut game = game.lock().unwrap();
-            let grid = game.grid_mut();
-            let mut editor = editor.lock().unwrap();
-            editor.apply_action(action, grid);
+        // generations are announced as they're run, and this catches a new board
+        if let Some(remote) = &remote {
+            remote.announce(&game.lock().unwrap());
         }
-    })
+    }
 }
 
-fn run_game(game: Arc<Mutex<Gol>>) -> Runner {
-    Runner::new(move || {
-        let mut game = game.lock().unwrap();
-        game.next_turn();
-    })
+/// Where `t` exports the statistics if the options don't say.
+const STATS_FILE: &str = "stats.csv";
+
+/// Where `G` records an animated GIF if the options don't say.
+const GIF_FILE: &str = "recording.gif";
+
+/// Where `P` saves a picture if the options don't say.
+const SNAPSHOT_FILE: &str = "snapshot.png";
+
+/// Milliseconds between generations for each speed, fastest first.
+static DELAYS: [u64; 8] = [0, 10, 25, 50, 100, 250, 500, 1000];
+
+fn change_speed(delay: u64, faster: bool) -> u64 {
+    let i = DELAYS
+        .iter()
+        .position(|&d| d >= delay)
+        .unwrap_or(DELAYS.len() - 1);
+    if faster {
+        DELAYS[i.saturating_sub(1)]
+    } else {
+        DELAYS[(i + 1).min(DELAYS.len() - 1)]
+    }
 }
 
-fn draw_current_state<Ui: GameBackend>(
-    state: GameState,
-    game: &Mutex<Gol>,
-    editor: &Mutex<Editor>,
-    ui: &mut Ui,
-) -> Result<(), Error> {
-    match state {
-        GameState::Running => {
-            let game = game.lock().unwrap();
-            ui.draw_game(game.grid())?;
-        }
-        GameState::Editing => {
-            let game = game.lock().unwrap();
-            let editor = editor.lock().unwrap();
-            ui.draw_editor(&editor, game.grid())?;
+/// Makes a panic on any thread save the game to the recovery file, put the terminal back
+/// to normal and exit. Otherwise the other threads would carry on with a broken game, and
+/// the message would be lost.
+fn install_panic_hook(
+    path: PathBuf,
+    game: Arc<Mutex<Gol>>,
+    editor: Arc<Mutex<Editor>>,
+    delay: Arc<AtomicU64>,
+) {
+    panic::set_hook(Box::new(move |info| {
+        // the panicking thread may be holding a lock, so don't wait for it
+        let saved = match (game.try_lock(), editor.try_lock()) {
+            (Ok(game), Ok(editor)) => {
+                let session = session_of(&game, &editor, (0, 0), delay.load(Ordering::Relaxed));
+                session.save(&path).is_ok()
+            }
+            _ => false,
+        };
+        let mut message = info.to_string();
+        if saved {
+            message += &format!("\nThe game was saved to {}", path.display());
+        } else if path.is_file() {
+            message += &format!("\nThe last autosave is in {}", path.display());
         }
-        _ => {}
-    }
-    Ok(())
+        terminal::restore_after_panic(&message);
+        process::exit(101);
+    }));
 }
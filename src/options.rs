@@ -0,0 +1,220 @@
+use backend;
+use export::{Colour, Style, MAX_CELL_SIZE};
+use rule::{self, Rule};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use topology::{self, GridSpec, Topology};
+
+#[derive(Debug)]
+pub struct Options {
+    pub grid: GridSpec,
+    pub rule: Rule,
+    pub pattern_dirs: Vec<PathBuf>,
+    /// Which terminal library draws the game.
+    pub backend: backend::Kind,
+    /// Where the web backend listens for browsers.
+    pub listen: SocketAddr,
+    /// A Unix domain socket where other programs can control the game.
+    pub control: Option<PathBuf>,
+    /// Where the session is saved and loaded.
+    pub session: PathBuf,
+    /// Whether to start from the saved session.
+    pub resume: bool,
+    /// How often to save to the recovery file, if at all.
+    pub autosave: Option<Duration>,
+    /// Where to record everything done in the game, to be played back later.
+    pub record: Option<PathBuf>,
+    /// A recording to play back, starting from the board it was made on.
+    pub replay: Option<PathBuf>,
+    /// A pattern to put in the middle of the board at the start.
+    pub load: Option<PathBuf>,
+    /// Pause, or stop when headless, once the board settles into a cycle.
+    pub stop_when_stable: bool,
+    /// Run without the terminal, printing the results.
+    pub headless: bool,
+    /// A Rhai script to run without the terminal, instead of a headless run.
+    pub script: Option<PathBuf>,
+    /// How many generations to run when headless.
+    pub generations: u64,
+    /// Print a census of the objects on the board when the headless run ends.
+    pub census: bool,
+    /// The seed of a random soup to put in the middle of the board at the start.
+    pub soup: Option<u64>,
+    /// Where to export the statistics of each generation, as CSV or JSON lines.
+    pub stats: Option<PathBuf>,
+    /// Where to record an animated GIF of the run.
+    pub gif: Option<PathBuf>,
+    /// Where to save a picture of the last generation of a headless run, as PNG or SVG.
+    pub snapshot: Option<PathBuf>,
+    /// Crop pictures to the live cells.
+    pub crop: bool,
+    /// How pictures of the board are drawn.
+    pub style: Style,
+    /// How long each generation is shown in an animated GIF.
+    pub frame_delay: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            grid: GridSpec::new(Topology::Torus),
+            rule: Rule::life(),
+            pattern_dirs: Vec::new(),
+            backend: backend::Kind::Rustty,
+            listen: ([127, 0, 0, 1], 8080).into(),
+            control: None,
+            session: "gol.session".into(),
+            resume: false,
+            autosave: Some(Duration::from_secs(60)),
+            record: None,
+            replay: None,
+            load: None,
+            stop_when_stable: false,
+            headless: false,
+            script: None,
+            generations: 1000,
+            census: false,
+            soup: None,
+            stats: None,
+            gif: None,
+            snapshot: None,
+            crop: false,
+            style: Style::default(),
+            frame_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    MissingValue(String),
+    UnknownArgument(String),
+    InvalidValue(String, String),
+    /// An option about running generations, which a script does for itself.
+    NotForScripts(String),
+    Topology(topology::Error),
+    Rule(rule::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::MissingValue(arg) => write!(f, "Missing value for {}", arg),
+            Error::UnknownArgument(arg) => write!(f, "Unknown argument: {}", arg),
+            Error::InvalidValue(arg, value) => write!(f, "Invalid value for {}: {}", arg, value),
+            Error::NotForScripts(arg) => {
+                write!(
+                    f,
+                    "{} can't be used with a script, which runs its own generations",
+                    arg
+                )
+            }
+            Error::Topology(err) => write!(f, "{}", err),
+            Error::Rule(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<topology::Error> for Error {
+    fn from(other: topology::Error) -> Error {
+        Error::Topology(other)
+    }
+}
+
+impl From<rule::Error> for Error {
+    fn from(other: rule::Error) -> Error {
+        Error::Rule(other)
+    }
+}
+
+fn parse_number(arg: &str, value: String) -> Result<u64, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidValue(arg.to_owned(), value))
+}
+
+fn parse_colour(arg: &str, value: String) -> Result<Colour, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidValue(arg.to_owned(), value))
+}
+
+impl Options {
+    pub fn from_args<I>(args: I) -> Result<Options, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        // the first option given that only makes sense without a script
+        let mut not_for_scripts = None;
+        while let Some(arg) = args.next() {
+            if ["-g", "--generations", "--stop-when-stable", "--gif"].contains(&arg.as_str()) {
+                not_for_scripts.get_or_insert_with(|| arg.clone());
+            }
+            let mut value = || args.next().ok_or_else(|| Error::MissingValue(arg.clone()));
+            match arg.as_str() {
+                "-t" | "--topology" => options.grid = value()?.parse()?,
+                "-r" | "--rule" => options.rule = value()?.parse()?,
+                "-p" | "--patterns" => options.pattern_dirs.push(value()?.into()),
+                "-b" | "--backend" => {
+                    let name = value()?;
+                    options.backend = name
+                        .parse()
+                        .map_err(|_| Error::InvalidValue(arg.clone(), name))?;
+                }
+                "--listen" => {
+                    let addr = value()?;
+                    options.listen = addr
+                        .parse()
+                        .map_err(|_| Error::InvalidValue(arg.clone(), addr))?;
+                }
+                "--control" => options.control = Some(value()?.into()),
+                "-s" | "--session" => options.session = value()?.into(),
+                "--resume" => options.resume = true,
+                "-a" | "--autosave" => {
+                    options.autosave = match parse_number(&arg, value()?)? {
+                        0 => None,
+                        seconds => Some(Duration::from_secs(seconds)),
+                    };
+                }
+                "--record" => options.record = Some(value()?.into()),
+                "--replay" => options.replay = Some(value()?.into()),
+                "-l" | "--load" => options.load = Some(value()?.into()),
+                "--stop-when-stable" => options.stop_when_stable = true,
+                "--headless" => options.headless = true,
+                // a command rather than an option, as in `gol script soups.rhai`
+                "script" => options.script = Some(value()?.into()),
+                "-g" | "--generations" => options.generations = parse_number(&arg, value()?)?,
+                "--census" => options.census = true,
+                "--soup" => options.soup = Some(parse_number(&arg, value()?)?),
+                "--stats" => options.stats = Some(value()?.into()),
+                "--gif" => options.gif = Some(value()?.into()),
+                "--snapshot" => options.snapshot = Some(value()?.into()),
+                "--crop" => options.crop = true,
+                "--cell-size" => {
+                    options.style.cell_size = match parse_number(&arg, value()?)? {
+                        size @ 1..=MAX_CELL_SIZE => size as usize,
+                        size => return Err(Error::InvalidValue(arg, size.to_string())),
+                    };
+                }
+                "--alive" => options.style.alive = parse_colour(&arg, value()?)?,
+                "--dead" => options.style.dead = parse_colour(&arg, value()?)?,
+                "--grid-lines" => options.style.grid_lines = Some(parse_colour(&arg, value()?)?),
+                "--frame-delay" => {
+                    options.frame_delay = Duration::from_millis(parse_number(&arg, value()?)?);
+                }
+                _ => return Err(Error::UnknownArgument(arg)),
+            }
+        }
+        if let (Some(arg), Some(_)) = (not_for_scripts, &options.script) {
+            return Err(Error::NotForScripts(arg));
+        }
+        Ok(options)
+    }
+}
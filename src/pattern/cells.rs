@@ -0,0 +1,43 @@
+use pattern::Pattern;
+use presets::get_preset_from;
+
+/// Parses the plaintext format: `!` lines are comments, `.` is a dead cell and any
+/// other character a live one.
+pub fn parse(data: &str) -> Pattern {
+    let mut name = String::new();
+    let mut comments = Vec::new();
+    let mut rows = Vec::new();
+    for line in data.lines() {
+        if let Some(comment) = line.strip_prefix('!') {
+            match comment.trim().strip_prefix("Name:") {
+                Some(value) => name = value.trim().to_owned(),
+                None => comments.push(comment.trim().to_owned()),
+            }
+        } else {
+            rows.push(line);
+        }
+    }
+    let cells = get_preset_from(rows.join("\n").as_bytes()).collect();
+    let mut pattern = Pattern::new(name, cells);
+    pattern.comments = comments;
+    pattern
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    if !pattern.name.is_empty() {
+        out += &format!("!Name: {}\n", pattern.name);
+    }
+    for comment in &pattern.comments {
+        out += &format!("!{}\n", comment);
+    }
+    let mut rows = vec![vec!['.'; pattern.width]; pattern.height];
+    for &(x, y) in &pattern.cells {
+        rows[y][x] = 'O';
+    }
+    for row in rows {
+        out.extend(row);
+        out.push('\n');
+    }
+    out
+}
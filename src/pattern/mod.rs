@@ -0,0 +1,223 @@
+//! Patterns, and reading and writing them as RLE or plaintext files.
+
+use grid::Grid;
+use presets::{self, Orientation};
+use rule::{self, Rule};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+use topology::{self, GridSpec};
+
+mod cells;
+mod rle;
+
+/// The most cells an RLE pattern can have along each side, so that a bad file can't ask
+/// for more memory than there is.
+pub const MAX_SIZE: usize = 10_000;
+
+/// Why a pattern couldn't be loaded or saved.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the file failed.
+    Io(io::Error),
+    /// The file isn't valid RLE.
+    Parse(String),
+    /// The rule in the file isn't valid.
+    Rule(rule::Error),
+    /// The bounded grid in the file isn't valid.
+    Topology(topology::Error),
+    /// The file extension isn't one of the formats.
+    UnknownFormat(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Rule(err) => Some(err),
+            Error::Topology(err) => Some(err),
+            Error::Parse(_) | Error::UnknownFormat(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Parse(msg) => write!(f, "Invalid pattern: {}", msg),
+            Error::Rule(err) => write!(f, "{}", err),
+            Error::Topology(err) => write!(f, "{}", err),
+            Error::UnknownFormat(ext) => write!(f, "Unknown pattern format: {:?}", ext),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Error {
+        Error::Io(other)
+    }
+}
+
+impl From<rule::Error> for Error {
+    fn from(other: rule::Error) -> Error {
+        Error::Rule(other)
+    }
+}
+
+impl From<topology::Error> for Error {
+    fn from(other: topology::Error) -> Error {
+        Error::Topology(other)
+    }
+}
+
+/// The extension of a file name in lower case, or an empty string if it hasn't got one, for
+/// choosing a format by it.
+pub fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// A pattern of live `cells`, relative to the top left corner of its `width` by `height`
+/// bounding box.
+#[derive(Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct Pattern {
+    /// The name from the file, or the file name if it didn't give one.
+    pub name: String,
+    /// The comment lines of the file, without the comment markers.
+    pub comments: Vec<String>,
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<(usize, usize)>,
+    /// The rule the pattern was designed for, if the file specified one.
+    pub rule: Option<Rule>,
+    /// The bounded grid the pattern was designed for, if the file specified one.
+    pub grid: Option<GridSpec>,
+}
+
+impl Pattern {
+    /// A pattern of the given cells, just big enough to hold them.
+    pub fn new(name: String, cells: Vec<(usize, usize)>) -> Pattern {
+        let width = cells.iter().map(|&(x, _)| x + 1).max().unwrap_or(0);
+        let height = cells.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+        Pattern {
+            name,
+            comments: Vec::new(),
+            width,
+            height,
+            cells,
+            rule: None,
+            grid: None,
+        }
+    }
+
+    /// The live cells of a whole grid, keeping the size of the grid.
+    pub fn from_grid(name: String, grid: &Grid<u8>) -> Pattern {
+        let mut cells = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if grid.get(x, y) != 0 {
+                    cells.push((x, y));
+                }
+            }
+        }
+        let mut pattern = Pattern::new(name, cells);
+        pattern.width = grid.width();
+        pattern.height = grid.height();
+        pattern
+    }
+
+    /// A grid the size of the pattern with its cells alive.
+    pub fn to_grid(&self) -> Grid<u8> {
+        let mut grid = Grid::new(self.width, self.height);
+        for &(x, y) in &self.cells {
+            grid.set(x, y, 1);
+        }
+        grid
+    }
+
+    /// Loads an RLE (`.rle`) or plaintext (`.cells`, `.txt`) pattern file. Patterns without
+    /// a name are named after the file.
+    pub fn load(path: &Path) -> Result<Pattern, Error> {
+        let data = fs::read_to_string(path)?;
+        let ext = extension(path);
+        let mut pattern = match ext.as_str() {
+            "rle" => Pattern::from_rle(&data)?,
+            "cells" | "txt" => Pattern::from_cells(&data),
+            _ => return Err(Error::UnknownFormat(ext)),
+        };
+        if pattern.name.is_empty() {
+            if let Some(stem) = path.file_stem() {
+                pattern.name = stem.to_string_lossy().into_owned();
+            }
+        }
+        Ok(pattern)
+    }
+
+    /// Saves the pattern as RLE or plaintext, depending on the extension of `path`.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let ext = extension(path);
+        let data = match ext.as_str() {
+            "rle" => self.to_rle(),
+            "cells" | "txt" => self.to_cells(),
+            _ => return Err(Error::UnknownFormat(ext)),
+        };
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Reads a pattern in RLE format, with its name, comments, rule and bounded grid.
+    pub fn from_rle(data: &str) -> Result<Pattern, Error> {
+        rle::parse(data)
+    }
+
+    /// Reads a pattern in plaintext format, where `.` is dead, anything else is alive and
+    /// lines starting with `!` are comments.
+    pub fn from_cells(data: &str) -> Pattern {
+        cells::parse(data)
+    }
+
+    /// The pattern in RLE format.
+    pub fn to_rle(&self) -> String {
+        rle::write(self)
+    }
+
+    /// The pattern in plaintext format.
+    pub fn to_cells(&self) -> String {
+        cells::write(self)
+    }
+
+    /// The cells moved to the middle of a `width` by `height` board, leaving out any that
+    /// don't fit.
+    pub fn centred_in(&self, width: usize, height: usize) -> Vec<(usize, usize)> {
+        let x = width.saturating_sub(self.width) / 2;
+        let y = height.saturating_sub(self.height) / 2;
+        self.cells
+            .iter()
+            .map(|&(i, j)| (x + i, y + j))
+            .filter(|&(i, j)| i < width && j < height)
+            .collect()
+    }
+
+    /// A copy of the pattern, rotated and/or mirrored.
+    pub fn transformed(&self, orientation: Orientation) -> Pattern {
+        let (width, height) = orientation.size(self.width, self.height);
+        let cells = presets::transform(
+            self.cells.iter().cloned(),
+            self.width,
+            self.height,
+            orientation,
+        );
+        Pattern {
+            width,
+            height,
+            cells: cells.collect(),
+            ..self.clone()
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use pattern::{Error, Pattern, MAX_SIZE};
+
+fn too_big() -> Error {
+    Error::Parse(format!("bigger than {} cells on a side", MAX_SIZE))
+}
+
+/// `at` moved on by `run` cells, as long as it's still within the biggest pattern.
+fn advance(at: usize, run: usize) -> Result<usize, Error> {
+    at.checked_add(run)
+        .filter(|&to| to <= MAX_SIZE)
+        .ok_or_else(too_big)
+}
+
+/// Parses the header line, e.g. `x = 3, y = 3, rule = B3/S23:T100,50`.
+fn parse_header(pattern: &mut Pattern, line: &str) -> Result<(), Error> {
+    // the rule goes last, and may itself contain commas
+    let (sizes, rule) = match line.find("rule") {
+        Some(i) => (&line[..i], Some(&line[i + "rule".len()..])),
+        None => (line, None),
+    };
+    for item in sizes
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let mut parts = item.splitn(2, '=').map(str::trim);
+        let key = parts.next().unwrap_or("");
+        let value: usize = parts
+            .next()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| Error::Parse(format!("bad header item {:?}", item)))?;
+        if value > MAX_SIZE {
+            return Err(too_big());
+        }
+        match key {
+            "x" => pattern.width = value,
+            "y" => pattern.height = value,
+            _ => {}
+        }
+    }
+    if let Some(rule) = rule {
+        let rule = rule.trim().trim_start_matches('=').trim();
+        let mut parts = rule.splitn(2, ':');
+        pattern.rule = Some(parts.next().unwrap_or("").parse()?);
+        if let Some(grid) = parts.next() {
+            pattern.grid = Some(grid.parse()?);
+        }
+    }
+    Ok(())
+}
+
+pub fn parse(data: &str) -> Result<Pattern, Error> {
+    let mut pattern = Pattern::new(String::new(), Vec::new());
+    let mut seen_header = false;
+    let mut body = String::new();
+    for line in data.lines().map(str::trim) {
+        if line.starts_with('#') {
+            let text = line.get(2..).unwrap_or("");
+            match line.get(..2).unwrap_or(line) {
+                "#N" => pattern.name = text.trim().to_owned(),
+                "#C" | "#c" | "#O" => pattern.comments.push(text.trim().to_owned()),
+                _ => {}
+            }
+        } else if !seen_header && line.starts_with('x') {
+            parse_header(&mut pattern, line)?;
+            seen_header = true;
+        } else {
+            body.push_str(line);
+            if line.contains('!') {
+                break;
+            }
+        }
+    }
+
+    let (mut x, mut y) = (0, 0);
+    let mut count = String::new();
+    for c in body.chars() {
+        if c.is_ascii_digit() {
+            count.push(c);
+            continue;
+        }
+        let run = if count.is_empty() {
+            1
+        } else {
+            count
+                .parse()
+                .map_err(|_| Error::Parse(format!("bad run length {:?}", count)))?
+        };
+        count.clear();
+        match c {
+            '!' => break,
+            '$' => {
+                x = 0;
+                y = advance(y, run)?;
+            }
+            'b' | '.' => x = advance(x, run)?,
+            c if c.is_ascii_alphabetic() => {
+                let end = advance(x, run)?;
+                if y >= MAX_SIZE {
+                    return Err(too_big());
+                }
+                pattern.cells.extend((x..end).map(|x| (x, y)));
+                x = end;
+            }
+            c if c.is_whitespace() => {}
+            c => return Err(Error::Parse(format!("unexpected {:?}", c))),
+        }
+    }
+    pattern.width = pattern
+        .cells
+        .iter()
+        .map(|&(x, _)| x + 1)
+        .fold(pattern.width, usize::max);
+    pattern.height = pattern
+        .cells
+        .iter()
+        .map(|&(_, y)| y + 1)
+        .fold(pattern.height, usize::max);
+    Ok(pattern)
+}
+
+/// Appends a run of `count` cells with the given tag, wrapping lines at 70 characters.
+fn push_run(out: &mut String, line_len: &mut usize, count: usize, tag: char) {
+    if count == 0 {
+        return;
+    }
+    let run = if count == 1 {
+        tag.to_string()
+    } else {
+        format!("{}{}", count, tag)
+    };
+    if *line_len + run.len() > 70 {
+        out.push('\n');
+        *line_len = 0;
+    }
+    *line_len += run.len();
+    out.push_str(&run);
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    if !pattern.name.is_empty() {
+        out += &format!("#N {}\n", pattern.name);
+    }
+    for comment in &pattern.comments {
+        out += &format!("#C {}\n", comment);
+    }
+    out += &format!("x = {}, y = {}", pattern.width, pattern.height);
+    if let Some(rule) = &pattern.rule {
+        out += &format!(", rule = {}", rule);
+        if let Some(grid) = &pattern.grid {
+            out += &format!(":{}", grid);
+        }
+    }
+    out.push('\n');
+
+    let mut rows = vec![Vec::new(); pattern.height];
+    for &(x, y) in &pattern.cells {
+        rows[y].push(x);
+    }
+    let mut line_len = 0;
+    let mut blank_rows = 0;
+    for (y, row) in rows.iter_mut().enumerate() {
+        row.sort();
+        row.dedup();
+        if row.is_empty() && y > 0 {
+            blank_rows += 1;
+            continue;
+        }
+        if y > 0 {
+            push_run(&mut out, &mut line_len, blank_rows + 1, '$');
+            blank_rows = 0;
+        }
+        let mut x = 0;
+        let mut i = 0;
+        while i < row.len() {
+            let start = row[i];
+            let mut end = start + 1;
+            while i + 1 < row.len() && row[i + 1] == end {
+                end += 1;
+                i += 1;
+            }
+            push_run(&mut out, &mut line_len, start - x, 'b');
+            push_run(&mut out, &mut line_len, end - start, 'o');
+            x = end;
+            i += 1;
+        }
+    }
+    out.push_str("!\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static GLIDER: &str = "#N Glider\n#C The smallest spaceship.\nx = 3, y = 3, rule = B3/S23\n\
+                           bob$2bo$3o!\n";
+
+    #[test]
+    fn a_pattern_is_read_with_its_name_comments_and_rule() {
+        let pattern = parse(GLIDER).unwrap();
+        assert_eq!(pattern.name, "Glider");
+        assert_eq!(pattern.comments, ["The smallest spaceship."]);
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.cells, [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        assert_eq!(
+            pattern.rule.map(|rule| rule.to_string()),
+            Some("B3/S23".to_owned())
+        );
+        assert_eq!(pattern.grid, None);
+    }
+
+    #[test]
+    fn runs_can_span_lines_and_skip_rows() {
+        let pattern = parse("x = 5, y = 4\n2o\n3b$\n2$5o!").unwrap();
+        let mut expected = vec![(0, 0), (1, 0)];
+        expected.extend((0..5).map(|x| (x, 3)));
+        assert_eq!(pattern.cells, expected);
+        assert_eq!((pattern.width, pattern.height), (5, 4));
+    }
+
+    #[test]
+    fn a_bounded_grid_is_read_after_the_rule() {
+        let pattern = parse("x = 2, y = 1, rule = B36/S23:T10,8\n2o!").unwrap();
+        assert_eq!(
+            pattern.grid.map(|grid| grid.to_string()),
+            Some("T10,8".to_owned())
+        );
+    }
+
+    #[test]
+    fn a_written_pattern_reads_back_the_same() {
+        let glider = parse(GLIDER).unwrap();
+        assert_eq!(parse(&write(&glider)).unwrap(), glider);
+        // long rows are wrapped, and blank rows and a gap at the end kept
+        let mut cells: Vec<_> = (0..200).filter(|x| x % 3 != 0).map(|x| (x, 0)).collect();
+        cells.push((0, 5));
+        let mut wide = Pattern::new("Wide".to_owned(), cells);
+        wide.height = 8;
+        let rle = write(&wide);
+        assert!(rle.lines().all(|line| line.len() <= 70));
+        assert_eq!(parse(&rle).unwrap(), wide);
+    }
+
+    #[test]
+    fn bad_characters_are_refused() {
+        assert!(matches!(parse("x = 1, y = 1\no%!"), Err(Error::Parse(_))));
+        assert!(matches!(parse("x = one, y = 1\no!"), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn patterns_too_big_to_hold_are_refused() {
+        for rle in &[
+            "999999999999o!",
+            "18446744073709551615b2b!",
+            "18446744073709551615$o!",
+            "99999999999999999999o!",
+            "10000bo!",
+            "10001o!",
+            "10000$o!",
+            "x = 1000000000000, y = 1\no!",
+            "x = 1, y = 10001\no!",
+        ] {
+            assert!(matches!(parse(rle), Err(Error::Parse(_))), "{:?}", rle);
+        }
+        let pattern = parse("9999bo!").unwrap();
+        assert_eq!(pattern.width, MAX_SIZE);
+    }
+}
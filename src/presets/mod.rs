@@ -1,22 +1,212 @@
-pub fn get_preset_from<'a>(data: &'a [u8]) -> impl Iterator<Item = (usize, usize)> + 'a {
-    data.splitn(usize::max_value(), |&c| c == b'\n')
+//! The built-in patterns, and turning patterns round.
+
+use pattern::Pattern;
+use std::mem;
+
+/// The live cells of a plaintext picture, where anything but a space or `.` is alive.
+pub(crate) fn get_preset_from<'a>(data: &'a [u8]) -> impl Iterator<Item = (usize, usize)> + 'a {
+    data.splitn(usize::MAX, |&c| c == b'\n')
         .zip(0..)
         .flat_map(|(line, y)| {
-            line.into_iter()
+            line.iter()
                 .zip(0..)
-                .filter(|(&c, _)| c != b' ')
+                .filter(|(&c, _)| c != b' ' && c != b'.')
                 .map(move |(_, x)| (x, y))
         })
 }
 
-pub fn get_preset(index: u8) -> impl Iterator<Item = (usize, usize)> {
-    let shape: &[u8] = match index {
-        1 => include_bytes!("glider_1.txt"),
-        2 => include_bytes!("glider_2.txt"),
-        3 => include_bytes!("glider_3.txt"),
-        4 => include_bytes!("glider_4.txt"),
-        0 => include_bytes!("glider_gun.txt"),
-        _ => b"",
-    };
-    get_preset_from(shape)
+/// Rotation and reflection of a pattern. The pattern is first mirrored left to right
+/// if `flipped` is set, then rotated clockwise by `rotation` quarter turns.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[allow(missing_docs)]
+pub struct Orientation {
+    pub rotation: u8,
+    pub flipped: bool,
+}
+
+impl Orientation {
+    /// This orientation followed by a quarter turn clockwise.
+    pub fn rotate_clockwise(self) -> Orientation {
+        Orientation {
+            rotation: (self.rotation + 1) % 4,
+            ..self
+        }
+    }
+
+    /// This orientation followed by a quarter turn anticlockwise.
+    pub fn rotate_anticlockwise(self) -> Orientation {
+        Orientation {
+            rotation: (self.rotation + 3) % 4,
+            ..self
+        }
+    }
+
+    /// Mirrors left to right.
+    pub fn flip_horizontal(self) -> Orientation {
+        Orientation {
+            rotation: (4 - self.rotation) % 4,
+            flipped: !self.flipped,
+        }
+    }
+
+    /// Mirrors top to bottom.
+    pub fn flip_vertical(self) -> Orientation {
+        Orientation {
+            rotation: (6 - self.rotation) % 4,
+            flipped: !self.flipped,
+        }
+    }
+
+    /// The size of a `width` x `height` pattern after it is transformed.
+    pub fn size(self, width: usize, height: usize) -> (usize, usize) {
+        match self.rotation {
+            1 | 3 => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// Transforms one cell of a `width` x `height` pattern.
+    pub fn apply(self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        let (mut x, mut y) = (x, y);
+        let (mut w, mut h) = (width, height);
+        if self.flipped {
+            x = w - 1 - x;
+        }
+        for _ in 0..self.rotation {
+            let (new_x, new_y) = (h - 1 - y, x);
+            x = new_x;
+            y = new_y;
+            mem::swap(&mut w, &mut h);
+        }
+        (x, y)
+    }
+}
+
+/// Transforms the cells of a `width` x `height` pattern, such as those from
+/// `get_preset_from`, keeping them within the pattern's (transformed) bounding box.
+pub(crate) fn transform<'a>(
+    cells: impl Iterator<Item = (usize, usize)> + 'a,
+    width: usize,
+    height: usize,
+    orientation: Orientation,
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    cells.map(move |(x, y)| orientation.apply(x, y, width, height))
+}
+
+fn from_text(name: &str, data: &[u8]) -> Pattern {
+    Pattern::new(name.to_owned(), get_preset_from(data).collect())
+}
+
+fn from_rle(data: &str) -> Pattern {
+    Pattern::from_rle(data).expect("built-in patterns are valid")
+}
+
+/// The patterns that are always available in the pattern library.
+pub fn builtin() -> Vec<Pattern> {
+    vec![
+        from_text("Glider", include_bytes!("glider.txt")),
+        from_text("Gosper glider gun", include_bytes!("glider_gun.txt")),
+        from_rle(include_str!("lwss.rle")),
+        from_rle(include_str!("mwss.rle")),
+        from_rle(include_str!("hwss.rle")),
+        from_rle(include_str!("block.rle")),
+        from_rle(include_str!("beehive.rle")),
+        from_rle(include_str!("loaf.rle")),
+        from_rle(include_str!("boat.rle")),
+        from_rle(include_str!("blinker.rle")),
+        from_rle(include_str!("toad.rle")),
+        from_rle(include_str!("beacon.rle")),
+        from_rle(include_str!("pulsar.rle")),
+        from_rle(include_str!("pentadecathlon.rle")),
+        from_rle(include_str!("r_pentomino.rle")),
+        from_rle(include_str!("acorn.rle")),
+        from_rle(include_str!("diehard.rle")),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every rotation, with and without a flip.
+    fn orientations() -> Vec<Orientation> {
+        (0..8)
+            .map(|i| Orientation {
+                rotation: i % 4,
+                flipped: i >= 4,
+            })
+            .collect()
+    }
+
+    fn sorted(mut cells: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn four_quarter_turns_and_two_flips_change_nothing() {
+        for orientation in orientations() {
+            let mut turned = orientation;
+            for _ in 0..4 {
+                turned = turned.rotate_clockwise();
+            }
+            assert_eq!(turned, orientation);
+            let back = orientation.rotate_clockwise().rotate_anticlockwise();
+            assert_eq!(back, orientation);
+            assert_eq!(orientation.flip_horizontal().flip_horizontal(), orientation);
+            assert_eq!(orientation.flip_vertical().flip_vertical(), orientation);
+        }
+    }
+
+    #[test]
+    fn a_quarter_turn_swaps_the_width_and_height() {
+        let turned = Orientation::default().rotate_clockwise();
+        assert_eq!(turned.size(3, 5), (5, 3));
+        assert_eq!(turned.rotate_clockwise().size(3, 5), (3, 5));
+        assert_eq!(Orientation::default().flip_horizontal().size(3, 5), (3, 5));
+        // a cell of a 3 x 2 pattern stays within the 2 x 3 box it's turned into
+        assert_eq!(turned.apply(2, 0, 3, 2), (1, 2));
+        assert_eq!(turned.apply(0, 1, 3, 2), (0, 0));
+    }
+
+    #[test]
+    fn flips_mirror_the_cells_after_the_turns_so_far() {
+        for orientation in orientations() {
+            let horizontal = orientation.flip_horizontal();
+            let vertical = orientation.flip_vertical();
+            for y in 0..2 {
+                for x in 0..3 {
+                    let (i, j) = orientation.apply(x, y, 3, 2);
+                    let (w, h) = orientation.size(3, 2);
+                    assert_eq!(horizontal.apply(x, y, 3, 2), (w - 1 - i, j));
+                    assert_eq!(vertical.apply(x, y, 3, 2), (i, h - 1 - j));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_glider_is_turned_and_flipped() {
+        let glider = from_text("Glider", include_bytes!("glider.txt"));
+        assert_eq!(
+            sorted(glider.cells.clone()),
+            [(0, 0), (1, 0), (1, 2), (2, 0), (2, 1)]
+        );
+        let turned = glider.transformed(Orientation::default().rotate_clockwise());
+        assert_eq!((turned.width, turned.height), (3, 3));
+        assert_eq!(
+            sorted(turned.cells),
+            [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]
+        );
+        let flipped = glider.transformed(Orientation::default().flip_horizontal());
+        assert_eq!(
+            sorted(flipped.cells),
+            [(0, 0), (0, 1), (1, 0), (1, 2), (2, 0)]
+        );
+        let upside_down = glider.transformed(Orientation::default().flip_vertical());
+        assert_eq!(
+            sorted(upside_down.cells),
+            [(0, 2), (1, 0), (1, 2), (2, 1), (2, 2)]
+        );
+    }
 }
@@ -0,0 +1,115 @@
+//! Autosaving to a recovery file, so that a crash doesn't lose the board.
+
+use session::{self, Session};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Saves the session to a recovery file every so often. The file is removed when the
+/// game exits cleanly, so if it's there at startup the last game must have crashed.
+pub struct Autosave {
+    path: PathBuf,
+    interval: Option<Duration>,
+    last_save: Instant,
+}
+
+impl Autosave {
+    /// An `interval` of `None` never saves.
+    pub fn new(path: PathBuf, interval: Option<Duration>) -> Autosave {
+        Autosave {
+            path,
+            interval,
+            last_save: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.interval
+            .is_some_and(|interval| self.last_save.elapsed() >= interval)
+    }
+
+    pub fn save(&mut self, session: &Session) -> Result<(), session::Error> {
+        self.last_save = Instant::now();
+        session.save(&self.path)
+    }
+
+    /// Removes the recovery file, if there is one.
+    pub fn remove(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Asks on the console whether to restore the recovery file at `path`, if there is one.
+pub fn offer_restore(path: &Path) -> io::Result<bool> {
+    if !path.is_file() {
+        return Ok(false);
+    }
+    print!(
+        "The last game didn't exit cleanly. Restore the autosave in {}? [Y/n] ",
+        path.display()
+    );
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(!answer.trim().to_lowercase().starts_with('n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grid::Grid;
+    use rule::Rule;
+    use std::env;
+    use std::thread;
+    use topology::Topology;
+
+    fn session() -> Session {
+        let mut grid = Grid::new(5, 4);
+        grid.set(1, 2, 1);
+        Session {
+            grid,
+            generation: 7,
+            rule: Rule::life(),
+            topology: Topology::Torus,
+            cursor: (1, 2),
+            viewport: (0, 0),
+            delay: 100,
+            undo: vec![Grid::new(5, 4)],
+            redo: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn saves_are_due_once_the_interval_has_passed() {
+        let path = PathBuf::from("unused.recovery");
+        assert!(!Autosave::new(path.clone(), None).is_due());
+        assert!(Autosave::new(path.clone(), Some(Duration::ZERO)).is_due());
+        let autosave = Autosave::new(path, Some(Duration::from_millis(20)));
+        assert!(!autosave.is_due());
+        thread::sleep(Duration::from_millis(30));
+        assert!(autosave.is_due());
+    }
+
+    #[test]
+    fn a_saved_session_is_loaded_back_and_removed() {
+        let path = env::temp_dir().join(format!("gol-recovery-{}", std::process::id()));
+        let mut autosave = Autosave::new(path.clone(), Some(Duration::from_millis(20)));
+        thread::sleep(Duration::from_millis(30));
+        autosave.save(&session()).unwrap();
+        assert!(!autosave.is_due());
+        assert_eq!(Session::load(&path).unwrap(), session());
+
+        autosave.remove().unwrap();
+        assert!(!path.exists());
+        // removing it again is fine
+        autosave.remove().unwrap();
+    }
+}
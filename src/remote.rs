@@ -0,0 +1,645 @@
+//! Control of a running game by other programs, over a Unix domain socket. Each line a
+//! program sends is a JSON-RPC 2.0 request, and each line it gets back is a response, or
+//! a notification that the generation has changed.
+
+use crate::{AppAction, GameState};
+use backend::names::{app_action_named, edit_action_named};
+use backend::replay::External;
+use console::Command;
+use editor::Editor;
+use game::Gol;
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use topology::GridSpec;
+
+/// How many lines can wait to be written to a program. Beyond that, a program that isn't
+/// reading misses what's sent to it, rather than the lines piling up in memory.
+const BACKLOG: usize = 64;
+
+/// Why a request couldn't be carried out, with its JSON-RPC error code.
+#[derive(Debug)]
+pub enum Failure {
+    UnknownMethod(String),
+    InvalidParams(String),
+    /// The request made sense, but didn't work.
+    Failed(String),
+}
+
+impl Failure {
+    fn code(&self) -> i64 {
+        match self {
+            Failure::UnknownMethod(_) => -32601,
+            Failure::InvalidParams(_) => -32602,
+            Failure::Failed(_) => -32000,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Failure::UnknownMethod(method) => format!("Unknown method: {}", method),
+            Failure::InvalidParams(msg) | Failure::Failed(msg) => msg.clone(),
+        }
+    }
+}
+
+/// What a request asks for.
+#[derive(Debug)]
+pub enum Call {
+    App(AppAction),
+    Pause,
+    Resume,
+    /// Anything that can be typed at the console, including edits.
+    Command(Command),
+    /// The live cells in a rectangle, or on the whole board.
+    Region(Option<(usize, usize, usize, usize)>),
+    SetCells {
+        cells: Vec<(usize, usize)>,
+        alive: bool,
+    },
+    Stats,
+}
+
+/// A request from a program, which must be answered with `respond`.
+pub struct Request {
+    method: String,
+    params: Map<String, Value>,
+    /// Missing for notifications, which aren't answered.
+    id: Option<Value>,
+    reply: SyncSender<String>,
+}
+
+impl Request {
+    /// Reads a request, or gives the error response to send back.
+    fn parse(line: &str, reply: SyncSender<String>) -> Result<Request, String> {
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(err) => return Err(error_response(&Value::Null, -32700, &err.to_string())),
+        };
+        let id = value.get("id").cloned();
+        let method = match value.get("method").and_then(Value::as_str) {
+            Some(method) => method.to_owned(),
+            None => {
+                let id = id.unwrap_or(Value::Null);
+                return Err(error_response(&id, -32600, "A request needs a method"));
+            }
+        };
+        let params = match value.get("params") {
+            None => Map::new(),
+            Some(Value::Object(params)) => params.clone(),
+            Some(_) => {
+                let id = id.unwrap_or(Value::Null);
+                return Err(error_response(&id, -32602, "Params must be an object"));
+            }
+        };
+        Ok(Request {
+            method,
+            params,
+            id,
+            reply,
+        })
+    }
+
+    /// A request played back from a recording, which isn't answered.
+    pub fn replayed(method: String, params: Map<String, Value>) -> Request {
+        Request {
+            method,
+            params,
+            id: None,
+            reply: sync_channel(0).0,
+        }
+    }
+
+    /// The request to write down in a recording, unless it only asks about the game.
+    pub fn to_external(&self) -> Option<External> {
+        match self.method.as_str() {
+            "region" | "stats" => None,
+            _ => Some(External::Request(self.method.clone(), self.params.clone())),
+        }
+    }
+
+    /// What the request asks for, if it makes sense.
+    pub fn call(&self) -> Result<Call, Failure> {
+        let call = match self.method.as_str() {
+            "step" => Call::Command(Command::Step(self.number("generations")?.unwrap_or(1))),
+            "pause" => Call::Pause,
+            "resume" => Call::Resume,
+            "app" => {
+                let name = self.text("action")?;
+                let action = app_action_named(name)
+                    .ok_or_else(|| Failure::InvalidParams(format!("Unknown action: {}", name)))?;
+                Call::App(action)
+            }
+            "edit" => {
+                let name = self.text("action")?;
+                let at = match (self.signed("x")?, self.signed("y")?) {
+                    (Some(x), Some(y)) => Some((x, y)),
+                    (None, None) => None,
+                    _ => return Err(Failure::InvalidParams("Give both x and y".to_owned())),
+                };
+                let action = edit_action_named(name, at)
+                    .ok_or_else(|| Failure::InvalidParams(format!("Unknown action: {}", name)))?;
+                Call::Command(Command::Edit(action))
+            }
+            "load" => Call::Command(Command::Load {
+                path: self.text("path")?.into(),
+                at: self.cell()?,
+            }),
+            "command" => {
+                let line = self.text("line")?;
+                let line = line.strip_prefix(':').unwrap_or(line);
+                let command = line.parse();
+                Call::Command(command.map_err(|err| Failure::InvalidParams(format!("{}", err)))?)
+            }
+            "region" => {
+                let size = (self.number("width")?, self.number("height")?);
+                match (self.cell()?, size) {
+                    (Some((x, y)), (Some(width), Some(height))) => {
+                        Call::Region(Some((x, y, width as usize, height as usize)))
+                    }
+                    (None, (None, None)) => Call::Region(None),
+                    _ => {
+                        let msg = "Give x, y, width and height, or none of them";
+                        return Err(Failure::InvalidParams(msg.to_owned()));
+                    }
+                }
+            }
+            "set_cells" => {
+                let alive = match self.params.get("alive") {
+                    None => true,
+                    Some(alive) => alive.as_bool().ok_or_else(|| invalid("alive"))?,
+                };
+                Call::SetCells {
+                    cells: self.cells()?,
+                    alive,
+                }
+            }
+            "stats" => Call::Stats,
+            method => return Err(Failure::UnknownMethod(method.to_owned())),
+        };
+        Ok(call)
+    }
+
+    /// Sends back the result, unless the request was a notification.
+    pub fn respond(self, result: Result<Value, Failure>) {
+        let id = match self.id {
+            Some(id) => id,
+            None => return,
+        };
+        let response = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string(),
+            Err(failure) => error_response(&id, failure.code(), &failure.message()),
+        };
+        // the program may have gone, or stopped reading
+        let _ = self.reply.try_send(response);
+    }
+
+    fn text(&self, name: &str) -> Result<&str, Failure> {
+        self.params
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid(name))
+    }
+
+    fn number(&self, name: &str) -> Result<Option<u64>, Failure> {
+        match self.params.get(name) {
+            None => Ok(None),
+            Some(value) => value.as_u64().map(Some).ok_or_else(|| invalid(name)),
+        }
+    }
+
+    /// A whole number that fits in 32 bits, as offsets are no bigger on any machine.
+    fn signed(&self, name: &str) -> Result<Option<isize>, Failure> {
+        match self.params.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_i64()
+                .and_then(|n| i32::try_from(n).ok())
+                .map(|n| Some(n as isize))
+                .ok_or_else(|| invalid(name)),
+        }
+    }
+
+    /// The cell given by `x` and `y`, if there is one.
+    fn cell(&self) -> Result<Option<(usize, usize)>, Failure> {
+        match (self.number("x")?, self.number("y")?) {
+            (Some(x), Some(y)) => Ok(Some((x as usize, y as usize))),
+            (None, None) => Ok(None),
+            _ => Err(Failure::InvalidParams("Give both x and y".to_owned())),
+        }
+    }
+
+    /// `cells`, as a list of `[x, y]` pairs.
+    fn cells(&self) -> Result<Vec<(usize, usize)>, Failure> {
+        let cells = self
+            .params
+            .get("cells")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("cells"))?;
+        cells
+            .iter()
+            .map(|cell| match cell.as_array().map(Vec::as_slice) {
+                Some([x, y]) => match (x.as_u64(), y.as_u64()) {
+                    (Some(x), Some(y)) => Ok((x as usize, y as usize)),
+                    _ => Err(invalid("cells")),
+                },
+                _ => Err(invalid("cells")),
+            })
+            .collect()
+    }
+}
+
+fn invalid(name: &str) -> Failure {
+    Failure::InvalidParams(format!("Missing or invalid {}", name))
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}).to_string()
+}
+
+/// The live cells in a rectangle, clipped to the board, or on the whole board.
+pub fn region(gol: &Gol, area: Option<(usize, usize, usize, usize)>) -> Result<Value, Failure> {
+    let grid = gol.grid();
+    let (x, y, width, height) = area.unwrap_or((0, 0, grid.width(), grid.height()));
+    let (right, bottom) = match (x.checked_add(width), y.checked_add(height)) {
+        (Some(right), Some(bottom)) => (right.min(grid.width()), bottom.min(grid.height())),
+        _ => return Err(Failure::InvalidParams("The region is too big".to_owned())),
+    };
+    let mut alive = Vec::new();
+    for j in y..bottom {
+        for i in x..right {
+            if grid.get(i, j) != 0 {
+                alive.push(json!([i, j]));
+            }
+        }
+    }
+    Ok(json!({"x": x, "y": y, "width": width, "height": height, "alive": alive}))
+}
+
+/// Brings cells to life or kills them, which can be undone in one go like an edit.
+pub fn set_cells(
+    gol: &mut Gol,
+    editor: &mut Editor,
+    cells: &[(usize, usize)],
+    alive: bool,
+) -> Result<Value, Failure> {
+    let (width, height) = (gol.grid().width(), gol.grid().height());
+    if let Some((x, y)) = cells.iter().find(|&&(x, y)| x >= width || y >= height) {
+        return Err(Failure::Failed(format!("{}, {} is off the board", x, y)));
+    }
+    let before = gol.grid().clone();
+    let grid = gol.grid_mut();
+    for &(x, y) in cells {
+        grid.set(x, y, alive as u8);
+    }
+    editor.remember(&before, gol.grid());
+    Ok(json!({"population": gol.population()}))
+}
+
+/// The state of the game, with the statistics of the latest generation.
+pub fn stats(gol: &Gol, state: GameState) -> Value {
+    let grid = GridSpec {
+        topology: gol.topology(),
+        width: Some(gol.grid().width()),
+        height: Some(gol.grid().height()),
+    };
+    let latest = gol
+        .stats()
+        .back()
+        .filter(|stats| stats.generation == gol.generation());
+    json!({
+        "state": format!("{:?}", state),
+        "generation": gol.generation(),
+        "population": latest.map_or_else(|| gol.population(), |stats| stats.population),
+        "rule": gol.rule().to_string(),
+        "topology": grid.to_string(),
+        "cycle": gol.cycle().map(|cycle| cycle.to_string()),
+        "births": latest.map(|stats| stats.births),
+        "deaths": latest.map(|stats| stats.deaths),
+        "bounding_box": latest.and_then(|stats| stats.bounding_box),
+        "density": latest.map(|stats| stats.density),
+    })
+}
+
+/// Listens for programs on a socket, which is removed when the `Remote` is dropped.
+pub struct Remote {
+    path: PathBuf,
+    requests: Receiver<Request>,
+    clients: Arc<Mutex<Vec<SyncSender<String>>>>,
+    /// The generation programs were last told about.
+    announced: Cell<Option<u64>>,
+}
+
+impl Remote {
+    pub fn new(path: &Path) -> io::Result<Remote> {
+        // a socket left behind by a game that crashed is replaced, but not one in use
+        let is_socket = fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+        if is_socket && UnixStream::connect(path).is_err() {
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let (requests, incoming) = channel();
+        let accepting = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let clients = Arc::clone(&accepting);
+                let requests = requests.clone();
+                thread::spawn(move || serve(stream, &clients, requests));
+            }
+        });
+        Ok(Remote {
+            path: path.to_owned(),
+            requests: incoming,
+            clients,
+            announced: Cell::new(None),
+        })
+    }
+
+    /// The requests waiting to be answered.
+    pub fn requests(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+
+    /// Tells every program the generation and population, if the generation has changed
+    /// since they were last told.
+    pub fn announce(&self, gol: &Gol) {
+        if self.announced.get() == Some(gol.generation()) {
+            return;
+        }
+        self.announced.set(Some(gol.generation()));
+        let latest = gol
+            .stats()
+            .back()
+            .filter(|stats| stats.generation == gol.generation());
+        let params = json!({
+            "generation": gol.generation(),
+            "population": latest.map_or_else(|| gol.population(), |stats| stats.population),
+        });
+        let line = json!({"jsonrpc": "2.0", "method": "generation", "params": params});
+        let line = line.to_string();
+        // programs that have gone are forgotten, and those not reading miss a generation
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|client| match client.try_send(line.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Reads requests from a program until it goes, while another thread writes what's sent
+/// back to it.
+fn serve(stream: UnixStream, clients: &Mutex<Vec<SyncSender<String>>>, requests: Sender<Request>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let (outgoing, lines) = sync_channel::<String>(BACKLOG);
+    clients.lock().unwrap().push(outgoing.clone());
+    thread::spawn(move || {
+        for line in lines {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+    for line in BufReader::new(&stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Request::parse(&line, outgoing.clone()) {
+            Ok(request) => {
+                if requests.send(request).is_err() {
+                    break;
+                }
+            }
+            // waiting here holds back the program's requests until it reads its answers
+            Err(response) => {
+                let _ = outgoing.send(response);
+            }
+        }
+    }
+    // so that the writer stops at its next line, and the program is forgotten
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor::EditAction;
+    use grid::Grid;
+    use topology::Topology;
+
+    fn request(line: Value) -> Request {
+        match Request::parse(&line.to_string(), sync_channel(1).0) {
+            Ok(request) => request,
+            Err(response) => panic!("{}", response),
+        }
+    }
+
+    /// The code and id of the error response to a line that can't be read as a request.
+    fn refused(line: &str) -> (Value, Value) {
+        let response = match Request::parse(line, sync_channel(1).0) {
+            Ok(request) => panic!("{} was read as {}", line, request.method),
+            Err(response) => response,
+        };
+        let response: Value = serde_json::from_str(&response).unwrap();
+        (response["error"]["code"].clone(), response["id"].clone())
+    }
+
+    fn invalid_params(call: Result<Call, Failure>) -> String {
+        match call {
+            Err(Failure::InvalidParams(msg)) => msg,
+            call => panic!("{:?}", call),
+        }
+    }
+
+    /// A 4 by 3 board with a few live cells.
+    fn board() -> Gol {
+        let mut gol = Gol::new(4, 3, Topology::Plane);
+        for &(x, y) in &[(0, 0), (2, 1), (3, 1), (3, 2)] {
+            gol.grid_mut().set(x, y, 1);
+        }
+        gol
+    }
+
+    /// What asking to move the cursor across by `x` makes of it.
+    fn move_across(x: Value) -> Result<Call, Failure> {
+        let params = json!({"action": "MoveCursorBy", "x": x, "y": 0});
+        let line = json!({"jsonrpc": "2.0", "method": "edit", "params": params});
+        Request::parse(&line.to_string(), sync_channel(1).0)
+            .unwrap()
+            .call()
+    }
+
+    fn offset(call: Result<Call, Failure>) -> isize {
+        match call {
+            Ok(Call::Command(Command::Edit(EditAction::MoveCursorBy { x, .. }))) => x,
+            call => panic!("{:?}", call),
+        }
+    }
+
+    #[test]
+    fn the_cursor_is_moved_round_by_large_negative_offsets() {
+        let x = offset(move_across(json!(-1000)));
+        let (mut editor, mut grid) = (Editor::new(), Grid::new(100, 10));
+        editor.set_cursor(5, 5);
+        editor.apply_action(EditAction::MoveCursorBy { x, y: 0 }, &mut grid);
+        assert_eq!(editor.get_cursor(), (5, 5));
+        let x = offset(move_across(json!(-1006)));
+        editor.apply_action(EditAction::MoveCursorBy { x, y: 0 }, &mut grid);
+        assert_eq!(editor.get_cursor(), (99, 5));
+        assert_eq!(offset(move_across(json!(i32::MIN))), i32::MIN as isize);
+    }
+
+    #[test]
+    fn offsets_beyond_32_bits_are_refused() {
+        for x in [
+            json!(i64::MAX),
+            json!(i64::MIN),
+            json!(1u64 << 31),
+            json!(1.5),
+        ] {
+            match move_across(x.clone()) {
+                Err(Failure::InvalidParams(msg)) => assert_eq!(msg, "Missing or invalid x"),
+                call => panic!("{}: {:?}", x, call),
+            }
+        }
+    }
+
+    #[test]
+    fn lines_that_arent_requests_are_refused() {
+        assert_eq!(refused("{\"method\": "), (json!(-32700), Value::Null));
+        assert_eq!(refused("{\"id\": 3}"), (json!(-32600), json!(3)));
+        assert_eq!(
+            refused("{\"id\": \"a\", \"method\": \"step\", \"params\": [1]}"),
+            (json!(-32602), json!("a"))
+        );
+    }
+
+    #[test]
+    fn unknown_methods_are_answered_with_an_error() {
+        let (reply, replies) = sync_channel(1);
+        let line = json!({"jsonrpc": "2.0", "id": 7, "method": "fly"}).to_string();
+        let request = Request::parse(&line, reply).ok().unwrap();
+        let failure = request.call().unwrap_err();
+        assert!(matches!(&failure, Failure::UnknownMethod(method) if method == "fly"));
+        request.respond(Err(failure));
+        let response: Value = serde_json::from_str(&replies.try_recv().unwrap()).unwrap();
+        assert_eq!(response["id"], json!(7));
+        assert_eq!(response["error"]["code"], json!(-32601));
+        assert_eq!(response["error"]["message"], json!("Unknown method: fly"));
+    }
+
+    #[test]
+    fn missing_params_are_refused() {
+        let call = |method: &str, params: Value| {
+            request(json!({"method": method, "params": params})).call()
+        };
+        assert_eq!(
+            invalid_params(call("load", json!({}))),
+            "Missing or invalid path"
+        );
+        assert_eq!(
+            invalid_params(call("set_cells", json!({"alive": true}))),
+            "Missing or invalid cells"
+        );
+        assert_eq!(
+            invalid_params(call("set_cells", json!({"cells": [[1]]}))),
+            "Missing or invalid cells"
+        );
+        assert_eq!(
+            invalid_params(call("region", json!({"x": 1, "y": 1}))),
+            "Give x, y, width and height, or none of them"
+        );
+        assert_eq!(
+            invalid_params(call("edit", json!({"action": "MoveCursorTo", "x": 1}))),
+            "Give both x and y"
+        );
+        assert!(matches!(
+            call("step", json!({})),
+            Ok(Call::Command(Command::Step(1)))
+        ));
+    }
+
+    #[test]
+    fn regions_list_their_live_cells_clipped_to_the_board() {
+        let gol = board();
+        assert_eq!(
+            region(&gol, None).unwrap(),
+            json!({"x": 0, "y": 0, "width": 4, "height": 3,
+                   "alive": [[0, 0], [2, 1], [3, 1], [3, 2]]})
+        );
+        assert_eq!(
+            region(&gol, Some((2, 1, 10, 1))).unwrap(),
+            json!({"x": 2, "y": 1, "width": 10, "height": 1, "alive": [[2, 1], [3, 1]]})
+        );
+        assert_eq!(
+            region(&gol, Some((5, 5, 1, 1))).unwrap()["alive"],
+            json!([])
+        );
+        assert!(matches!(
+            region(&gol, Some((1, 0, usize::MAX, 1))),
+            Err(Failure::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn cells_are_set_in_one_edit() {
+        let (mut gol, mut editor) = (board(), Editor::new());
+        let before = gol.grid().clone();
+        let result = set_cells(&mut gol, &mut editor, &[(1, 0), (1, 1)], true).unwrap();
+        assert_eq!(result, json!({"population": 6}));
+        let result = set_cells(&mut gol, &mut editor, &[(0, 0), (1, 2)], false).unwrap();
+        assert_eq!(result, json!({"population": 5}));
+        match set_cells(&mut gol, &mut editor, &[(1, 2), (4, 0)], true) {
+            Err(Failure::Failed(msg)) => assert_eq!(msg, "4, 0 is off the board"),
+            result => panic!("{:?}", result),
+        }
+        assert_eq!(gol.grid().get(1, 2), 0);
+        editor.apply_action(EditAction::Undo, gol.grid_mut());
+        editor.apply_action(EditAction::Undo, gol.grid_mut());
+        assert_eq!(gol.grid(), &before);
+    }
+
+    #[test]
+    fn programs_that_dont_read_miss_generations_and_those_gone_are_forgotten() {
+        let (reading, lines) = sync_channel(1);
+        let (gone, _) = sync_channel(1);
+        let remote = Remote {
+            path: PathBuf::new(),
+            requests: channel().1,
+            clients: Arc::new(Mutex::new(vec![reading, gone])),
+            announced: Cell::new(None),
+        };
+        let mut gol = board();
+        remote.announce(&gol);
+        gol.next_turn();
+        remote.announce(&gol);
+        assert_eq!(remote.clients.lock().unwrap().len(), 1);
+        let line: Value = serde_json::from_str(&lines.try_recv().unwrap()).unwrap();
+        assert_eq!(line["params"]["generation"], json!(0));
+        assert!(lines.try_recv().is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Rules in B/S notation, including isotropic non-totalistic rules in Hensel notation.
+
+use grid::NEIGHBOUR_POSITIONS;
+use std::error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+
+/// Hensel notation letters for each neighbour count from 1 to 4, in canonical order.
+/// Counts 5 to 7 use the same letters as 3 to 1, for the complementary neighbourhoods.
+static HENSEL_LETTERS: [&str; 4] = ["ce", "ceaikn", "ceaiknjqry", "ceaiknjqrytwz"];
+
+/// One neighbourhood for each Hensel letter, as a 3x3 bitmask in reading order with the
+/// centre cell at bit 4. The other members of each class are its rotations and reflections.
+static HENSEL_NEIGHBOURHOODS: [&[u16]; 4] = [
+    &[1, 2],
+    &[5, 10, 3, 40, 33, 68],
+    &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+    &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+];
+
+type Symmetry = fn(isize, isize) -> (isize, isize);
+
+static SYMMETRIES: [Symmetry; 8] = [
+    |x, y| (x, y),
+    |x, y| (-y, x),
+    |x, y| (-x, -y),
+    |x, y| (y, -x),
+    |x, y| (-x, y),
+    |x, y| (y, x),
+    |x, y| (x, -y),
+    |x, y| (-y, -x),
+];
+
+fn letters_for(count: usize) -> &'static str {
+    match count {
+        1..=4 => HENSEL_LETTERS[count - 1],
+        5..=7 => HENSEL_LETTERS[7 - count],
+        _ => "",
+    }
+}
+
+/// All neighbourhoods, indexed by the order of `NEIGHBOUR_POSITIONS`, that belong to the
+/// class named by `letter` for the given neighbour count.
+fn neighbourhoods_for(count: usize, letter: char) -> Vec<u8> {
+    let (base_count, complement) = if count > 4 {
+        (8 - count, 0xff)
+    } else {
+        (count, 0)
+    };
+    let index = match letters_for(base_count).find(letter) {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+    let bits = HENSEL_NEIGHBOURHOODS[base_count - 1][index];
+    let mut masks: Vec<u8> = SYMMETRIES
+        .iter()
+        .map(|symmetry| {
+            (0..9)
+                .filter(|&bit| bit != 4 && bits & (1 << bit) != 0)
+                .map(|bit| symmetry(bit % 3 - 1, bit / 3 - 1))
+                .map(|pos| NEIGHBOUR_POSITIONS.iter().position(|&p| p == pos).unwrap())
+                .fold(0, |mask, i| mask | 1 << i)
+        })
+        .map(|mask: u8| mask ^ complement)
+        .collect();
+    masks.sort();
+    masks.dedup();
+    masks
+}
+
+/// A Life-like or isotropic non-totalistic rule, stored as a lookup table from the
+/// neighbourhood of a cell to whether it is alive in the next generation.
+#[derive(Clone, PartialEq)]
+pub struct Rule {
+    birth: [bool; 256],
+    survival: [bool; 256],
+}
+
+impl Rule {
+    /// Conway's Game of Life, `B3/S23`.
+    pub fn life() -> Rule {
+        "B3/S23".parse().unwrap()
+    }
+
+    /// Whether a cell is alive in the next generation. `neighbourhood` has a bit set for
+    /// each live neighbour, in the order of `NEIGHBOUR_POSITIONS`.
+    pub fn next_state(&self, alive: bool, neighbourhood: u8) -> bool {
+        if alive {
+            self.survival[neighbourhood as usize]
+        } else {
+            self.birth[neighbourhood as usize]
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Rule {
+        Rule::life()
+    }
+}
+
+/// Why a rule couldn't be read.
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    msg: String,
+}
+
+impl Error {
+    fn new(msg: String) -> Error {
+        Error { msg }
+    }
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Invalid rule: {}", self.msg)
+    }
+}
+
+/// Parses the conditions for one half of a rule, e.g. `2-i34q`.
+fn parse_conditions(s: &str) -> Result<[bool; 256], Error> {
+    let mut table = [false; 256];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let count = c
+            .to_digit(10)
+            .filter(|&d| d <= 8)
+            .ok_or_else(|| Error::new(format!("unexpected {:?} in {:?}", c, s)))?
+            as usize;
+        let negate = chars.peek() == Some(&'-');
+        if negate {
+            chars.next();
+        }
+        let mut letters = Vec::new();
+        while let Some(&letter) = chars.peek() {
+            if !letter.is_ascii_alphabetic() {
+                break;
+            }
+            if !letters_for(count).contains(letter) {
+                return Err(Error::new(format!(
+                    "{:?} is not valid for {} neighbours",
+                    letter, count
+                )));
+            }
+            letters.push(letter);
+            chars.next();
+        }
+        if negate && letters.is_empty() {
+            return Err(Error::new(format!("expected letters after '-' in {:?}", s)));
+        }
+        if letters.is_empty() || negate {
+            for mask in 0..=255u8 {
+                if mask.count_ones() as usize == count {
+                    table[mask as usize] = true;
+                }
+            }
+        }
+        for letter in letters {
+            for mask in neighbourhoods_for(count, letter) {
+                table[mask as usize] = !negate;
+            }
+        }
+    }
+    Ok(table)
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    /// Parses `B3/S23` style rules, with optional Hensel letters after each count, e.g.
+    /// `B2-a/S12`. The older `S/B` form, e.g. `23/3`, is also accepted.
+    fn from_str(s: &str) -> Result<Rule, Error> {
+        let s = s.trim();
+        let (birth, survival) = match s.find(['B', 'b']) {
+            Some(b) => {
+                let (birth, survival) = match s.find(['S', 's']) {
+                    Some(i) if i > b => (&s[b + 1..i], &s[i + 1..]),
+                    Some(i) => (&s[b + 1..], &s[i + 1..b]),
+                    None => return Err(Error::new(format!("missing survival in {:?}", s))),
+                };
+                (birth.trim_end_matches('/'), survival.trim_end_matches('/'))
+            }
+            None => {
+                let mut parts = s.splitn(2, '/');
+                let survival = parts.next().unwrap_or("");
+                let birth = parts
+                    .next()
+                    .ok_or_else(|| Error::new(format!("missing birth in {:?}", s)))?;
+                (birth, survival)
+            }
+        };
+        Ok(Rule {
+            birth: parse_conditions(birth)?,
+            survival: parse_conditions(survival)?,
+        })
+    }
+}
+
+fn write_conditions(f: &mut Formatter, table: &[bool; 256]) -> fmt::Result {
+    for count in 0..=8 {
+        let all = (0..=255u8).filter(|mask| mask.count_ones() as usize == count);
+        let total = all.clone().count();
+        let enabled = all.filter(|&mask| table[mask as usize]).count();
+        if enabled == 0 {
+            continue;
+        }
+        if enabled == total {
+            write!(f, "{}", count)?;
+            continue;
+        }
+        let letters = letters_for(count);
+        let (included, excluded): (String, String) = letters.chars().partition(|&letter| {
+            neighbourhoods_for(count, letter)
+                .iter()
+                .all(|&mask| table[mask as usize])
+        });
+        if included.len() * 2 > letters.len() {
+            write!(f, "{}-{}", count, excluded)?;
+        } else {
+            write!(f, "{}{}", count, included)?;
+        }
+    }
+    Ok(())
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "B")?;
+        write_conditions(f, &self.birth)?;
+        write!(f, "/S")?;
+        write_conditions(f, &self.survival)
+    }
+}
+
+impl Debug for Rule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Rule({:?})", self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The neighbourhood with live cells at these offsets.
+    fn neighbourhood(cells: &[(isize, isize)]) -> u8 {
+        cells.iter().fold(0, |mask, cell| {
+            mask | 1 << NEIGHBOUR_POSITIONS.iter().position(|p| p == cell).unwrap()
+        })
+    }
+
+    fn births(rule: &Rule, cells: &[(isize, isize)]) -> bool {
+        rule.next_state(false, neighbourhood(cells))
+    }
+
+    fn survives(rule: &Rule, cells: &[(isize, isize)]) -> bool {
+        rule.next_state(true, neighbourhood(cells))
+    }
+
+    #[test]
+    fn negated_letters_block_only_their_neighbourhoods() {
+        let rule: Rule = "B2-a/S12".parse().unwrap();
+        // 2a, two neighbours side by side, in two of its orientations
+        assert!(!births(&rule, &[(-1, -1), (0, -1)]));
+        assert!(!births(&rule, &[(1, 0), (1, 1)]));
+        // 2c, 2e and 2i
+        assert!(births(&rule, &[(-1, -1), (1, -1)]));
+        assert!(births(&rule, &[(0, -1), (-1, 0)]));
+        assert!(births(&rule, &[(-1, 0), (1, 0)]));
+        assert!(!births(&rule, &[(-1, 0)]));
+        assert!(!births(&rule, &[(-1, 0), (1, 0), (0, 1)]));
+        assert!(survives(&rule, &[(1, 1)]));
+        assert!(survives(&rule, &[(-1, -1), (0, -1)]));
+        assert!(!survives(&rule, &[]));
+        assert!(!survives(&rule, &[(-1, 0), (1, 0), (0, 1)]));
+    }
+
+    #[test]
+    fn letters_allow_only_their_neighbourhoods() {
+        let rule: Rule = "B3/S2-i34q".parse().unwrap();
+        // 2i, two opposite neighbours, either way round
+        assert!(!survives(&rule, &[(-1, 0), (1, 0)]));
+        assert!(!survives(&rule, &[(0, -1), (0, 1)]));
+        assert!(survives(&rule, &[(-1, -1), (0, -1)]));
+        assert!(survives(&rule, &[(-1, -1), (1, 1)]));
+        assert!(survives(&rule, &[(-1, -1), (0, -1), (1, -1)]));
+        // 4q, and its mirror image
+        assert!(survives(&rule, &[(0, -1), (1, -1), (1, 0), (-1, 1)]));
+        assert!(survives(&rule, &[(0, -1), (-1, -1), (-1, 0), (1, 1)]));
+        // 4c and 4e
+        assert!(!survives(&rule, &[(-1, -1), (1, -1), (-1, 1), (1, 1)]));
+        assert!(!survives(&rule, &[(0, -1), (-1, 0), (1, 0), (0, 1)]));
+        assert!(births(&rule, &[(-1, 0), (1, 0), (0, 1)]));
+        assert!(!births(&rule, &[(-1, 0), (1, 0)]));
+    }
+
+    #[test]
+    fn other_ways_of_writing_a_rule_are_read_the_same() {
+        assert_eq!("23/3".parse::<Rule>().unwrap(), Rule::life());
+        assert_eq!("b3/s23".parse::<Rule>().unwrap(), Rule::life());
+        assert_eq!("S23/B3".parse::<Rule>().unwrap(), Rule::life());
+        let highlife: Rule = "b36/s23".parse().unwrap();
+        assert_eq!(highlife.to_string(), "B36/S23");
+        assert_eq!("23/36".parse::<Rule>().unwrap(), highlife);
+    }
+
+    #[test]
+    fn rules_are_written_as_they_are_read() {
+        for rule in &[
+            "B3/S23",
+            "B36/S23",
+            "B2-a/S12",
+            "B3/S2-i34q",
+            "B2e3aik/S",
+            "B/S012345678",
+        ] {
+            assert_eq!(rule.parse::<Rule>().unwrap().to_string(), *rule);
+        }
+    }
+
+    #[test]
+    fn invalid_rules_are_refused() {
+        for rule in &["B9", "B2z", "B3/S23/T", "B3", "3", "B2-/S23"] {
+            assert!(rule.parse::<Rule>().is_err(), "{:?}", rule);
+        }
+    }
+}
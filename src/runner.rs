@@ -1,67 +1,384 @@
-use std::sync::mpsc::{channel, Receiver, RecvError, Sender, TryRecvError};
-use std::thread;
-
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Control {
-    Finish,
-    Play,
-    Pause,
+//! Runs the game a frame at a time on the main loop, so that edits, generations and drawing
+//! never overlap and always come in the same order: first the edits from the frame's
+//! input, in the order they were made, then the generations that are due, then drawing.
+
+use editor::EditAction;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The most generations a frame runs to catch up when it's fallen behind, after which
+/// the runner carries on from now rather than trying to make up the rest.
+const CATCH_UP_LIMIT: u32 = 100;
+
+/// How long a frame spends running generations when there's no delay between them.
+const FRAME_BUDGET: Duration = Duration::from_millis(25);
+
+/// What the runner works on.
+pub trait Simulation {
+    fn edit(&mut self, action: EditAction);
+
+    /// Runs a generation, unless the simulation is being held where it is, as it is while a
+    /// replay waits for its next action.
+    fn step(&mut self) -> bool;
 }
 
 pub struct Runner {
-    send: Sender<Control>,
+    running: bool,
+    /// Milliseconds between generations, or 0 for as many as fit in a frame.
+    delay: u64,
+    /// When the next generation is due, or `None` if it's due straight away.
+    next_step: Option<Instant>,
+    /// Edits waiting for the next frame.
+    edits: VecDeque<EditAction>,
+    /// Generations asked for with `step`, still to run.
+    steps: u64,
 }
 
-#[derive(Debug)]
-pub struct Error;
-
 impl Runner {
-    pub fn start(&self) -> Result<(), Error> {
-        self.send.send(Control::Play).map_err(|_| Error)
+    pub fn new() -> Runner {
+        Runner {
+            running: false,
+            delay: 0,
+            next_step: None,
+            edits: VecDeque::new(),
+            steps: 0,
+        }
     }
-    pub fn pause(&self) -> Result<(), Error> {
-        self.send.send(Control::Pause).map_err(|_| Error)
+
+    /// Starts or stops running generations. A generation runs as soon as it starts.
+    pub fn set_running(&mut self, running: bool) {
+        if running != self.running {
+            self.running = running;
+            self.next_step = None;
+        }
+    }
+
+    pub fn set_delay(&mut self, delay: u64) {
+        self.delay = delay;
+    }
+
+    /// Queues an edit for the next frame, whether or not the game is running.
+    pub fn edit(&mut self, action: EditAction) {
+        self.edits.push_back(action);
+    }
+
+    /// Queues generations to run whether or not the game is running, as many each frame as
+    /// there's time for, so that a long run doesn't hold up input or drawing.
+    pub fn step(&mut self, generations: u64) {
+        self.steps = self.steps.saturating_add(generations);
+    }
+
+    /// The generations queued with `step` that haven't run yet.
+    pub fn steps_left(&self) -> u64 {
+        self.steps
     }
-    pub fn finish(&self) -> Result<(), Error> {
-        self.send.send(Control::Finish).map_err(|_| Error)
+
+    /// Forgets the generations queued with `step` that haven't run yet.
+    pub fn cancel_steps(&mut self) {
+        self.steps = 0;
     }
 
-    pub fn new<F>(f: F) -> Runner
+    /// Runs a frame at `now`: the queued edits, the generations due, then `draw`. Queued
+    /// steps come before any generations the game would run anyway.
+    pub fn run_frame<S, D, E>(&mut self, simulation: &mut S, now: Instant, draw: D) -> Result<(), E>
     where
-        F: Fn() + Send + 'static,
+        S: Simulation,
+        D: FnOnce(&mut S) -> Result<(), E>,
     {
-        let (send, recv) = channel();
-        thread::spawn(move || {
-            let mut paused = true;
-            loop {
-                match recv_msg(&recv, paused, Control::Play) {
-                    Ok(Control::Finish) => return,
-                    Ok(Control::Pause) => {
-                        paused = true;
-                    }
-                    Ok(Control::Play) => {
-                        paused = false;
-                        f();
-                    }
-                    Err(_) => return,
+        for action in self.edits.drain(..) {
+            simulation.edit(action);
+        }
+        if self.steps > 0 {
+            self.run_steps(simulation);
+        } else if self.running {
+            self.run_generations(simulation, now);
+        }
+        draw(simulation)
+    }
+
+    fn run_steps<S: Simulation>(&mut self, simulation: &mut S) {
+        let deadline = Instant::now() + FRAME_BUDGET;
+        while self.steps > 0 && simulation.step() {
+            self.steps -= 1;
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+        // the game carries on from here once the steps are done
+        self.next_step = None;
+    }
+
+    fn run_generations<S: Simulation>(&mut self, simulation: &mut S, now: Instant) {
+        if self.delay == 0 {
+            // wall clock time rather than `now`, as it's how long the generations take
+            let deadline = Instant::now() + FRAME_BUDGET;
+            while simulation.step() {
+                if Instant::now() >= deadline {
+                    break;
                 }
             }
-        });
-        Runner { send }
+            return;
+        }
+        let delay = Duration::from_millis(self.delay);
+        let mut due = self.next_step.unwrap_or(now);
+        let mut steps = 0;
+        while due <= now {
+            if steps == CATCH_UP_LIMIT {
+                due = now + delay;
+                break;
+            }
+            if !simulation.step() {
+                // when it's let go, it carries on straight away
+                self.next_step = None;
+                return;
+            }
+            steps += 1;
+            due += delay;
+        }
+        self.next_step = Some(due);
     }
 }
 
-fn recv_msg<T>(receiver: &Receiver<T>, block: bool, def_msg: T) -> Result<T, RecvError> {
-    if block {
-        match receiver.recv() {
-            Ok(msg) => Ok(msg),
-            _ => Err(RecvError),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Event {
+        Edit(EditAction),
+        Step(u64),
+        Draw,
+    }
+
+    /// A simulation that notes down what was done to it.
+    #[derive(Default)]
+    struct Log {
+        events: Vec<Event>,
+        generation: u64,
+        /// The generation it's held at, if any.
+        hold: Option<u64>,
+    }
+
+    impl Simulation for Log {
+        fn edit(&mut self, action: EditAction) {
+            self.events.push(Event::Edit(action));
         }
-    } else {
-        match receiver.try_recv() {
-            Ok(msg) => Ok(msg),
-            Err(TryRecvError::Empty) => Ok(def_msg),
-            _ => Err(RecvError),
+
+        fn step(&mut self) -> bool {
+            if self.hold == Some(self.generation) {
+                return false;
+            }
+            self.generation += 1;
+            self.events.push(Event::Step(self.generation));
+            true
         }
     }
+
+    fn frame(runner: &mut Runner, log: &mut Log, now: Instant) -> Vec<Event> {
+        log.events.clear();
+        runner
+            .run_frame(log, now, |log| {
+                log.events.push(Event::Draw);
+                Ok::<(), ()>(())
+            })
+            .unwrap();
+        log.events.clone()
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn steps(events: &[Event]) -> usize {
+        events
+            .iter()
+            .filter(|event| matches!(event, Event::Step(_)))
+            .count()
+    }
+
+    fn cursor(x: isize) -> EditAction {
+        EditAction::MoveCursorBy { x, y: 0 }
+    }
+
+    #[test]
+    fn edits_come_before_generations_which_come_before_drawing() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.set_delay(100);
+        runner.set_running(true);
+        runner.edit(cursor(1));
+        runner.edit(EditAction::ToggleCell);
+        runner.edit(cursor(-1));
+        assert_eq!(
+            frame(&mut runner, &mut log, start),
+            [
+                Event::Edit(cursor(1)),
+                Event::Edit(EditAction::ToggleCell),
+                Event::Edit(cursor(-1)),
+                Event::Step(1),
+                Event::Draw,
+            ]
+        );
+    }
+
+    #[test]
+    fn edits_are_made_once_in_the_frame_after_they_are_queued() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.edit(EditAction::Clear);
+        assert_eq!(
+            frame(&mut runner, &mut log, start),
+            [Event::Edit(EditAction::Clear), Event::Draw]
+        );
+        runner.edit(EditAction::Undo);
+        runner.edit(EditAction::Redo);
+        assert_eq!(
+            frame(&mut runner, &mut log, start + ms(20)),
+            [
+                Event::Edit(EditAction::Undo),
+                Event::Edit(EditAction::Redo),
+                Event::Draw
+            ]
+        );
+        assert_eq!(frame(&mut runner, &mut log, start + ms(40)), [Event::Draw]);
+    }
+
+    #[test]
+    fn no_generations_run_while_paused() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.set_delay(10);
+        assert_eq!(frame(&mut runner, &mut log, start), [Event::Draw]);
+        runner.set_running(true);
+        runner.set_running(false);
+        assert_eq!(
+            frame(&mut runner, &mut log, start + ms(1000)),
+            [Event::Draw]
+        );
+        assert_eq!(log.generation, 0);
+    }
+
+    #[test]
+    fn generations_are_paced_by_the_delay() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.set_delay(100);
+        runner.set_running(true);
+        assert_eq!(steps(&frame(&mut runner, &mut log, start)), 1);
+        assert_eq!(steps(&frame(&mut runner, &mut log, start + ms(50))), 0);
+        assert_eq!(steps(&frame(&mut runner, &mut log, start + ms(100))), 1);
+        // a slow frame runs every generation it missed, in order
+        assert_eq!(
+            frame(&mut runner, &mut log, start + ms(320)),
+            [Event::Step(3), Event::Step(4), Event::Draw]
+        );
+        assert_eq!(steps(&frame(&mut runner, &mut log, start + ms(399))), 0);
+        assert_eq!(steps(&frame(&mut runner, &mut log, start + ms(400))), 1);
+    }
+
+    #[test]
+    fn a_frame_far_behind_catches_up_only_so_far() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.set_delay(10);
+        runner.set_running(true);
+        frame(&mut runner, &mut log, start);
+        let late = start + ms(60_000);
+        assert_eq!(
+            steps(&frame(&mut runner, &mut log, late)),
+            CATCH_UP_LIMIT as usize
+        );
+        assert_eq!(steps(&frame(&mut runner, &mut log, late + ms(5))), 0);
+        assert_eq!(steps(&frame(&mut runner, &mut log, late + ms(10))), 1);
+    }
+
+    #[test]
+    fn restarting_runs_a_generation_straight_away() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.set_delay(1000);
+        runner.set_running(true);
+        assert_eq!(steps(&frame(&mut runner, &mut log, start)), 1);
+        runner.set_running(false);
+        runner.set_running(true);
+        assert_eq!(steps(&frame(&mut runner, &mut log, start + ms(10))), 1);
+    }
+
+    #[test]
+    fn without_a_delay_generations_still_come_between_edits_and_drawing() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.set_running(true);
+        runner.edit(EditAction::Clear);
+        let events = frame(&mut runner, &mut log, start);
+        assert_eq!(events.first(), Some(&Event::Edit(EditAction::Clear)));
+        assert_eq!(events.last(), Some(&Event::Draw));
+        assert!(steps(&events) >= 1);
+        assert_eq!(steps(&events), events.len() - 2);
+    }
+
+    #[test]
+    fn a_held_simulation_stops_until_it_is_let_go() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.set_delay(10);
+        runner.set_running(true);
+        log.hold = Some(2);
+        assert_eq!(
+            frame(&mut runner, &mut log, start + ms(0)),
+            [Event::Step(1), Event::Draw]
+        );
+        assert_eq!(
+            frame(&mut runner, &mut log, start + ms(50)),
+            [Event::Step(2), Event::Draw]
+        );
+        assert_eq!(frame(&mut runner, &mut log, start + ms(60)), [Event::Draw]);
+        // held without a delay too
+        runner.set_delay(0);
+        assert_eq!(frame(&mut runner, &mut log, start + ms(70)), [Event::Draw]);
+        runner.set_delay(10);
+        log.hold = None;
+        assert_eq!(
+            frame(&mut runner, &mut log, start + ms(80)),
+            [Event::Step(3), Event::Draw]
+        );
+    }
+
+    #[test]
+    fn queued_steps_run_while_paused_and_before_drawing() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.step(2);
+        runner.edit(EditAction::Clear);
+        assert_eq!(
+            frame(&mut runner, &mut log, start),
+            [
+                Event::Edit(EditAction::Clear),
+                Event::Step(1),
+                Event::Step(2),
+                Event::Draw
+            ]
+        );
+        assert_eq!(runner.steps_left(), 0);
+        assert_eq!(frame(&mut runner, &mut log, start + ms(20)), [Event::Draw]);
+    }
+
+    #[test]
+    fn many_steps_are_spread_over_frames_and_can_be_cancelled() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        runner.step(u64::MAX);
+        let events = frame(&mut runner, &mut log, start);
+        assert_eq!(events.last(), Some(&Event::Draw));
+        assert_eq!(runner.steps_left(), u64::MAX - steps(&events) as u64);
+        runner.cancel_steps();
+        assert_eq!(frame(&mut runner, &mut log, start + ms(20)), [Event::Draw]);
+    }
+
+    #[test]
+    fn queued_steps_wait_while_the_simulation_is_held() {
+        let (mut runner, mut log, start) = (Runner::new(), Log::default(), Instant::now());
+        log.hold = Some(1);
+        runner.step(3);
+        assert_eq!(
+            frame(&mut runner, &mut log, start),
+            [Event::Step(1), Event::Draw]
+        );
+        assert_eq!(frame(&mut runner, &mut log, start + ms(20)), [Event::Draw]);
+        log.hold = None;
+        assert_eq!(
+            frame(&mut runner, &mut log, start + ms(40)),
+            [Event::Step(2), Event::Step(3), Event::Draw]
+        );
+    }
 }
@@ -0,0 +1,503 @@
+//! Scripts in Rhai that drive the board, for experiments too long to do by hand, such as
+//! running soups until they settle and taking a census of each. Scripts call functions
+//! of the board, the editor and pattern files, as Golly's scripts do.
+
+use census::{self, Census};
+use editor::{EditAction, Editor};
+use game::Gol;
+use pattern::{Pattern, MAX_SIZE};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, INT};
+use soup::Soup;
+use std::cell::RefCell;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How long a script run from the game may take. The game waits for it, so one that runs
+/// on and on is stopped rather than leaving the game stuck.
+pub const TIME_LIMIT: Duration = Duration::from_secs(10);
+
+/// How many operations a script does between looking at the time.
+const OPERATIONS_PER_CHECK: u64 = 1024;
+
+#[derive(Debug)]
+pub struct Error {
+    msg: String,
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Script failed: {}", self.msg)
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// What a script works on, shared by the functions it calls.
+struct Board {
+    gol: Gol,
+    editor: Editor,
+    /// When the script is stopped, if it has a time limit.
+    deadline: Option<Instant>,
+    /// The seeds of soups the script doesn't give one for.
+    seeds: Box<dyn Iterator<Item = u64>>,
+}
+
+type Shared = Rc<RefCell<Board>>;
+
+impl Board {
+    /// A cell given by a script, if it's on the board.
+    fn cell(&self, x: INT, y: INT) -> Option<(usize, usize)> {
+        let grid = self.gol.grid();
+        if x < 0 || y < 0 || x as usize >= grid.width() || y as usize >= grid.height() {
+            None
+        } else {
+            Some((x as usize, y as usize))
+        }
+    }
+
+    /// Like `cell`, but a cell off the board is an error.
+    fn cell_on_board(&self, x: INT, y: INT) -> Result<(usize, usize)> {
+        self.cell(x, y)
+            .ok_or_else(|| format!("{}, {} is off the board", x, y).into())
+    }
+
+    fn edit(&mut self, action: EditAction) {
+        self.editor.set_topology(self.gol.topology());
+        self.editor.apply_action(action, self.gol.grid_mut());
+    }
+
+    fn out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// Runs the script in `path` on the game and editor, giving each line it prints to
+/// `output`, and stopping it if it runs for longer than `time_limit`. Soups the script
+/// doesn't give a seed for have seeds chosen from `seed`, so the same `seed` runs the
+/// script the same way again. Everything the script changes is undone in one go.
+pub fn run<F>(
+    path: &Path,
+    gol: &mut Gol,
+    editor: &mut Editor,
+    time_limit: Option<Duration>,
+    seed: u64,
+    output: F,
+) -> std::result::Result<(), Error>
+where
+    F: Fn(&str) + 'static,
+{
+    let history = editor.take_history();
+    let before = gol.grid().clone();
+    let topology = gol.topology();
+    let board = Rc::new(RefCell::new(Board {
+        gol: mem::replace(gol, Gol::new(0, 0, topology)),
+        editor: mem::take(editor),
+        deadline: time_limit.map(|limit| Instant::now() + limit),
+        seeds: Box::new(Soup::seeds(seed)),
+    }));
+    let result = engine(&board, output).run_file(path.to_owned());
+    let out_of_time = {
+        let mut board = board.borrow_mut();
+        mem::swap(gol, &mut board.gol);
+        mem::swap(editor, &mut board.editor);
+        board.out_of_time()
+    };
+    // the script's own steps are left out of the history, as long as the size is the same
+    if (before.width(), before.height()) == (gol.grid().width(), gol.grid().height()) {
+        editor.restore_history(history);
+        editor.remember(&before, gol.grid());
+    }
+    result.map_err(|err| Error {
+        msg: match time_limit {
+            Some(limit) if out_of_time => format!("stopped after {}s", limit.as_secs()),
+            _ => err.to_string(),
+        },
+    })
+}
+
+fn engine<F>(board: &Shared, output: F) -> Engine
+where
+    F: Fn(&str) + 'static,
+{
+    let mut engine = Engine::new();
+    let output = Rc::new(output);
+    let print = Rc::clone(&output);
+    engine.on_print(move |text| print(text));
+    engine.on_debug(move |text, _, _| output(text));
+    let b = Rc::clone(board);
+    engine.on_progress(move |operations| {
+        if operations % OPERATIONS_PER_CHECK == 0 && b.borrow().out_of_time() {
+            Some(Dynamic::UNIT)
+        } else {
+            None
+        }
+    });
+    register_board(&mut engine, board);
+    register_editor(&mut engine, board);
+    register_patterns(&mut engine, board);
+    engine
+}
+
+fn register_board(engine: &mut Engine, board: &Shared) {
+    let b = Rc::clone(board);
+    engine.register_fn("step", move || b.borrow_mut().gol.next_turn());
+    let b = Rc::clone(board);
+    engine.register_fn("step", move |generations: INT| -> Result<()> {
+        let board = &mut *b.borrow_mut();
+        for _ in 0..generations {
+            if board.out_of_time() {
+                return Err("out of time".into());
+            }
+            board.gol.next_turn();
+        }
+        Ok(())
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("generation", move || b.borrow().gol.generation() as INT);
+    let b = Rc::clone(board);
+    engine.register_fn("population", move || b.borrow().gol.population() as INT);
+    let b = Rc::clone(board);
+    engine.register_fn("width", move || b.borrow().gol.grid().width() as INT);
+    let b = Rc::clone(board);
+    engine.register_fn("height", move || b.borrow().gol.grid().height() as INT);
+    let b = Rc::clone(board);
+    engine.register_fn("get", move |x: INT, y: INT| {
+        let board = b.borrow();
+        board
+            .cell(x, y)
+            .is_some_and(|(x, y)| board.gol.grid().get(x, y) != 0)
+    });
+    // cells are set directly rather than by the editor, which would copy the grid each time
+    let b = Rc::clone(board);
+    engine.register_fn("set", move |x: INT, y: INT, alive: bool| {
+        let mut board = b.borrow_mut();
+        if let Some((x, y)) = board.cell(x, y) {
+            board.gol.grid_mut().set(x, y, alive as u8);
+        }
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("clear", move || b.borrow_mut().edit(EditAction::Clear));
+    let b = Rc::clone(board);
+    engine.register_fn("resize", move |width: INT, height: INT| -> Result<()> {
+        // no bigger than a pattern file can be, as at the console
+        let side = 1..=MAX_SIZE as INT;
+        if !side.contains(&width) || !side.contains(&height) {
+            return Err(format!("Can't resize the board to {} by {}", width, height).into());
+        }
+        let board = &mut *b.borrow_mut();
+        board.gol.resize(width as usize, height as usize);
+        board.editor.resize(width as usize, height as usize);
+        Ok(())
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("rule", move || b.borrow().gol.rule().to_string());
+    let b = Rc::clone(board);
+    engine.register_fn("set_rule", move |rule: &str| -> Result<()> {
+        let rule = rule.parse().map_err(|err| format!("{}", err))?;
+        b.borrow_mut().gol.set_rule(rule);
+        Ok(())
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("stable", move || b.borrow().gol.cycle().is_some());
+    let b = Rc::clone(board);
+    engine.register_fn("cycle", move || {
+        b.borrow()
+            .gol
+            .cycle()
+            .map_or_else(String::new, |cycle| cycle.to_string())
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("census", move || {
+        let board = b.borrow();
+        let gol = &board.gol;
+        let census = Census::new(gol.grid(), gol.topology(), gol.rule());
+        let mut counts = Map::new();
+        for (code, count) in census.counts {
+            let name = census::name(&code).map_or(code, str::to_owned);
+            counts.insert(name.into(), Dynamic::from(count as INT));
+        }
+        counts
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("soup", move |density: f64| -> Result<()> {
+        let mut board = b.borrow_mut();
+        let seed = board.seeds.next().unwrap();
+        board.edit(soup(density, seed)?);
+        Ok(())
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("soup", move |density: f64, seed: INT| -> Result<()> {
+        b.borrow_mut().edit(soup(density, seed as u64)?);
+        Ok(())
+    });
+}
+
+/// A random soup filling the selection or the whole board.
+fn soup(density: f64, seed: u64) -> Result<EditAction> {
+    if !(0.0..=1.0).contains(&density) {
+        return Err(format!("The density of a soup must be from 0 to 1, not {}", density).into());
+    }
+    Ok(EditAction::RandomSoup {
+        seed: Some(seed),
+        density: Some(density),
+        symmetry: None,
+    })
+}
+
+fn register_editor(engine: &mut Engine, board: &Shared) {
+    let b = Rc::clone(board);
+    engine.register_fn("cursor", move || {
+        let (x, y) = b.borrow().editor.get_cursor();
+        vec![Dynamic::from(x as INT), Dynamic::from(y as INT)] as Array
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("move_cursor", move |x: INT, y: INT| -> Result<()> {
+        let mut board = b.borrow_mut();
+        let (x, y) = board.cell_on_board(x, y)?;
+        board.edit(EditAction::MoveCursorTo { x, y });
+        Ok(())
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("select", move |x: INT, y: INT, width: INT, height: INT| {
+        let mut board = b.borrow_mut();
+        let (x, y) = board.cell_on_board(x, y)?;
+        let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+        let corner = (x + width - 1, y + height - 1);
+        let (right, bottom) = board.cell_on_board(corner.0 as INT, corner.1 as INT)?;
+        if board.editor.selection().is_some() {
+            board.edit(EditAction::ToggleSelection);
+        }
+        board.edit(EditAction::MoveCursorTo { x, y });
+        board.edit(EditAction::ToggleSelection);
+        board.edit(EditAction::MoveCursorTo {
+            x: right,
+            y: bottom,
+        });
+        Ok(()) as Result<()>
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("deselect", move || {
+        let mut board = b.borrow_mut();
+        if board.editor.selection().is_some() {
+            board.edit(EditAction::ToggleSelection);
+        }
+    });
+    let actions = [
+        ("copy", EditAction::Copy),
+        ("cut", EditAction::Cut),
+        ("fill", EditAction::FillSelection),
+        ("clear_selection", EditAction::ClearSelection),
+        ("clear_outside", EditAction::ClearOutsideSelection),
+        ("undo", EditAction::Undo),
+        ("redo", EditAction::Redo),
+    ];
+    for (name, action) in actions {
+        let b = Rc::clone(board);
+        engine.register_fn(name, move || b.borrow_mut().edit(action.clone()));
+    }
+    let b = Rc::clone(board);
+    engine.register_fn("paste", move || {
+        let mut board = b.borrow_mut();
+        board.edit(EditAction::Paste);
+        board.edit(EditAction::CommitStamp);
+    });
+    let b = Rc::clone(board);
+    engine.register_fn("flood_fill", move |x: INT, y: INT| -> Result<()> {
+        let mut board = b.borrow_mut();
+        let (x, y) = board.cell_on_board(x, y)?;
+        board.edit(EditAction::FloodFillAt { x, y });
+        Ok(())
+    });
+}
+
+fn register_patterns(engine: &mut Engine, board: &Shared) {
+    engine
+        .register_type_with_name::<Pattern>("Pattern")
+        .register_get("name", |pattern: &mut Pattern| pattern.name.clone())
+        .register_get("width", |pattern: &mut Pattern| pattern.width as INT)
+        .register_get("height", |pattern: &mut Pattern| pattern.height as INT)
+        .register_get("population", |pattern: &mut Pattern| {
+            pattern.cells.len() as INT
+        })
+        .register_get("rule", |pattern: &mut Pattern| {
+            pattern
+                .rule
+                .as_ref()
+                .map_or_else(String::new, |rule| rule.to_string())
+        })
+        .register_fn("to_rle", |pattern: &mut Pattern| pattern.to_rle());
+    engine.register_fn("load", |path: &str| -> Result<Pattern> {
+        Pattern::load(Path::new(path))
+            .map_err(|err| format!("Couldn't load {}: {}", path, err).into())
+    });
+    engine.register_fn("parse_rle", |rle: &str| -> Result<Pattern> {
+        Pattern::from_rle(rle).map_err(|err| format!("{}", err).into())
+    });
+    let b = Rc::clone(board);
+    engine.register_fn(
+        "place",
+        move |pattern: Pattern, x: INT, y: INT| -> Result<()> {
+            let mut board = b.borrow_mut();
+            let (x, y) = board.cell_on_board(x, y)?;
+            let cursor = board.editor.get_cursor();
+            board.edit(EditAction::MoveCursorTo { x, y });
+            board.edit(EditAction::AddPattern(Box::new(pattern)));
+            board.edit(EditAction::CommitStamp);
+            board.editor.set_cursor(cursor.0, cursor.1);
+            Ok(())
+        },
+    );
+    let b = Rc::clone(board);
+    engine.register_fn("save", move |path: &str| -> Result<()> {
+        b.borrow()
+            .gol
+            .save(Path::new(path))
+            .map_err(|err| format!("Couldn't save {}: {}", path, err).into())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grid::Grid;
+    use std::env;
+    use std::fs;
+    use topology::Topology;
+
+    fn run_text(
+        text: &str,
+        gol: &mut Gol,
+        editor: &mut Editor,
+        time_limit: Option<Duration>,
+    ) -> std::result::Result<(), Error> {
+        let path = env::temp_dir().join(format!(
+            "gol-script-{}-{:?}.rhai",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        fs::write(&path, text).unwrap();
+        let result = run(&path, gol, editor, time_limit, 1, |_| {});
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn alive(grid: &Grid<u8>) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if grid.get(x, y) != 0 {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn cells_are_set_and_stepped() {
+        let (mut gol, mut editor) = (Gol::new(5, 5, Topology::Plane), Editor::new());
+        let script = "
+            set(2, 1, true); set(2, 2, true); set(2, 3, true); set(-1, 0, true);
+            step();
+            if !get(1, 2) || get(2, 1) || get(9, 9) { throw \"not a blinker\"; }
+            step(2);
+        ";
+        run_text(script, &mut gol, &mut editor, None).unwrap();
+        assert_eq!(gol.generation(), 3);
+        assert_eq!(alive(gol.grid()), [(1, 2), (2, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn a_selection_is_copied_and_pasted() {
+        let (mut gol, mut editor) = (Gol::new(6, 4, Topology::Plane), Editor::new());
+        let script = "
+            set(0, 0, true); set(1, 1, true);
+            select(0, 0, 2, 2); copy(); deselect();
+            move_cursor(3, 2); paste();
+            if cursor() != [3, 2] { throw \"the cursor moved\"; }
+        ";
+        run_text(script, &mut gol, &mut editor, None).unwrap();
+        assert_eq!(alive(gol.grid()), [(0, 0), (1, 1), (3, 2), (4, 3)]);
+        assert!(run_text("select(5, 3, 2, 1);", &mut gol, &mut editor, None).is_err());
+    }
+
+    #[test]
+    fn patterns_are_placed_without_moving_the_cursor() {
+        let (mut gol, mut editor) = (Gol::new(8, 8, Topology::Plane), Editor::new());
+        editor.set_cursor(1, 1);
+        let script = "
+            let glider = parse_rle(\"x = 3, y = 3\\nbo$2bo$3o!\");
+            if glider.population != 5 { throw \"not a glider\"; }
+            place(glider, 4, 4);
+        ";
+        run_text(script, &mut gol, &mut editor, None).unwrap();
+        assert_eq!(alive(gol.grid()), [(5, 4), (6, 5), (4, 6), (5, 6), (6, 6)]);
+        assert_eq!(editor.get_cursor(), (1, 1));
+    }
+
+    #[test]
+    fn soups_are_the_same_for_the_same_seeds() {
+        let soups = |script: &str| {
+            let (mut gol, mut editor) = (Gol::new(16, 16, Topology::Plane), Editor::new());
+            run_text(script, &mut gol, &mut editor, None).unwrap();
+            alive(gol.grid())
+        };
+        let chosen = soups("soup(0.5);");
+        assert!(!chosen.is_empty());
+        assert_eq!(soups("soup(0.5);"), chosen);
+        assert_eq!(soups("soup(0.5, 7);"), soups("soup(0.5, 7);"));
+        // each soup without a seed has the next one
+        assert_ne!(soups("soup(0.5); clear(); soup(0.5);"), chosen);
+        assert!(run_text(
+            "soup(1.5);",
+            &mut Gol::new(4, 4, Topology::Plane),
+            &mut Editor::new(),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn a_script_that_runs_too_long_is_stopped() {
+        let (mut gol, mut editor) = (Gol::new(4, 4, Topology::Plane), Editor::new());
+        let limit = Some(Duration::from_millis(100));
+        let err = run_text("loop { }", &mut gol, &mut editor, limit).unwrap_err();
+        assert_eq!(err.to_string(), "Script failed: stopped after 0s");
+        let err = run_text("step(1000000000);", &mut gol, &mut editor, limit).unwrap_err();
+        assert_eq!(err.to_string(), "Script failed: stopped after 0s");
+    }
+
+    #[test]
+    fn the_whole_script_is_undone_in_one_go() {
+        let (mut gol, mut editor) = (Gol::new(6, 6, Topology::Plane), Editor::new());
+        gol.grid_mut().set(0, 0, 1);
+        let before = gol.grid().clone();
+        let script = "
+            set(3, 3, true); select(0, 0, 2, 2); fill(); deselect(); step(); soup(0.3);
+        ";
+        run_text(script, &mut gol, &mut editor, None).unwrap();
+        assert_ne!(gol.grid(), &before);
+        editor.apply_action(EditAction::Undo, gol.grid_mut());
+        assert_eq!(gol.grid(), &before);
+        editor.apply_action(EditAction::Undo, gol.grid_mut());
+        assert_eq!(gol.grid(), &before);
+    }
+
+    #[test]
+    fn the_board_is_only_resized_to_a_pattern_size() {
+        let (mut gol, mut editor) = (Gol::new(4, 4, Topology::Plane), Editor::new());
+        run_text("resize(7, 3);", &mut gol, &mut editor, None).unwrap();
+        assert_eq!((gol.grid().width(), gol.grid().height()), (7, 3));
+        for script in ["resize(0, 3);", "resize(7, 10001);", "resize(100000, 1);"] {
+            assert!(run_text(script, &mut gol, &mut editor, None).is_err());
+        }
+        assert_eq!((gol.grid().width(), gol.grid().height()), (7, 3));
+    }
+}
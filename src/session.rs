@@ -0,0 +1,324 @@
+//! Saving and restoring everything needed to carry on exploring where we left off.
+//!
+//! A session file is plain text: one `key value` line per setting, followed by the grid
+//! and the undo history, each as an RLE pattern the size of the whole grid.
+
+use grid::Grid;
+use pattern::{self, Pattern};
+use rule::{self, Rule};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::{FromStr, Lines};
+use topology::{self, GridSpec, Topology};
+
+static HEADER: &str = "#GOL session";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(String),
+    Pattern(pattern::Error),
+    Rule(rule::Error),
+    Topology(topology::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Parse(msg) => write!(f, "Invalid session: {}", msg),
+            Error::Pattern(err) => write!(f, "{}", err),
+            Error::Rule(err) => write!(f, "{}", err),
+            Error::Topology(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Error {
+        Error::Io(other)
+    }
+}
+
+impl From<pattern::Error> for Error {
+    fn from(other: pattern::Error) -> Error {
+        Error::Pattern(other)
+    }
+}
+
+impl From<rule::Error> for Error {
+    fn from(other: rule::Error) -> Error {
+        Error::Rule(other)
+    }
+}
+
+impl From<topology::Error> for Error {
+    fn from(other: topology::Error) -> Error {
+        Error::Topology(other)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub grid: Grid<u8>,
+    pub generation: u64,
+    pub rule: Rule,
+    pub topology: Topology,
+    pub cursor: (usize, usize),
+    /// The cell shown in the top left corner of the screen.
+    pub viewport: (usize, usize),
+    /// Milliseconds between generations.
+    pub delay: u64,
+    /// Earlier grids, oldest first.
+    pub undo: Vec<Grid<u8>>,
+    /// Grids that were undone, most recently undone last.
+    pub redo: Vec<Grid<u8>>,
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Session, Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Saves the session, writing it beside `path` first and then moving it into place, so
+    /// that a crash part way through leaves the last save whole.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        fs::write(&temp, self.to_string())?;
+        fs::rename(&temp, path).map_err(|err| {
+            let _ = fs::remove_file(&temp);
+            Error::Io(err)
+        })
+    }
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::Parse(format!("bad {} {:?}", key, value)))
+}
+
+fn parse_pair(key: &str, value: &str) -> Result<(usize, usize), Error> {
+    let mut parts = value.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(x), Some(y), None) => Ok((parse_number(key, x)?, parse_number(key, y)?)),
+        _ => Err(Error::Parse(format!("bad {} {:?}", key, value))),
+    }
+}
+
+/// Reads an RLE pattern up to and including the line that ends it.
+fn parse_grid(lines: &mut Lines) -> Result<Grid<u8>, Error> {
+    let mut rle = String::new();
+    for line in lines {
+        rle.push_str(line);
+        rle.push('\n');
+        if !line.starts_with('#') && line.contains('!') {
+            return Ok(Pattern::from_rle(&rle)?.to_grid());
+        }
+    }
+    Err(Error::Parse("unfinished grid".to_owned()))
+}
+
+impl FromStr for Session {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Session, Error> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some(HEADER) {
+            return Err(Error::Parse("not a session file".to_owned()));
+        }
+        let mut grid = None;
+        let mut session = Session {
+            grid: Grid::new(0, 0),
+            generation: 0,
+            rule: Rule::life(),
+            topology: Topology::Torus,
+            cursor: (0, 0),
+            viewport: (0, 0),
+            delay: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        };
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            let mut parts = line.splitn(2, ' ');
+            let key = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("");
+            match key {
+                "" => {}
+                "generation" => session.generation = parse_number(key, value)?,
+                "rule" => session.rule = value.parse()?,
+                "topology" => session.topology = value.parse::<GridSpec>()?.topology,
+                "cursor" => session.cursor = parse_pair(key, value)?,
+                "viewport" => session.viewport = parse_pair(key, value)?,
+                "delay" => session.delay = parse_number(key, value)?,
+                "grid" => grid = Some(parse_grid(&mut lines)?),
+                "undo" => session.undo.push(parse_grid(&mut lines)?),
+                "redo" => session.redo.push(parse_grid(&mut lines)?),
+                _ => return Err(Error::Parse(format!("unknown setting {:?}", key))),
+            }
+        }
+        session.grid = grid.ok_or_else(|| Error::Parse("missing grid".to_owned()))?;
+        let (width, height) = (session.grid.width(), session.grid.height());
+        let (x, y) = session.cursor;
+        if x >= width || y >= height {
+            return Err(Error::Parse(format!("cursor {},{} is off the grid", x, y)));
+        }
+        let mut history = session.undo.iter().chain(&session.redo);
+        if history.any(|grid| grid.width() != width || grid.height() != height) {
+            return Err(Error::Parse("undo history is a different size".to_owned()));
+        }
+        Ok(session)
+    }
+}
+
+fn write_grid(f: &mut Formatter, key: &str, grid: &Grid<u8>) -> fmt::Result {
+    writeln!(f, "{}", key)?;
+    write!(f, "{}", Pattern::from_grid(String::new(), grid).to_rle())
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let grid = GridSpec {
+            topology: self.topology,
+            width: Some(self.grid.width()),
+            height: Some(self.grid.height()),
+        };
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "generation {}", self.generation)?;
+        writeln!(f, "rule {}", self.rule)?;
+        writeln!(f, "topology {}", grid)?;
+        writeln!(f, "cursor {} {}", self.cursor.0, self.cursor.1)?;
+        writeln!(f, "viewport {} {}", self.viewport.0, self.viewport.1)?;
+        writeln!(f, "delay {}", self.delay)?;
+        write_grid(f, "grid", &self.grid)?;
+        for grid in &self.undo {
+            write_grid(f, "undo", grid)?;
+        }
+        for grid in &self.redo {
+            write_grid(f, "redo", grid)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use topology::Twist;
+
+    fn grid_with(width: usize, height: usize, cells: &[(usize, usize)]) -> Grid<u8> {
+        let mut grid = Grid::new(width, height);
+        for &(x, y) in cells {
+            grid.set(x, y, 1);
+        }
+        grid
+    }
+
+    fn session() -> Session {
+        Session {
+            grid: grid_with(8, 6, &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]),
+            generation: 42,
+            rule: "B36/S23".parse().unwrap(),
+            topology: Topology::KleinBottle(Twist::LeftRight),
+            cursor: (7, 5),
+            viewport: (3, 2),
+            delay: 250,
+            undo: vec![Grid::new(8, 6), grid_with(8, 6, &[(1, 0)])],
+            redo: vec![grid_with(8, 6, &[(7, 5), (0, 5)])],
+        }
+    }
+
+    fn parse_error(text: &str) -> String {
+        match text.parse::<Session>() {
+            Ok(session) => panic!("{:?} parsed as {:?}", text, session),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn a_session_reads_back_as_it_was_written() {
+        let session = session();
+        assert_eq!(session.to_string().parse::<Session>().unwrap(), session);
+    }
+
+    #[test]
+    fn a_session_is_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("gol-session-{}", std::process::id()));
+        session().save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn saving_replaces_the_file_and_leaves_nothing_beside_it() {
+        let dir = std::env::temp_dir().join(format!("gol-session-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.session");
+        fs::write(&path, "an older save").unwrap();
+        session().save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session());
+        let files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|f| f.unwrap().path())
+            .collect();
+        assert_eq!(files, [path]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_file_without_the_header_is_not_a_session() {
+        let text = session().to_string().replacen(HEADER, "#GOL", 1);
+        assert_eq!(parse_error(&text), "Invalid session: not a session file");
+        assert_eq!(parse_error(""), "Invalid session: not a session file");
+    }
+
+    #[test]
+    fn unknown_settings_and_bad_values_are_errors() {
+        let text = session().to_string();
+        let with = |from: &str, to: &str| text.replacen(from, to, 1);
+        assert_eq!(
+            parse_error(&with("delay 250", "speed 250")),
+            "Invalid session: unknown setting \"speed\""
+        );
+        assert_eq!(
+            parse_error(&with("delay 250", "delay fast")),
+            "Invalid session: bad delay \"fast\""
+        );
+        assert_eq!(
+            parse_error(&with("cursor 7 5", "cursor 7")),
+            "Invalid session: bad cursor \"7\""
+        );
+        assert_eq!(
+            parse_error(&with("cursor 7 5", "cursor 8 5")),
+            "Invalid session: cursor 8,5 is off the grid"
+        );
+        assert!(with("rule B36/S23", "rule B9").parse::<Session>().is_err());
+        assert!(with("topology K8,6*", "topology Q8,6")
+            .parse::<Session>()
+            .is_err());
+    }
+
+    #[test]
+    fn the_grid_and_history_must_be_whole_and_the_same_size() {
+        let mut session = session();
+        let text = session.to_string();
+        let without_grid = text.replacen("grid\n", "", 1);
+        assert!(without_grid.parse::<Session>().is_err());
+        let unfinished = text.trim_end().trim_end_matches('!');
+        assert_eq!(parse_error(unfinished), "Invalid session: unfinished grid");
+        session.undo.push(Grid::new(6, 8));
+        assert_eq!(
+            parse_error(&session.to_string()),
+            "Invalid session: undo history is a different size"
+        );
+    }
+}
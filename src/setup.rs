@@ -0,0 +1,88 @@
+//! Setting up the game: a new one from the options, or one carried on from a session.
+
+use backend::GameBackend;
+use editor::Editor;
+use game::Gol;
+use options::Options;
+use pattern::Pattern;
+use session::Session;
+use soup::{Soup, Symmetry};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The size of the square filled by `--soup`, as in apgsearch.
+const SOUP_SIZE: usize = 16;
+
+/// A new game set up by the options, `width` by `height` unless the options give a size,
+/// with the loaded pattern in the middle.
+pub fn new_game(options: &Options, width: usize, height: usize, pattern: Option<&Pattern>) -> Gol {
+    let width = options.grid.width.unwrap_or(width);
+    let height = options.grid.height.unwrap_or(height);
+    let mut gol = Gol::new(width, height, options.grid.topology);
+    gol.set_rule(options.rule.clone());
+    if let Some(pattern) = pattern {
+        gol.init(&pattern.centred_in(width, height));
+    }
+    if let Some(seed) = options.soup {
+        let size = SOUP_SIZE.min(width).min(height);
+        let (x, y) = ((width - size) / 2, (height - size) / 2);
+        Soup::new(seed, 0.5, Symmetry::C1).fill(gol.grid_mut(), x, y, size, size);
+    }
+    gol
+}
+
+/// The game a session was saved from.
+pub fn game_from_session(session: &Session) -> Gol {
+    let mut gol = Gol::new_from_grid(session.grid.clone(), session.topology);
+    gol.set_rule(session.rule.clone());
+    gol.set_generation(session.generation);
+    gol
+}
+
+/// Replaces the game with a saved session. The editor starts afresh, apart from the
+/// cursor and undo history.
+pub fn restore_session<Ui: GameBackend + ?Sized>(
+    session: Session,
+    game: &Mutex<Gol>,
+    editor: &Mutex<Editor>,
+    ui: &Ui,
+    delay: &AtomicU64,
+) {
+    let mut game = game.lock().unwrap();
+    *game = game_from_session(&session);
+    let mut editor = editor.lock().unwrap();
+    *editor = Editor::new();
+    editor.set_cursor(session.cursor.0, session.cursor.1);
+    editor.set_history(&session.undo, &session.redo, game.grid());
+    ui.set_viewport(session.viewport.0, session.viewport.1);
+    delay.store(session.delay, Ordering::Relaxed);
+}
+
+/// A session to save the game as it is now.
+pub fn capture_session<Ui: GameBackend + ?Sized>(
+    game: &Mutex<Gol>,
+    editor: &Mutex<Editor>,
+    ui: &Ui,
+    delay: &AtomicU64,
+) -> Session {
+    let game = game.lock().unwrap();
+    let editor = editor.lock().unwrap();
+    session_of(&game, &editor, ui.viewport(), delay.load(Ordering::Relaxed))
+}
+
+/// A session of the game and editor, with the screen showing from `viewport` and
+/// `delay` milliseconds between generations.
+pub fn session_of(game: &Gol, editor: &Editor, viewport: (usize, usize), delay: u64) -> Session {
+    let (undo, redo) = editor.history(game.grid());
+    Session {
+        grid: game.grid().clone(),
+        generation: game.generation(),
+        rule: game.rule().clone(),
+        topology: game.topology(),
+        cursor: editor.get_cursor(),
+        viewport,
+        delay,
+        undo,
+        redo,
+    }
+}
@@ -0,0 +1,206 @@
+//! Cells covered by simple shapes, for the editor's drawing tools.
+
+/// A shape that can be drawn between two points.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// A straight line from one point to the other.
+    Line,
+    /// The outline of a rectangle.
+    Rectangle,
+    /// A solid rectangle.
+    FilledRectangle,
+    /// The outline of the ellipse that fits in a rectangle.
+    Ellipse,
+    /// The ellipse that fits in a rectangle, filled in.
+    FilledEllipse,
+}
+
+type Point = (usize, usize);
+
+impl Shape {
+    /// The cells covered by the shape drawn from one point to another. For rectangles and
+    /// ellipses the points are opposite corners of the bounding box.
+    pub fn cells(self, from: Point, to: Point) -> Vec<Point> {
+        match self {
+            Shape::Line => line(from, to),
+            Shape::Rectangle => rectangle(from, to, false),
+            Shape::FilledRectangle => rectangle(from, to, true),
+            Shape::Ellipse => ellipse(from, to, false),
+            Shape::FilledEllipse => ellipse(from, to, true),
+        }
+    }
+}
+
+/// Bresenham's line algorithm.
+fn line(from: Point, to: Point) -> Vec<Point> {
+    let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+    let (x1, y1) = (to.0 as isize, to.1 as isize);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut cells = Vec::new();
+    loop {
+        cells.push((x as usize, y as usize));
+        if x == x1 && y == y1 {
+            return cells;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+fn rectangle(from: Point, to: Point, filled: bool) -> Vec<Point> {
+    let (x0, x1) = (from.0.min(to.0), from.0.max(to.0));
+    let (y0, y1) = (from.1.min(to.1), from.1.max(to.1));
+    let mut cells = Vec::new();
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            if filled || x == x0 || x == x1 || y == y0 || y == y1 {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+/// Alois Zingl's rasterisation of an ellipse inscribed in a rectangle, which handles
+/// both odd and even diameters.
+fn ellipse(from: Point, to: Point, filled: bool) -> Vec<Point> {
+    let mut cells = Vec::new();
+    let mut span = |x0: isize, x1: isize, y: isize| {
+        if filled {
+            cells.extend((x0..=x1).map(|x| (x, y)));
+        } else {
+            cells.push((x0, y));
+            cells.push((x1, y));
+        }
+    };
+    let (mut x0, mut x1) = (from.0.min(to.0) as isize, from.0.max(to.0) as isize);
+    let (top, bottom) = (from.1.min(to.1) as isize, from.1.max(to.1) as isize);
+    let a = x1 - x0;
+    let b = bottom - top;
+    let b1 = b & 1;
+    let mut dx = 4 * (1 - a) * b * b;
+    let mut dy = 4 * (b1 + 1) * a * a;
+    let mut err = dx + dy + b1 * a * a;
+    let mut y0 = top + (b + 1) / 2;
+    let mut y1 = y0 - b1;
+    let a8 = 8 * a * a;
+    let b8 = 8 * b * b;
+    loop {
+        span(x0, x1, y0);
+        span(x0, x1, y1);
+        let e2 = 2 * err;
+        if e2 <= dy {
+            y0 += 1;
+            y1 -= 1;
+            dy += a8;
+            err += dy;
+        }
+        if e2 >= dx || 2 * err > dy {
+            x0 += 1;
+            x1 -= 1;
+            dx += b8;
+            err += dx;
+        }
+        if x0 > x1 {
+            break;
+        }
+    }
+    // flat ellipses stop early, so finish off the tips
+    while y0 - y1 < b {
+        span(x0 - 1, x1 + 1, y0);
+        span(x0 - 1, x1 + 1, y1);
+        y0 += 1;
+        y1 -= 1;
+    }
+    let mut cells: Vec<Point> = cells
+        .into_iter()
+        .filter(|&(x, y)| x >= 0 && y >= 0)
+        .map(|(x, y)| (x as usize, y as usize))
+        .collect();
+    cells.sort();
+    cells.dedup();
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_step_one_cell_at_a_time() {
+        assert_eq!(line((0, 0), (3, 0)), [(0, 0), (1, 0), (2, 0), (3, 0)]);
+        assert_eq!(line((3, 3), (1, 1)), [(3, 3), (2, 2), (1, 1)]);
+        assert_eq!(line((0, 0), (1, 3)), [(0, 0), (0, 1), (1, 2), (1, 3)]);
+        assert_eq!(line((2, 2), (2, 2)), [(2, 2)]);
+        for &to in &[(7, 2), (2, 7), (0, 5), (9, 9)] {
+            let cells = line((4, 3), to);
+            assert_eq!((cells[0], *cells.last().unwrap()), ((4, 3), to));
+            for pair in cells.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                assert!(
+                    a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1,
+                    "{:?}",
+                    pair
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rectangles_are_the_same_from_any_corner() {
+        let outline = Shape::Rectangle.cells((3, 2), (0, 0));
+        assert_eq!(outline, Shape::Rectangle.cells((0, 0), (3, 2)));
+        assert_eq!(outline.len(), 10);
+        assert!(!outline.contains(&(1, 1)));
+        assert_eq!(Shape::FilledRectangle.cells((0, 2), (3, 0)).len(), 12);
+    }
+
+    #[test]
+    fn ellipses_touch_each_side_of_their_box() {
+        let circle = Shape::Ellipse.cells((0, 0), (4, 4));
+        let expected = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (1, 0),
+            (1, 4),
+            (2, 0),
+            (2, 4),
+            (3, 0),
+            (3, 4),
+            (4, 1),
+            (4, 2),
+            (4, 3),
+        ];
+        assert_eq!(circle, expected);
+        let filled = Shape::FilledEllipse.cells((4, 4), (0, 0));
+        assert_eq!(filled.len(), 21);
+        assert!(circle.iter().all(|cell| filled.contains(cell)));
+        assert!(filled.contains(&(2, 2)));
+    }
+
+    #[test]
+    fn ellipses_with_even_diameters_are_symmetric() {
+        let ellipse = Shape::Ellipse.cells((1, 1), (6, 4));
+        for &(x, y) in &ellipse {
+            assert!(ellipse.contains(&(7 - x, y)) && ellipse.contains(&(x, 5 - y)));
+            assert!((1..=6).contains(&x) && (1..=4).contains(&y));
+        }
+        for x in 1..=6 {
+            assert!(ellipse.iter().any(|&(i, _)| i == x));
+        }
+        // a flat one is a line
+        assert_eq!(Shape::Ellipse.cells((0, 0), (3, 0)), line((0, 0), (3, 0)));
+    }
+}
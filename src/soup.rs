@@ -0,0 +1,288 @@
+//! Random soups, which can be made again from their seed.
+
+use grid::Grid;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::iter;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Symmetries for random soups, named as in apgsearch.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    /// No symmetry.
+    C1,
+    /// Unchanged by a half turn.
+    C2,
+    /// Unchanged by a quarter turn.
+    C4,
+    /// Mirrored left to right.
+    D2,
+    /// Mirrored left to right and top to bottom.
+    D4,
+    /// Unchanged by any rotation or reflection of the square.
+    D8,
+}
+
+static SYMMETRIES: [Symmetry; 6] = [
+    Symmetry::C1,
+    Symmetry::C2,
+    Symmetry::C4,
+    Symmetry::D2,
+    Symmetry::D4,
+    Symmetry::D8,
+];
+
+impl Symmetry {
+    /// The symmetry after this one, for cycling through them all.
+    pub fn next(self) -> Symmetry {
+        let i = SYMMETRIES.iter().position(|&s| s == self).unwrap();
+        SYMMETRIES[(i + 1) % SYMMETRIES.len()]
+    }
+
+    fn is_square(self) -> bool {
+        matches!(self, Symmetry::C4 | Symmetry::D8)
+    }
+
+    /// The cells of a `width` x `height` region that `(x, y)` is mapped to by the symmetry,
+    /// including itself.
+    fn orbit(self, x: usize, y: usize, width: usize, height: usize) -> Vec<(usize, usize)> {
+        let (mx, my) = (width - 1 - x, height - 1 - y);
+        match self {
+            Symmetry::C1 => vec![(x, y)],
+            Symmetry::C2 => vec![(x, y), (mx, my)],
+            Symmetry::C4 => vec![(x, y), (my, x), (mx, my), (y, mx)],
+            Symmetry::D2 => vec![(x, y), (mx, y)],
+            Symmetry::D4 => vec![(x, y), (mx, y), (x, my), (mx, my)],
+            Symmetry::D8 => vec![
+                (x, y),
+                (my, x),
+                (mx, my),
+                (y, mx),
+                (mx, y),
+                (x, my),
+                (y, x),
+                (my, mx),
+            ],
+        }
+    }
+}
+
+/// Why a symmetry couldn't be read.
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    msg: String,
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Invalid soup: {}", self.msg)
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Symmetry, Error> {
+        SYMMETRIES
+            .iter()
+            .cloned()
+            .find(|symmetry| symmetry.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error {
+                msg: format!("unknown symmetry {:?}", s),
+            })
+    }
+}
+
+impl Display for Symmetry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// SplitMix64, which is tiny and gives the same soup for a seed on every platform.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The settings for a random soup: the `seed` of the random number generator, the
+/// `density` of live cells from 0 to 1, and the `symmetry`. The same settings always give
+/// the same soup.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct Soup {
+    pub seed: u64,
+    pub density: f64,
+    pub symmetry: Symmetry,
+}
+
+impl Soup {
+    /// The soup with the given settings.
+    pub fn new(seed: u64, density: f64, symmetry: Symmetry) -> Soup {
+        Soup {
+            seed,
+            density,
+            symmetry,
+        }
+    }
+
+    /// A seed taken from the clock.
+    pub fn random_seed() -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Rng(now.as_secs() ^ u64::from(now.subsec_nanos())).next_u64()
+    }
+
+    /// Seeds for any number of soups, which are all chosen again from the same `seed`.
+    pub fn seeds(seed: u64) -> impl Iterator<Item = u64> {
+        let mut rng = Rng(seed);
+        iter::repeat_with(move || rng.next_u64())
+    }
+
+    /// Fills the `width` x `height` region at `(x, y)` with the soup. Soups with C4 or D8
+    /// symmetry fill the largest square centred in the region.
+    pub fn fill(&self, grid: &mut Grid<u8>, x: usize, y: usize, width: usize, height: usize) {
+        let (mut x, mut y, mut width, mut height) = (x, y, width, height);
+        if self.symmetry.is_square() {
+            let side = width.min(height);
+            x += (width - side) / 2;
+            y += (height - side) / 2;
+            width = side;
+            height = side;
+        }
+        let mut rng = Rng(self.seed);
+        for j in 0..height {
+            for i in 0..width {
+                // cells are visited in reading order, so the first cell of each orbit is
+                // the one that gets a random state and the others copy it
+                let first = self
+                    .symmetry
+                    .orbit(i, j, width, height)
+                    .into_iter()
+                    .min_by_key(|&(i, j)| (j, i))
+                    .unwrap();
+                let value = if first == (i, j) {
+                    (rng.next_f64() < self.density) as u8
+                } else {
+                    grid.get(x + first.0, y + first.1)
+                };
+                grid.set(x + i, y + j, value);
+            }
+        }
+    }
+}
+
+impl Display for Soup {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "seed {} {:.0}% {}",
+            self.seed,
+            self.density * 100.0,
+            self.symmetry
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A board of `size` filled with a soup.
+    fn soup(seed: u64, density: f64, symmetry: Symmetry, size: (usize, usize)) -> Grid<u8> {
+        let (width, height) = size;
+        let mut grid = Grid::new(width, height);
+        Soup::new(seed, density, symmetry).fill(&mut grid, 0, 0, width, height);
+        grid
+    }
+
+    #[test]
+    fn the_generator_is_splitmix64() {
+        let mut rng = Rng(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        let seeds: Vec<u64> = Soup::seeds(0).take(2).collect();
+        assert_eq!(seeds, [0xe220_a839_7b1d_cdaf, 0x6e78_9e6a_a1b9_65f4]);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_soup() {
+        let first = soup(42, 0.5, Symmetry::C1, (16, 16));
+        assert_eq!(soup(42, 0.5, Symmetry::C1, (16, 16)), first);
+        assert_ne!(soup(43, 0.5, Symmetry::C1, (16, 16)), first);
+        let population = (0..16)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .filter(|&(x, y)| first.get(x, y) != 0)
+            .count();
+        assert!(population > 64 && population < 192, "{}", population);
+    }
+
+    #[test]
+    fn density_is_the_chance_of_each_cell_being_alive() {
+        assert_eq!(soup(1, 0.0, Symmetry::C1, (8, 8)), Grid::new(8, 8));
+        assert_eq!(
+            soup(1, 1.0, Symmetry::D4, (3, 2)),
+            Grid::with_data(3, 2, vec![1; 6])
+        );
+    }
+
+    #[test]
+    fn each_symmetry_maps_the_soup_onto_itself() {
+        for &symmetry in &SYMMETRIES {
+            let (width, height) = if symmetry.is_square() { (9, 9) } else { (9, 6) };
+            let grid = soup(7, 0.5, symmetry, (width, height));
+            for y in 0..height {
+                for x in 0..width {
+                    for (i, j) in symmetry.orbit(x, y, width, height) {
+                        assert_eq!(grid.get(i, j), grid.get(x, y), "{} {} {}", symmetry, x, y);
+                    }
+                }
+            }
+        }
+        // D4 is mirrored both ways, but not turned
+        let grid = soup(7, 0.5, Symmetry::D4, (8, 5));
+        for y in 0..5 {
+            for x in 0..8 {
+                assert_eq!(grid.get(x, y), grid.get(7 - x, y));
+                assert_eq!(grid.get(x, y), grid.get(x, 4 - y));
+            }
+        }
+    }
+
+    #[test]
+    fn square_symmetries_fill_a_square_in_the_middle() {
+        let mut grid = Grid::new(10, 6);
+        Soup::new(3, 1.0, Symmetry::C4).fill(&mut grid, 0, 0, 10, 6);
+        for y in 0..6 {
+            for x in 0..10 {
+                assert_eq!(grid.get(x, y), (2..8).contains(&x) as u8, "{} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetries_are_read_by_name_in_any_case() {
+        for &symmetry in &SYMMETRIES {
+            assert_eq!(symmetry.to_string().parse(), Ok(symmetry));
+            assert_eq!(symmetry.to_string().to_lowercase().parse(), Ok(symmetry));
+        }
+        assert!("D6".parse::<Symmetry>().is_err());
+        assert_eq!(Symmetry::D8.next(), Symmetry::C1);
+    }
+}
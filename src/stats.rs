@@ -0,0 +1,287 @@
+//! Statistics for each generation, for plotting how a pattern grows.
+
+use grid::Grid;
+use pattern::extension;
+use serde_json::json;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Statistics for one generation, starting with its number and population.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct Stats {
+    pub generation: u64,
+    pub population: usize,
+    /// Cells that came alive since the generation before.
+    pub births: usize,
+    /// Cells that died since the generation before.
+    pub deaths: usize,
+    /// The smallest rectangle containing every live cell, as `(x, y, width, height)`.
+    pub bounding_box: Option<(usize, usize, usize, usize)>,
+    /// The proportion of the board that is alive.
+    pub density: f64,
+}
+
+impl Stats {
+    /// The statistics for `grid`, with no births or deaths.
+    pub fn of(generation: u64, grid: &Grid<u8>) -> Stats {
+        let mut population = 0;
+        let (mut x0, mut y0, mut x1, mut y1) = (usize::MAX, usize::MAX, 0, 0);
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if grid.get(x, y) != 0 {
+                    population += 1;
+                    x0 = x0.min(x);
+                    y0 = y0.min(y);
+                    x1 = x1.max(x);
+                    y1 = y1.max(y);
+                }
+            }
+        }
+        let area = grid.width() * grid.height();
+        Stats {
+            generation,
+            population,
+            births: 0,
+            deaths: 0,
+            bounding_box: if population > 0 {
+                Some((x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+            } else {
+                None
+            },
+            density: if area > 0 {
+                population as f64 / area as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Why statistics couldn't be saved.
+#[derive(Debug)]
+pub enum Error {
+    /// Writing the file failed.
+    Io(io::Error),
+    /// The file extension isn't one of the formats.
+    UnknownFormat(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::UnknownFormat(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::UnknownFormat(ext) => write!(f, "Unknown statistics format: {:?}", ext),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Error {
+        Error::Io(other)
+    }
+}
+
+/// How statistics are written.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// A header line, then a line of comma separated values per generation.
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl Format {
+    /// The format for a file extension: `.csv`, or `.jsonl` or `.json` for JSON lines.
+    pub fn from_path(path: &Path) -> Result<Format, Error> {
+        let ext = extension(path);
+        match ext.as_str() {
+            "csv" => Ok(Format::Csv),
+            "jsonl" | "json" => Ok(Format::JsonLines),
+            _ => Err(Error::UnknownFormat(ext)),
+        }
+    }
+}
+
+fn write_csv<'a, W, I>(out: &mut W, stats: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Stats>,
+{
+    writeln!(
+        out,
+        "generation,population,births,deaths,x,y,width,height,density"
+    )?;
+    for s in stats {
+        let bounds = match s.bounding_box {
+            Some((x, y, w, h)) => format!("{},{},{},{}", x, y, w, h),
+            None => ",,,".to_owned(),
+        };
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            s.generation, s.population, s.births, s.deaths, bounds, s.density
+        )?;
+    }
+    Ok(())
+}
+
+fn write_json_lines<'a, W, I>(out: &mut W, stats: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Stats>,
+{
+    for s in stats {
+        let bounds = s
+            .bounding_box
+            .map(|(x, y, w, h)| json!({"x": x, "y": y, "width": w, "height": h}));
+        let line = json!({
+            "generation": s.generation,
+            "population": s.population,
+            "births": s.births,
+            "deaths": s.deaths,
+            "bounding_box": bounds,
+            "density": s.density,
+        });
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Writes the statistics in the given format.
+pub fn write<'a, W, I>(out: &mut W, format: Format, stats: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Stats>,
+{
+    match format {
+        Format::Csv => write_csv(out, stats),
+        Format::JsonLines => write_json_lines(out, stats),
+    }
+}
+
+/// Saves the statistics as CSV or JSON lines, depending on the extension of `path`.
+pub fn save<'a, I>(path: &Path, stats: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a Stats>,
+{
+    let format = Format::from_path(path)?;
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out, format, stats)?;
+    out.flush()?;
+    Ok(())
+}
+
+static SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A tiny bar chart of `values`, one character each, scaled between the smallest and
+/// largest of them.
+pub fn sparkline(values: &[usize]) -> String {
+    let min = values.iter().cloned().min().unwrap_or(0);
+    let max = values.iter().cloned().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&value| match max - min {
+            0 => SPARKS[0],
+            range => SPARKS[(value - min) * (SPARKS.len() - 1) / range],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn written(format: Format, stats: &[Stats]) -> String {
+        let mut out = Vec::new();
+        write(&mut out, format, stats).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn stats() -> Vec<Stats> {
+        let grid = Grid::with_data(4, 2, vec![0, 1, 1, 0, 0, 0, 1, 0]);
+        vec![
+            Stats::of(0, &Grid::new(4, 2)),
+            Stats {
+                births: 3,
+                deaths: 1,
+                ..Stats::of(1, &grid)
+            },
+        ]
+    }
+
+    #[test]
+    fn statistics_are_counted_from_the_grid() {
+        let stats = stats();
+        assert_eq!(stats[0].population, 0);
+        assert_eq!(stats[0].bounding_box, None);
+        assert_eq!(stats[0].density, 0.0);
+        assert_eq!(stats[1].population, 3);
+        assert_eq!(stats[1].bounding_box, Some((1, 0, 2, 2)));
+        assert_eq!(stats[1].density, 0.375);
+        assert_eq!(Stats::of(0, &Grid::new(0, 0)).density, 0.0);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_a_row_per_generation() {
+        assert_eq!(
+            written(Format::Csv, &stats()),
+            "generation,population,births,deaths,x,y,width,height,density\n\
+             0,0,0,0,,,,,0\n\
+             1,3,3,1,1,0,2,2,0.375\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_have_an_object_per_generation() {
+        let text = written(Format::JsonLines, &stats());
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                json!({"generation": 0, "population": 0, "births": 0, "deaths": 0,
+                       "bounding_box": null, "density": 0.0}),
+                json!({"generation": 1, "population": 3, "births": 3, "deaths": 1,
+                       "bounding_box": {"x": 1, "y": 0, "width": 2, "height": 2},
+                       "density": 0.375}),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_format_is_chosen_by_extension() {
+        assert_eq!(Format::from_path(Path::new("a.csv")).unwrap(), Format::Csv);
+        assert_eq!(
+            Format::from_path(Path::new("a.jsonl")).unwrap(),
+            Format::JsonLines
+        );
+        assert_eq!(
+            Format::from_path(Path::new("a.json")).unwrap(),
+            Format::JsonLines
+        );
+        assert!(Format::from_path(Path::new("a.txt")).is_err());
+    }
+
+    #[test]
+    fn sparklines_are_scaled_between_the_smallest_and_largest() {
+        assert_eq!(sparkline(&[0, 7, 14, 7]), "▁▄█▄");
+        assert_eq!(sparkline(&[5, 5]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
@@ -0,0 +1,108 @@
+//! The status line, and drawing the screen for each state.
+
+use crate::GameState;
+use backend::{self, GameBackend};
+use editor::Editor;
+use game::Gol;
+use library::Browser;
+use stats;
+use std::sync::Mutex;
+use topology::GridSpec;
+
+/// How many generations of population the status line graphs.
+const SPARKLINE_LENGTH: usize = 16;
+
+/// What the status line needs to know besides the game and editor.
+pub struct Status<'a> {
+    /// The state being shown, which is the one the console was opened from while it's open.
+    pub state: GameState,
+    /// The line being typed at the console.
+    pub prompt: Option<String>,
+    pub delay: u64,
+    /// Generations asked for at the console or by another program, still to run.
+    pub steps_left: u64,
+    pub recording: bool,
+    pub message: Option<&'a str>,
+}
+
+/// The line shown below the board.
+pub fn status_line(status: &Status, game: &Gol, editor: &Editor) -> String {
+    if let Some(prompt) = &status.prompt {
+        return prompt.clone();
+    }
+    let state = status.state;
+    if let Some(message) = editor.message().filter(|_| state == GameState::Editing) {
+        return message.to_owned();
+    }
+    if let Some(message) = status.message {
+        return message.to_owned();
+    }
+    let grid = GridSpec {
+        topology: game.topology(),
+        width: Some(game.grid().width()),
+        height: Some(game.grid().height()),
+    };
+    let mut line = format!(
+        "{:?} | gen {} | {} | {}",
+        state,
+        game.generation(),
+        game.rule(),
+        grid
+    );
+    let populations: Vec<usize> = game
+        .stats()
+        .iter()
+        .rev()
+        .take(SPARKLINE_LENGTH)
+        .rev()
+        .map(|stats| stats.population)
+        .collect();
+    if let Some(&population) = populations.last() {
+        line += &format!(" | pop {} {}", population, stats::sparkline(&populations));
+    }
+    if let Some(cycle) = game.cycle() {
+        line += &format!(" | {}", cycle);
+    }
+    if status.steps_left > 0 {
+        line += &format!(" | stepping, {} to go", status.steps_left);
+    }
+    if status.delay > 0 {
+        line += &format!(" | delay {}ms", status.delay);
+    }
+    if status.recording {
+        line += " | recording";
+    }
+    if let Some(soup) = editor.last_soup() {
+        line += &format!(" | soup {}", soup);
+    }
+    line
+}
+
+/// Draws whatever the state shows, with the status line.
+pub fn draw_current_state<Ui: GameBackend + ?Sized>(
+    status: &Status,
+    game: &Mutex<Gol>,
+    editor: &Mutex<Editor>,
+    browser: &Browser,
+    ui: &mut Ui,
+) -> Result<(), backend::Error> {
+    match status.state {
+        GameState::Running | GameState::Paused => {
+            let game = game.lock().unwrap();
+            let editor = editor.lock().unwrap();
+            ui.draw_game(game.grid(), &status_line(status, &game, &editor))?;
+        }
+        GameState::Editing => {
+            let game = game.lock().unwrap();
+            let editor = editor.lock().unwrap();
+            let status = status_line(status, &game, &editor);
+            ui.draw_editor(&editor, game.grid(), &status)?;
+        }
+        GameState::Browsing => {
+            ui.draw_browser(browser)?;
+        }
+        // the state shown is never the console itself
+        GameState::Console => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,455 @@
+//! The shapes a bounded board can be joined into, and Golly's syntax for them.
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// How the edges of a bounded grid are joined together.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Topology {
+    /// No edges are joined; cells beyond the edges are always dead.
+    Plane,
+    /// Left and right edges are joined, as are top and bottom.
+    Torus,
+    /// Only the left and right edges are joined.
+    HorizontalCylinder,
+    /// Only the top and bottom edges are joined.
+    VerticalCylinder,
+    /// Like a torus, except that one pair of edges is joined with a twist.
+    KleinBottle(Twist),
+    /// Both pairs of edges are joined with a twist (a projective plane). The corners
+    /// aren't joined to anything.
+    CrossSurface,
+    /// The top edge is joined to the left edge and the bottom edge to the right edge.
+    /// Only meaningful for square grids. The corners aren't joined to anything.
+    Sphere,
+}
+
+/// Which pair of edges of a Klein bottle is twisted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Twist {
+    /// Crossing the top or bottom edge mirrors the x coordinate. Written `K100*,50`.
+    TopBottom,
+    /// Crossing the left or right edge mirrors the y coordinate. Written `K100,50*`.
+    LeftRight,
+}
+
+impl Topology {
+    /// Whether a pattern that moves off one edge comes back on the opposite edge the same
+    /// way up, for the left and right edges and for the top and bottom edges.
+    pub fn translates(self) -> (bool, bool) {
+        use self::Topology::*;
+        match self {
+            Torus => (true, true),
+            HorizontalCylinder | KleinBottle(Twist::TopBottom) => (true, false),
+            VerticalCylinder | KleinBottle(Twist::LeftRight) => (false, true),
+            Plane | CrossSurface | Sphere => (false, false),
+        }
+    }
+
+    /// Maps a possibly out-of-range position onto the grid, or `None` if the position
+    /// lies beyond an edge that isn't joined to anything.
+    pub fn wrap(self, x: isize, y: isize, width: usize, height: usize) -> Option<(usize, usize)> {
+        use self::Topology::*;
+        let (w, h) = (width as isize, height as isize);
+        let (mut x, mut y) = (x, y);
+        let outside_x = x < 0 || x >= w;
+        let outside_y = y < 0 || y >= h;
+        match self {
+            Plane => {}
+            Torus => {
+                x = x.rem_euclid(w);
+                y = y.rem_euclid(h);
+            }
+            HorizontalCylinder => x = x.rem_euclid(w),
+            VerticalCylinder => y = y.rem_euclid(h),
+            KleinBottle(Twist::TopBottom) => {
+                if outside_y {
+                    x = w - 1 - x;
+                }
+                x = x.rem_euclid(w);
+                y = y.rem_euclid(h);
+            }
+            KleinBottle(Twist::LeftRight) => {
+                if outside_x {
+                    y = h - 1 - y;
+                }
+                x = x.rem_euclid(w);
+                y = y.rem_euclid(h);
+            }
+            CrossSurface => match (outside_x, outside_y) {
+                (true, true) => return None,
+                (false, true) => {
+                    x = w - 1 - x;
+                    y = y.rem_euclid(h);
+                }
+                (true, false) => {
+                    y = h - 1 - y;
+                    x = x.rem_euclid(w);
+                }
+                (false, false) => {}
+            },
+            Sphere => match (outside_x, outside_y) {
+                (true, true) => return None,
+                // each row beyond the top edge is a column in from the left edge, and
+                // so on
+                (false, true) if y < 0 => (x, y) = (-1 - y, x),
+                (false, true) => (x, y) = (w - 1 - (y - h), x),
+                (true, false) if x < 0 => (x, y) = (y, -1 - x),
+                (true, false) => (x, y) = (y, h - 1 - (x - w)),
+                (false, false) => {}
+            },
+        }
+        if x >= 0 && y >= 0 && x < w && y < h {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+}
+
+/// Why a bounded grid couldn't be read.
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    msg: String,
+}
+
+impl Error {
+    fn new(msg: String) -> Error {
+        Error { msg }
+    }
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Invalid bounded grid: {}", self.msg)
+    }
+}
+
+/// A topology together with the grid size, as written in Golly's bounded grid syntax,
+/// e.g. `T100,50` or `K100*,50`. A dimension of `None` (written as `0`) means the grid
+/// should use whatever size the board already has in that direction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct GridSpec {
+    pub topology: Topology,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+}
+
+impl GridSpec {
+    /// A grid with the given topology that takes the size of the board.
+    pub fn new(topology: Topology) -> GridSpec {
+        GridSpec {
+            topology,
+            width: None,
+            height: None,
+        }
+    }
+}
+
+fn parse_dimension(s: &str) -> Result<(usize, bool), Error> {
+    let (digits, twisted) = match s.strip_suffix('*') {
+        Some(digits) => (digits, true),
+        None => (s, false),
+    };
+    if digits.contains('+') || digits.contains('-') {
+        return Err(Error::new(format!(
+            "shifted edges are not supported: {}",
+            s
+        )));
+    }
+    digits
+        .parse()
+        .map(|n| (n, twisted))
+        .map_err(|_| Error::new(format!("bad dimension: {:?}", s)))
+}
+
+impl FromStr for GridSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<GridSpec, Error> {
+        let s = s.trim();
+        let kind = s
+            .chars()
+            .next()
+            .ok_or_else(|| Error::new("empty specifier".to_owned()))?;
+        let mut dims = s[kind.len_utf8()..].splitn(2, ',');
+        let (width, width_twisted) = parse_dimension(dims.next().unwrap_or(""))?;
+        let (height, height_twisted) = match dims.next() {
+            Some(h) => parse_dimension(h)?,
+            None => (width, false),
+        };
+        if (width_twisted || height_twisted) && !kind.eq_ignore_ascii_case(&'K') {
+            return Err(Error::new(format!(
+                "only a Klein bottle can be twisted: {}",
+                s
+            )));
+        }
+        let topology = match kind.to_ascii_uppercase() {
+            'P' => Topology::Plane,
+            // `T0,0` is a torus the size of the board, rather than a cylinder
+            'T' => match (width, height) {
+                (0, 0) => Topology::Torus,
+                (_, 0) => Topology::HorizontalCylinder,
+                (0, _) => Topology::VerticalCylinder,
+                _ => Topology::Torus,
+            },
+            'K' => match (width_twisted, height_twisted) {
+                (true, false) => Topology::KleinBottle(Twist::TopBottom),
+                (false, true) => Topology::KleinBottle(Twist::LeftRight),
+                _ => {
+                    return Err(Error::new(format!(
+                        "exactly one dimension of a Klein bottle must be twisted: {}",
+                        s
+                    )))
+                }
+            },
+            'C' => Topology::CrossSurface,
+            'S' => Topology::Sphere,
+            _ => return Err(Error::new(format!("unknown topology {:?}", kind))),
+        };
+        match topology {
+            Topology::KleinBottle(_) | Topology::CrossSurface | Topology::Sphere
+                if width == 0 || height == 0 =>
+            {
+                return Err(Error::new(format!("dimensions must be non-zero: {}", s)));
+            }
+            Topology::Sphere if width != height => {
+                return Err(Error::new(format!("a sphere must be square: {}", s)));
+            }
+            _ => {}
+        }
+        let non_zero = |n| if n == 0 { None } else { Some(n) };
+        Ok(GridSpec {
+            topology,
+            width: non_zero(width),
+            height: non_zero(height),
+        })
+    }
+}
+
+impl Display for GridSpec {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let w = self.width.unwrap_or(0);
+        let h = self.height.unwrap_or(0);
+        match self.topology {
+            Topology::Plane => write!(f, "P{},{}", w, h),
+            Topology::Torus => write!(f, "T{},{}", w, h),
+            Topology::HorizontalCylinder => write!(f, "T{},0", w),
+            Topology::VerticalCylinder => write!(f, "T0,{}", h),
+            Topology::KleinBottle(Twist::TopBottom) => write!(f, "K{}*,{}", w, h),
+            Topology::KleinBottle(Twist::LeftRight) => write!(f, "K{},{}*", w, h),
+            Topology::CrossSurface => write!(f, "C{},{}", w, h),
+            Topology::Sphere => write!(f, "S{}", w),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(topology: Topology, width: Option<usize>, height: Option<usize>) -> GridSpec {
+        GridSpec {
+            topology,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn a_torus_without_a_size_takes_the_size_of_the_board() {
+        assert_eq!(
+            "T0,0".parse::<GridSpec>().unwrap(),
+            GridSpec::new(Topology::Torus)
+        );
+        assert_eq!(
+            "T0".parse::<GridSpec>().unwrap(),
+            GridSpec::new(Topology::Torus)
+        );
+    }
+
+    #[test]
+    fn a_single_zero_makes_a_cylinder() {
+        assert_eq!(
+            "T100,0".parse::<GridSpec>().unwrap(),
+            spec(Topology::HorizontalCylinder, Some(100), None)
+        );
+        assert_eq!(
+            "T0,50".parse::<GridSpec>().unwrap(),
+            spec(Topology::VerticalCylinder, None, Some(50))
+        );
+    }
+
+    #[test]
+    fn specs_are_written_as_they_are_read() {
+        let specs = [
+            "P0,0", "P100,50", "T0,0", "T100,50", "T100,0", "T0,50", "K100*,50", "K100,50*",
+            "C100,50", "S50",
+        ];
+        for text in specs {
+            let spec: GridSpec = text.parse().unwrap();
+            assert_eq!(spec.to_string(), text);
+            assert_eq!(spec.to_string().parse::<GridSpec>().unwrap(), spec);
+        }
+        let default = GridSpec::new(Topology::Torus);
+        assert_eq!(default.to_string().parse::<GridSpec>().unwrap(), default);
+    }
+
+    /// Where each position just off a 4 x 3 board goes: the four edges, then the four
+    /// corners, clockwise from the top left.
+    fn edges_and_corners(topology: Topology) -> Vec<Option<(usize, usize)>> {
+        let positions = [
+            (1, -1),
+            (4, 1),
+            (1, 3),
+            (-1, 1),
+            (-1, -1),
+            (4, -1),
+            (4, 3),
+            (-1, 3),
+        ];
+        positions
+            .iter()
+            .map(|&(x, y)| topology.wrap(x, y, 4, 3))
+            .collect()
+    }
+
+    #[test]
+    fn cells_on_the_board_stay_where_they_are() {
+        let topologies = [
+            Topology::Plane,
+            Topology::Torus,
+            Topology::HorizontalCylinder,
+            Topology::VerticalCylinder,
+            Topology::KleinBottle(Twist::TopBottom),
+            Topology::KleinBottle(Twist::LeftRight),
+            Topology::CrossSurface,
+        ];
+        for &topology in &topologies {
+            for y in 0..3 {
+                for x in 0..4 {
+                    let wrapped = topology.wrap(x as isize, y as isize, 4, 3);
+                    assert_eq!(wrapped, Some((x, y)), "{:?}", topology);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edges_are_joined_as_golly_joins_them() {
+        let table = [
+            (
+                Topology::Plane,
+                [None, None, None, None, None, None, None, None],
+            ),
+            (
+                Topology::Torus,
+                [
+                    Some((1, 2)),
+                    Some((0, 1)),
+                    Some((1, 0)),
+                    Some((3, 1)),
+                    Some((3, 2)),
+                    Some((0, 2)),
+                    Some((0, 0)),
+                    Some((3, 0)),
+                ],
+            ),
+            (
+                Topology::HorizontalCylinder,
+                [
+                    None,
+                    Some((0, 1)),
+                    None,
+                    Some((3, 1)),
+                    None,
+                    None,
+                    None,
+                    None,
+                ],
+            ),
+            (
+                Topology::VerticalCylinder,
+                [
+                    Some((1, 2)),
+                    None,
+                    Some((1, 0)),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                ],
+            ),
+            // crossing the top or bottom mirrors x
+            (
+                Topology::KleinBottle(Twist::TopBottom),
+                [
+                    Some((2, 2)),
+                    Some((0, 1)),
+                    Some((2, 0)),
+                    Some((3, 1)),
+                    Some((0, 2)),
+                    Some((3, 2)),
+                    Some((3, 0)),
+                    Some((0, 0)),
+                ],
+            ),
+            // crossing the left or right mirrors y
+            (
+                Topology::KleinBottle(Twist::LeftRight),
+                [
+                    Some((1, 2)),
+                    Some((0, 1)),
+                    Some((1, 0)),
+                    Some((3, 1)),
+                    Some((3, 0)),
+                    Some((0, 0)),
+                    Some((0, 2)),
+                    Some((3, 2)),
+                ],
+            ),
+            // both are mirrored, and the corners aren't joined
+            (
+                Topology::CrossSurface,
+                [
+                    Some((2, 2)),
+                    Some((0, 1)),
+                    Some((2, 0)),
+                    Some((3, 1)),
+                    None,
+                    None,
+                    None,
+                    None,
+                ],
+            ),
+        ];
+        for (topology, expected) in table.iter() {
+            assert_eq!(edges_and_corners(*topology), expected, "{:?}", topology);
+        }
+    }
+
+    #[test]
+    fn a_sphere_joins_the_top_to_the_left_and_the_bottom_to_the_right() {
+        let wrap = |x, y| Topology::Sphere.wrap(x, y, 4, 4);
+        // above (1, 0) is beside (0, 1), and the other way round
+        assert_eq!(wrap(1, -1), Some((0, 1)));
+        assert_eq!(wrap(-1, 1), Some((1, 0)));
+        // below (2, 3) is beside (3, 2), and the other way round
+        assert_eq!(wrap(2, 4), Some((3, 2)));
+        assert_eq!(wrap(4, 2), Some((2, 3)));
+        // two rows beyond an edge is two columns in from the other
+        assert_eq!(wrap(1, -2), Some((1, 1)));
+        assert_eq!(wrap(-2, 1), Some((1, 1)));
+        assert_eq!(wrap(2, 5), Some((2, 2)));
+        assert_eq!(wrap(5, 2), Some((2, 2)));
+        for &(x, y) in &[(-1, -1), (4, -1), (4, 4), (-1, 4)] {
+            assert_eq!(wrap(x, y), None);
+        }
+        assert_eq!(wrap(2, 1), Some((2, 1)));
+    }
+}
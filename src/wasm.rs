@@ -0,0 +1,95 @@
+//! Bindings for JavaScript, for running the engine in web pages. What the board does is
+//! up to `Gol`, where it can be tested natively, so these only translate the calls.
+
+use game::Gol;
+use pattern::Pattern;
+use topology::Topology;
+use wasm_bindgen::prelude::*;
+
+/// A board and the rule it runs, as a JavaScript class.
+#[wasm_bindgen]
+pub struct Board {
+    gol: Gol,
+}
+
+#[wasm_bindgen]
+impl Board {
+    /// An empty board with Conway's rule, whose edges wrap round as a torus.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: usize, height: usize) -> Board {
+        Board {
+            gol: Gol::new(width, height, Topology::Torus),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.gol.grid().width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.gol.grid().height()
+    }
+
+    /// The generation as a float, which JavaScript numbers can hold exactly up to 2^53.
+    pub fn generation(&self) -> f64 {
+        self.gol.generation() as f64
+    }
+
+    pub fn population(&self) -> usize {
+        self.gol.population()
+    }
+
+    /// The rule in B/S notation, e.g. `B3/S23`.
+    pub fn rule(&self) -> String {
+        self.gol.rule().to_string()
+    }
+
+    #[wasm_bindgen(js_name = setRule)]
+    pub fn set_rule(&mut self, rule: &str) -> Result<(), JsError> {
+        self.gol.set_rule(rule.parse()?);
+        Ok(())
+    }
+
+    /// Runs the given number of generations.
+    pub fn step(&mut self, generations: u32) {
+        for _ in 0..generations {
+            self.gol.next_turn();
+        }
+    }
+
+    /// The cells row by row, one byte each: 1 if the cell is alive, 0 if it's dead.
+    pub fn cells(&self) -> Vec<u8> {
+        let grid = self.gol.grid();
+        let mut cells = Vec::with_capacity(grid.width() * grid.height());
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                cells.push(grid.get(x, y));
+            }
+        }
+        cells
+    }
+
+    /// Whether a cell is alive. Cells off the board are dead.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.gol.is_alive(x, y)
+    }
+
+    /// Brings a cell to life or kills it. Cells off the board are ignored.
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        self.gol.set_alive(x, y, alive);
+    }
+
+    /// Kills every cell.
+    pub fn clear(&mut self) {
+        self.gol.grid_mut().set_all(0);
+    }
+
+    /// Replaces the board with an RLE pattern in the middle of an empty one, back at
+    /// generation 0, and runs the rule it was designed for, if it gives one. Cells that
+    /// don't fit are left out.
+    #[wasm_bindgen(js_name = loadRle)]
+    pub fn load_rle(&mut self, rle: &str) -> Result<(), JsError> {
+        self.gol.restart_with(&Pattern::from_rle(rle)?);
+        Ok(())
+    }
+}